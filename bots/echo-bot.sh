@@ -0,0 +1,18 @@
+#!/bin/sh
+# The reference external player : it bets the smallest bet of the
+# table and takes the first legal action, to test the protocol.
+#
+#     blackjack --players human,external --external bots/echo-bot.sh
+
+while read -r line; do
+    case "$line" in
+        *'"type":"bet"'*)
+            min=$(echo "$line" | sed 's/.*"min":\([0-9]*\).*/\1/')
+            echo "{\"bet\": $min}"
+            ;;
+        *'"type":"action"'*)
+            action=$(echo "$line" | sed 's/.*"legal":\["\([a-z]*\)".*/\1/')
+            echo "{\"action\": \"$action\"}"
+            ;;
+    esac
+done
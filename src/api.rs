@@ -0,0 +1,1192 @@
+//! A local HTTP API driving tables, for web front ends and
+//! integration tests : `api` listens on `127.0.0.1`, on the port
+//! given by `--port`, and answers with JSON :
+//!
+//! - `POST /tables` creates a table from a table setup written in
+//!   JSON, with the keys of the table files ([TableConfig]), as in
+//!   `{"rules": {"decks": 6}, "seats": [{"player": "remote"},
+//!   {"player": "bot"}]}`. Seats are `remote`, taken by the players
+//!   of the API, or `bot`. The table and its `id` are returned.
+//! - `POST /tables/ID/join`, with `{"name": "Ann"}`, seats a player
+//!   at the next free remote seat, which is returned. The game starts
+//!   when every remote seat is taken.
+//! - `POST /tables/ID/bet`, with `{"seat": 0, "amount": 25}`, and
+//!   `POST /tables/ID/action`, with `{"seat": 0, "action": "hit"}`,
+//!   answer the question the table is waiting for.
+//! - `GET /tables/ID` returns the table : its `status` (`waiting`
+//!   for players, `playing` or `over`), its `rules` and `limits`, the
+//!   `round`, the `seats` with their `name`, `bank`, `bet` and `hands`,
+//!   the `dealer` hand and the question the table is `waiting_for`,
+//!   a `bet` or an `action` of a `seat`, with the `legal` actions.
+//! - `GET /tables/ID/history` returns the `lines` of the hand
+//!   history of the table, as written in [crate::history].
+//!
+//! The requests that move the game on answer once the table waits
+//! for the next answer, or is over, with the table. Errors are
+//! answered with a status code and `{"error": KIND, "message": ...}`,
+//! where the kind is one of those of [ErrorKind], as `illegal_action`
+//! for an action the rules of the round do not allow.
+//!
+//! The tables are bounded as described in [ApiSettings] : a player
+//! who does not answer in time bets the minimum or stands, a table
+//! whose players do not come is closed, the tables that are over
+//! are removed after a while, and the tables open at once are
+//! limited.
+
+use crate::betting::TableLimits;
+use crate::card::Card;
+use crate::cli::Args;
+use crate::config::TableConfig;
+use crate::external::cards;
+use crate::history::{Hand, Record};
+use crate::player::{PlayerAction, PlayerType};
+use crate::round::{play_round, settle_round, start_round, Table};
+use crate::save::GameState;
+use crate::server::RemoteSeats;
+use crate::setup::{check_name, GameSetup};
+use crate::strategy::strategy_from_name;
+use crate::utils::*;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// The port `api` listens on, unless `--port` says otherwise.
+pub const DEFAULT_API_PORT: u16 = 8080;
+
+/// The largest body a request may have, in bytes.
+const MAX_BODY: usize = 64 * 1024;
+
+/// The longest request line or header, in bytes.
+const MAX_LINE: usize = 8 * 1024;
+
+/// The most headers a request may have.
+const MAX_HEADERS: usize = 100;
+
+/// How long a client has to send each part of its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How the API bounds its tables.
+#[derive(Clone, Debug)]
+pub struct ApiSettings {
+    /// How long players have to answer each question, before they
+    /// bet the minimum or stand, as the remote players of a served
+    /// table ([crate::server]).
+    pub turn_timeout: Duration,
+    /// How long a table waits for its players before it is closed.
+    pub join_timeout: Duration,
+    /// How long a table that is over is kept, for its last state
+    /// and its history, before it is removed.
+    pub keep_over: Duration,
+    /// The most tables waiting for their players or playing at once.
+    pub max_tables: usize,
+}
+
+impl Default for ApiSettings {
+    fn default() -> ApiSettings {
+        ApiSettings {
+            turn_timeout: Duration::from_secs(60),
+            join_timeout: Duration::from_secs(600),
+            keep_over: Duration::from_secs(600),
+            max_tables: 100,
+        }
+    }
+}
+
+/// The kinds of errors of the API.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// The request cannot be read, or its body is not valid.
+    BadRequest,
+    NotFound,
+    MethodNotAllowed,
+    /// Every remote seat of the table is taken.
+    TableFull,
+    /// The table waits for its players.
+    NotStarted,
+    /// The table waits for another answer.
+    NotYourTurn,
+    /// The bet is not allowed by the limits of the table.
+    BetNotAllowed,
+    /// The action is not one of the legal actions of the hand.
+    IllegalAction,
+    GameOver,
+    /// The API plays as many tables as it may.
+    TooManyTables,
+}
+
+impl ErrorKind {
+    /// The HTTP status of the errors of the kind.
+    fn status(self) -> u16 {
+        match self {
+            ErrorKind::BadRequest => 400,
+            ErrorKind::NotFound => 404,
+            ErrorKind::MethodNotAllowed => 405,
+            ErrorKind::TableFull
+            | ErrorKind::NotStarted
+            | ErrorKind::NotYourTurn
+            | ErrorKind::GameOver => 409,
+            ErrorKind::BetNotAllowed | ErrorKind::IllegalAction => 422,
+            ErrorKind::TooManyTables => 503,
+        }
+    }
+}
+
+/// An error of the API, as it is answered.
+#[derive(Serialize, PartialEq, Eq, Debug)]
+pub struct ApiError {
+    pub error: ErrorKind,
+    pub message: String,
+}
+
+fn error(kind: ErrorKind, message: impl Into<String>) -> ApiError {
+    ApiError {
+        error: kind,
+        message: message.into(),
+    }
+}
+
+/// Whether a table waits for players, is played or is over.
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Waiting,
+    Playing,
+    Over,
+}
+
+/// The question a table is waiting for.
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Question {
+    Bet {
+        seat: usize,
+        /// The money the bet is taken from.
+        available: u32,
+        last_bet: Option<u32>,
+    },
+    Action {
+        seat: usize,
+        /// Whether the hand is the second one of a split.
+        second: bool,
+        legal: Vec<PlayerAction>,
+    },
+}
+
+/// An answer to the question of a table.
+#[derive(Clone, Copy, Debug)]
+enum Answer {
+    Bet(u32),
+    Action(PlayerAction),
+}
+
+/// A seat of a table, as the API shows it.
+#[derive(Serialize, Clone, Debug)]
+pub struct SeatState {
+    pub name: String,
+    pub player: String,
+    /// Whether a player took the seat, which bots always did.
+    pub joined: bool,
+    /// The bank of the seat at the end of the last round.
+    pub bank: u32,
+    /// The bet placed at the start of the round.
+    pub bet: u32,
+    /// The first hand of the seat, and the second one after a split.
+    pub hands: Vec<Vec<String>>,
+}
+
+/// A table, as the API shows it.
+#[derive(Serialize, Clone, Debug)]
+pub struct TableState {
+    pub id: usize,
+    pub status: Status,
+    pub rules: String,
+    pub limits: TableLimits,
+    pub round: u32,
+    pub seats: Vec<SeatState>,
+    pub dealer: Vec<String>,
+    pub waiting_for: Option<Question>,
+    /// The lines of the hand history.
+    #[serde(skip)]
+    history: Vec<String>,
+    #[serde(skip)]
+    answer: Option<Answer>,
+    /// Counts the questions asked, so that the requests
+    /// can wait for the next one.
+    #[serde(skip)]
+    turn: u64,
+    /// When the table was over.
+    #[serde(skip)]
+    over_since: Option<Instant>,
+}
+
+impl TableState {
+    fn new(id: usize, setup: &GameSetup) -> TableState {
+        TableState {
+            id,
+            status: Status::Waiting,
+            rules: setup.rules.to_string(),
+            limits: setup.limits.clone(),
+            round: 0,
+            seats: (0..setup.names.len())
+                .map(|seat| SeatState {
+                    name: setup.names[seat].clone(),
+                    player: setup.player_types[seat].to_string(),
+                    joined: setup.player_types[seat] != PlayerType::Remote,
+                    bank: setup.bankroll,
+                    bet: 0,
+                    hands: vec![Vec::new()],
+                })
+                .collect(),
+            dealer: Vec::new(),
+            waiting_for: None,
+            history: Vec::new(),
+            answer: None,
+            turn: 0,
+            over_since: None,
+        }
+    }
+
+    /// Follows an event of the game.
+    fn apply(&mut self, record: &Record) {
+        let names: Vec<String> = self.seats.iter().map(|seat| seat.name.clone()).collect();
+        // rounds start with an empty line in hand histories
+        self.history
+            .push(record.to_line(&names).trim_start().to_string());
+        match record {
+            Record::Round { number, .. } => {
+                self.round = *number;
+                self.dealer.clear();
+                for seat in &mut self.seats {
+                    seat.bet = 0;
+                    seat.hands = vec![Vec::new()];
+                }
+            }
+            Record::Bet { seat, amount } => self.seats[*seat].bet = *amount,
+            Record::Dealt { hand, card } => {
+                let card = cards(&[*card]).remove(0);
+                match hand {
+                    Hand::Player { seat, second } => {
+                        self.seats[*seat].hands[*second as usize].push(card)
+                    }
+                    Hand::Dealer => self.dealer.push(card),
+                }
+            }
+            Record::Action {
+                hand: Hand::Player { seat, .. },
+                action: PlayerAction::Split,
+            } => {
+                let hands = &mut self.seats[*seat].hands;
+                let card = hands[0].pop().unwrap();
+                hands.push(vec![card]);
+            }
+            Record::Banks(banks) => {
+                for (seat, &bank) in self.seats.iter_mut().zip(banks) {
+                    seat.bank = bank;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The question asked to a seat, if the table waits for its answer.
+    fn check_turn(&self, seat: usize) -> Result<&Question, ApiError> {
+        match self.status {
+            Status::Waiting => {
+                return Err(error(
+                    ErrorKind::NotStarted,
+                    "The table waits for its players",
+                ))
+            }
+            Status::Over => return Err(error(ErrorKind::GameOver, "The game is over")),
+            Status::Playing => {}
+        }
+        let question = match (&self.waiting_for, self.answer) {
+            (Some(question), None) => question,
+            _ => {
+                return Err(error(
+                    ErrorKind::NotYourTurn,
+                    "The table waits for the game",
+                ))
+            }
+        };
+        match question {
+            Question::Bet { seat: asked, .. } | Question::Action { seat: asked, .. }
+                if *asked != seat =>
+            {
+                Err(error(
+                    ErrorKind::NotYourTurn,
+                    format!("The table waits for seat {}", asked),
+                ))
+            }
+            _ => Ok(question),
+        }
+    }
+}
+
+/// A table shared between the thread playing it
+/// and the requests of the API.
+struct SharedTable {
+    state: Mutex<TableState>,
+    changed: Condvar,
+    /// How long the players have to answer.
+    timeout: Duration,
+}
+
+impl SharedTable {
+    fn lock(&self) -> MutexGuard<'_, TableState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Waits until the table asks a question after `turn`, or is over.
+    fn wait_after(&self, turn: u64) -> MutexGuard<'_, TableState> {
+        let mut state = self.lock();
+        while state.turn == turn && state.status != Status::Over {
+            state = self.changed.wait(state).unwrap();
+        }
+        state
+    }
+
+    /// Asks the players a question, and waits for its answer,
+    /// or returns None when they do not answer in time.
+    fn ask(&self, question: Question) -> Option<Answer> {
+        let mut state = self.lock();
+        state.waiting_for = Some(question);
+        state.answer = None;
+        state.turn += 1;
+        self.changed.notify_all();
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            if let Some(answer) = state.answer.take() {
+                state.waiting_for = None;
+                return Some(answer);
+            }
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                // the late answers are refused from now on
+                state.waiting_for = None;
+                return None;
+            }
+            state = self.changed.wait_timeout(state, left).unwrap().0;
+        }
+    }
+
+    /// Answers the question of the table, and waits for the next one.
+    /// The `state` must be locked from the moment the question was
+    /// checked, so that a question is answered only once.
+    fn answer(&self, mut state: MutexGuard<'_, TableState>, answer: Answer) -> TableState {
+        let turn = state.turn;
+        state.answer = Some(answer);
+        self.changed.notify_all();
+        drop(state);
+        self.wait_after(turn).clone()
+    }
+}
+
+/// The remote seats of a table played through the API.
+struct ApiSeats(Arc<SharedTable>);
+
+impl RemoteSeats for ApiSeats {
+    fn record(&self, record: &Record) {
+        self.0.lock().apply(record);
+    }
+
+    /// Asks the player of a seat for their bet. Players who
+    /// do not answer in time bet the smallest bet allowed.
+    fn bet(&self, seat: usize, limits: &TableLimits, available: u32, last_bet: Option<u32>) -> u32 {
+        match self.0.ask(Question::Bet {
+            seat,
+            available,
+            last_bet,
+        }) {
+            Some(Answer::Bet(bet)) => bet,
+            Some(Answer::Action(_)) => unreachable!("Bets are checked by the API"),
+            None => limits.clamp(0.0, available),
+        }
+    }
+
+    /// Asks the player of a seat for their action on a hand.
+    /// Players who do not answer in time stand.
+    fn action(
+        &self,
+        seat: usize,
+        (_, second): (&[Card], bool),
+        _: &[Card],
+        legal: &[PlayerAction],
+    ) -> PlayerAction {
+        match self.0.ask(Question::Action {
+            seat,
+            second,
+            legal: legal.to_vec(),
+        }) {
+            Some(Answer::Action(action)) => action,
+            Some(Answer::Bet(_)) => unreachable!("Actions are checked by the API"),
+            None => PlayerAction::Stand,
+        }
+    }
+}
+
+/// Plays a table once its players joined, until one of them runs
+/// out of money or the rounds are played. The table is over when
+/// its players do not join within the `join_timeout`.
+fn play_table(
+    mut setup: GameSetup,
+    table: Arc<SharedTable>,
+    join_timeout: Duration,
+) -> Result<(), String> {
+    {
+        let deadline = Instant::now() + join_timeout;
+        let mut state = table.lock();
+        while state.status == Status::Waiting {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                state.status = Status::Over;
+                return Ok(());
+            }
+            state = table.changed.wait_timeout(state, left).unwrap().0;
+        }
+        setup.names = state.seats.iter().map(|seat| seat.name.clone()).collect();
+    }
+    seed_shuffles(setup.seed.unwrap_or_else(rand::random));
+    let seats = ApiSeats(Arc::clone(&table));
+    let mut state = GameState::new(&setup);
+    let mut dealer_hand = Vec::new();
+    let mut bets = Vec::new();
+    let strategies = setup
+        .strategies
+        .iter()
+        .map(|name| strategy_from_name(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    while is_playable(&state.bank)
+        && setup
+            .rounds
+            .map_or(true, |rounds| (state.history.len() as u32 - 1) < rounds)
+    {
+        let table = Table {
+            limits: &setup.limits,
+            rules: &setup.rules,
+            names: &setup.names,
+            tui: None,
+            last_bets: &state.last_bets,
+            stats: &[],
+            history: &state.history,
+            saved: None,
+            log: None,
+            externals: &[],
+            server: Some(&seats),
+        };
+        start_round(&mut state.pack, state.history.len() as u32, &table);
+        let turns = play_round(
+            &mut state.player_hands,
+            &mut dealer_hand,
+            &mut state.pack,
+            &setup.player_types,
+            (&mut bets, &mut state.bank),
+            (&mut state.bet_policies, &strategies),
+            &table,
+        )
+        .expect("The tables of the API have no humans to quit");
+        let settled = settle_round(
+            &state.player_hands,
+            &dealer_hand,
+            &bets,
+            &turns,
+            &mut state.bank,
+            &mut state.bet_policies,
+            &table,
+        );
+        state.last_bets = settled.last_bets;
+        state.history.push(state.bank.clone());
+
+        for hand in state.player_hands.iter_mut() {
+            hand.0.clear();
+            hand.1 = None
+        }
+        dealer_hand.clear();
+        bets.clear();
+    }
+    Ok(())
+}
+
+/// The tables of the API, by id from 1.
+#[derive(Default)]
+struct Tables {
+    by_id: HashMap<usize, Arc<SharedTable>>,
+    /// The number of tables created, whose ids are not reused.
+    created: usize,
+}
+
+/// The tables of the API, bounded by its settings.
+#[derive(Default)]
+pub struct Api {
+    tables: Mutex<Tables>,
+    settings: ApiSettings,
+}
+
+#[derive(Deserialize)]
+struct JoinRequest {
+    #[serde(default)]
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct BetRequest {
+    seat: usize,
+    amount: u32,
+}
+
+#[derive(Deserialize)]
+struct ActionRequest {
+    seat: usize,
+    action: PlayerAction,
+}
+
+#[derive(Serialize)]
+struct JoinResponse {
+    seat: usize,
+    table: TableState,
+}
+
+#[derive(Serialize)]
+struct HistoryResponse {
+    lines: Vec<String>,
+}
+
+/// Reads the JSON body of a request.
+fn body<T: serde::de::DeserializeOwned>(text: &str) -> Result<T, ApiError> {
+    serde_json::from_str(text).map_err(|e| error(ErrorKind::BadRequest, e.to_string()))
+}
+
+fn json(value: &impl Serialize) -> String {
+    serde_json::to_string(value).unwrap()
+}
+
+impl Api {
+    pub fn new(settings: ApiSettings) -> Api {
+        Api {
+            tables: Mutex::default(),
+            settings,
+        }
+    }
+
+    /// The tables, without those that were over
+    /// for longer than the settings keep them.
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        let mut tables = self.tables.lock().unwrap();
+        let keep = self.settings.keep_over;
+        tables.by_id.retain(|_, table| {
+            table
+                .lock()
+                .over_since
+                .map_or(true, |since| since.elapsed() < keep)
+        });
+        tables
+    }
+
+    fn table(&self, id: &str) -> Result<Arc<SharedTable>, ApiError> {
+        let tables = self.tables();
+        id.parse::<usize>()
+            .ok()
+            .and_then(|id| tables.by_id.get(&id))
+            .cloned()
+            .ok_or_else(|| error(ErrorKind::NotFound, format!("There is no table {}", id)))
+    }
+
+    /// Creates a table and starts the thread playing it.
+    fn create(&self, text: &str) -> Result<TableState, ApiError> {
+        let bad = |message: String| error(ErrorKind::BadRequest, message);
+        let config = TableConfig::from_json(text).map_err(bad)?;
+        let no_remote = || {
+            bad(String::from(
+                "A table needs remote seats, as in \"seats\": [{\"player\": \"remote\"}]",
+            ))
+        };
+        // tables without seats would ask for them at the terminal
+        if config.seats.is_none() {
+            return Err(no_remote());
+        }
+        let setup = GameSetup::from_args(&Args::default(), &config).map_err(bad)?;
+        if !setup.player_types.contains(&PlayerType::Remote) {
+            return Err(no_remote());
+        }
+        if let Some(seat) = (setup.player_types[..setup.names.len()].iter())
+            .position(|player| !matches!(player, PlayerType::Remote | PlayerType::Bot))
+        {
+            return Err(bad(format!(
+                "seats[{}] : the tables of the API have remote and bot seats",
+                seat + 1
+            )));
+        }
+        let mut tables = self.tables();
+        let open = (tables.by_id.values())
+            .filter(|table| table.lock().status != Status::Over)
+            .count();
+        if open >= self.settings.max_tables {
+            return Err(error(
+                ErrorKind::TooManyTables,
+                format!("There are already {} tables open", open),
+            ));
+        }
+        tables.created += 1;
+        let state = TableState::new(tables.created, &setup);
+        let table = Arc::new(SharedTable {
+            state: Mutex::new(state.clone()),
+            changed: Condvar::new(),
+            timeout: self.settings.turn_timeout,
+        });
+        tables.by_id.insert(state.id, Arc::clone(&table));
+        let join_timeout = self.settings.join_timeout;
+        std::thread::spawn(move || {
+            if let Err(message) = play_table(setup, Arc::clone(&table), join_timeout) {
+                eprintln!("{}", message);
+            }
+            let mut state = table.lock();
+            state.status = Status::Over;
+            state.waiting_for = None;
+            state.over_since = Some(Instant::now());
+            table.changed.notify_all();
+        });
+        Ok(state)
+    }
+
+    /// Seats a player at the next free remote seat.
+    fn join(&self, table: &SharedTable, text: &str) -> Result<JoinResponse, ApiError> {
+        let request: JoinRequest = body(text)?;
+        let name = request.name.trim();
+        let mut state = table.lock();
+        if state.status == Status::Over {
+            return Err(error(ErrorKind::GameOver, "The game is over"));
+        }
+        let seat = (state.seats.iter())
+            .position(|seat| !seat.joined)
+            .ok_or_else(|| error(ErrorKind::TableFull, "Every seat is taken"))?;
+        if !name.is_empty() {
+            let others: Vec<String> = (state.seats.iter().enumerate())
+                .filter(|&(other, _)| other != seat)
+                .map(|(_, other)| other.name.clone())
+                .collect();
+            check_name(name, &others).map_err(|e| error(ErrorKind::BadRequest, e))?;
+            state.seats[seat].name = name.to_string();
+        }
+        state.seats[seat].joined = true;
+        if state.seats.iter().all(|seat| seat.joined) {
+            state.status = Status::Playing;
+            table.changed.notify_all();
+            let turn = state.turn;
+            drop(state);
+            state = table.wait_after(turn);
+        }
+        Ok(JoinResponse {
+            seat,
+            table: state.clone(),
+        })
+    }
+
+    fn bet(&self, table: &SharedTable, text: &str) -> Result<TableState, ApiError> {
+        let request: BetRequest = body(text)?;
+        let state = table.lock();
+        if let Question::Bet { available, .. } = *state.check_turn(request.seat)? {
+            if !state.limits.allows(request.amount, available) {
+                return Err(error(
+                    ErrorKind::BetNotAllowed,
+                    format!(
+                        "Bets go from {} to {}, by {}, with {} available",
+                        state.limits.min, state.limits.max, state.limits.chip, available
+                    ),
+                ));
+            }
+        } else {
+            return Err(error(
+                ErrorKind::NotYourTurn,
+                "The table waits for an action",
+            ));
+        }
+        Ok(table.answer(state, Answer::Bet(request.amount)))
+    }
+
+    fn action(&self, table: &SharedTable, text: &str) -> Result<TableState, ApiError> {
+        let request: ActionRequest = body(text)?;
+        let state = table.lock();
+        if let Question::Action { legal, .. } = state.check_turn(request.seat)? {
+            if !legal.contains(&request.action) {
+                let names: Vec<String> = (legal.iter().chain([request.action].iter()))
+                    .map(|action| format!("{:?}", action).to_lowercase())
+                    .collect();
+                let (action, legal) = names.split_last().unwrap();
+                return Err(error(
+                    ErrorKind::IllegalAction,
+                    format!("{} is not allowed now : {}", action, legal.join(", ")),
+                ));
+            }
+        } else {
+            return Err(error(ErrorKind::NotYourTurn, "The table waits for a bet"));
+        }
+        Ok(table.answer(state, Answer::Action(request.action)))
+    }
+
+    /// Answers a request, with its status and its JSON body.
+    pub fn handle(&self, method: &str, path: &str, text: &str) -> (u16, String) {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        let result = match (method, segments.as_slice()) {
+            ("POST", ["tables"]) => self.create(text).map(|state| (201, json(&state))),
+            (_, ["tables"]) => Err(error(
+                ErrorKind::MethodNotAllowed,
+                "Tables are created with POST",
+            )),
+            (method, ["tables", id, rest @ ..]) => self.table(id).and_then(|table| {
+                match (method, rest) {
+                    ("GET", []) => Ok(json(&*table.lock())),
+                    ("GET", ["history"]) => Ok(json(&HistoryResponse {
+                        lines: table.lock().history.clone(),
+                    })),
+                    ("POST", ["join"]) => self.join(&table, text).map(|joined| json(&joined)),
+                    ("POST", ["bet"]) => self.bet(&table, text).map(|state| json(&state)),
+                    ("POST", ["action"]) => self.action(&table, text).map(|state| json(&state)),
+                    (_, [] | ["history"] | ["join"] | ["bet"] | ["action"]) => Err(error(
+                        ErrorKind::MethodNotAllowed,
+                        format!("{} is not allowed on {}", method, path),
+                    )),
+                    _ => Err(error(ErrorKind::NotFound, format!("There is no {}", path))),
+                }
+                .map(|body| (200, body))
+            }),
+            _ => Err(error(ErrorKind::NotFound, format!("There is no {}", path))),
+        };
+        result.unwrap_or_else(|e| (e.error.status(), json(&e)))
+    }
+}
+
+/// Reads the request line or a header into `line`,
+/// which must be at most [MAX_LINE] bytes long.
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> Result<(), String> {
+    line.clear();
+    (reader.take(MAX_LINE as u64))
+        .read_line(line)
+        .map_err(|e| e.to_string())?;
+    if line.len() == MAX_LINE && !line.ends_with('\n') {
+        return Err(format!(
+            "A line of the request is longer than {} bytes",
+            MAX_LINE
+        ));
+    }
+    Ok(())
+}
+
+/// Reads a request : its method, its path and its body.
+fn read_request(input: impl Read) -> Result<(String, String, String), String> {
+    let mut reader = BufReader::new(input);
+    let mut line = String::new();
+    read_line(&mut reader, &mut line)?;
+    let mut words = line.split_whitespace();
+    let (method, path) = match (words.next(), words.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => return Err(format!("Invalid request line '{}'", line.trim())),
+    };
+    let mut length = 0;
+    for headers in 0.. {
+        read_line(&mut reader, &mut line)?;
+        let header = line.trim();
+        if header.is_empty() {
+            break;
+        }
+        if headers == MAX_HEADERS {
+            return Err(format!("The request has more than {} headers", MAX_HEADERS));
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().map_err(|_| "Invalid Content-Length")?;
+                if length > MAX_BODY {
+                    return Err(format!("The body is larger than {} bytes", MAX_BODY));
+                }
+            }
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+    let body = String::from_utf8(body).map_err(|e| e.to_string())?;
+    Ok((method, path, body))
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        422 => "Unprocessable Entity",
+        _ => "Service Unavailable",
+    }
+}
+
+/// Answers one connection.
+fn serve_connection(api: &Api, mut stream: TcpStream) {
+    let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+    let (status, body) = match read_request(&stream) {
+        Ok((method, path, body)) => api.handle(&method, &path, &body),
+        Err(message) => (400, json(&error(ErrorKind::BadRequest, message))),
+    };
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        reason(status),
+        body.len(),
+        body
+    );
+}
+
+/// Answers the requests made to the listener, each on its own thread.
+pub fn serve(listener: TcpListener, api: Arc<Api>) {
+    for stream in listener.incoming().flatten() {
+        let api = Arc::clone(&api);
+        std::thread::spawn(move || serve_connection(&api, stream));
+    }
+}
+
+/// Runs the `api` subcommand.
+pub fn run(args: &Args) -> Result<(), String> {
+    args.check_options(&[&["port", "turn-timeout", "join-timeout", "max-tables"]])?;
+    let port = args.parse_or("port", DEFAULT_API_PORT)?;
+    let defaults = ApiSettings::default();
+    let seconds = |key: &str, default: Duration| {
+        let seconds = args.parse_or(key, default.as_secs_f64())?;
+        if !seconds.is_finite() || seconds <= 0.0 {
+            return Err(format!("--{} must be a positive number of seconds", key));
+        }
+        Ok(Duration::from_secs_f64(seconds))
+    };
+    let settings = ApiSettings {
+        turn_timeout: seconds("turn-timeout", defaults.turn_timeout)?,
+        join_timeout: seconds("join-timeout", defaults.join_timeout)?,
+        max_tables: args.parse_or("max-tables", defaults.max_tables)?,
+        ..defaults
+    };
+    if settings.max_tables == 0 {
+        return Err(String::from("--max-tables must be at least 1"));
+    }
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .map_err(|e| format!("Cannot listen on port {} : {}", port, e))?;
+    println!("Listening on http://127.0.0.1:{}", port);
+    serve(listener, Arc::new(Api::new(settings)));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::*;
+
+    use serde_json::Value;
+
+    /// Makes a request to the API, as an HTTP client would.
+    fn request(port: u16, method: &str, path: &str, body: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    fn start_with(settings: ApiSettings) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || serve(listener, Arc::new(Api::new(settings))));
+        port
+    }
+
+    fn start() -> u16 {
+        start_with(ApiSettings::default())
+    }
+
+    /// Waits until the table is over, or removed, and returns it.
+    fn wait_for_end(port: u16, id: usize) -> (u16, Value) {
+        for _ in 0..200 {
+            let (status, table) = request(port, "GET", &format!("/tables/{}", id), "");
+            if status != 200 || table["status"] == "over" {
+                return (status, table);
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        panic!("Table {} is not over", id);
+    }
+
+    const TABLE: &str = r#"{
+        "limits": {"min_bet": 10, "chip": 5},
+        "game": {"bankroll": 200, "rounds": 3, "seed": 4},
+        "seats": [{"player": "remote"}, {"player": "bot"}]
+    }"#;
+
+    #[test]
+    fn play_a_table() {
+        let port = start();
+        let (status, table) = request(port, "POST", "/tables", TABLE);
+        assert_eq!(
+            (201, "waiting"),
+            (status, table["status"].as_str().unwrap())
+        );
+        let (status, error) = request(port, "POST", "/tables/1/bet", r#"{"seat":0,"amount":10}"#);
+        assert_eq!(
+            (409, "not_started"),
+            (status, error["error"].as_str().unwrap())
+        );
+
+        let (status, joined) = request(port, "POST", "/tables/1/join", r#"{"name":"Ann"}"#);
+        assert_eq!((200, 0), (status, joined["seat"].as_u64().unwrap()));
+        assert_eq!("Ann", joined["table"]["seats"][0]["name"]);
+        assert_eq!("bet", joined["table"]["waiting_for"]["type"]);
+        let (status, error) = request(port, "POST", "/tables/1/join", "{}");
+        assert_eq!(
+            (409, "table_full"),
+            (status, error["error"].as_str().unwrap())
+        );
+
+        let (status, error) = request(port, "POST", "/tables/1/bet", r#"{"seat":0,"amount":12}"#);
+        assert_eq!(
+            (422, "bet_not_allowed"),
+            (status, error["error"].as_str().unwrap())
+        );
+        let (status, error) = request(port, "POST", "/tables/1/bet", r#"{"seat":1,"amount":10}"#);
+        assert_eq!(
+            (409, "not_your_turn"),
+            (status, error["error"].as_str().unwrap())
+        );
+
+        let mut table = request(port, "POST", "/tables/1/bet", r#"{"seat":0,"amount":20}"#).1;
+        assert_eq!(20, table["seats"][0]["bet"]);
+        let mut actions = 0;
+        while table["status"] == "playing" {
+            let question = &table["waiting_for"];
+            table = if question["type"] == "bet" {
+                request(port, "POST", "/tables/1/bet", r#"{"seat":0,"amount":10}"#).1
+            } else {
+                // surrender is not offered by default, and the first legal action is taken
+                let (status, error) = request(
+                    port,
+                    "POST",
+                    "/tables/1/action",
+                    r#"{"seat":0,"action":"surrender"}"#,
+                );
+                assert_eq!(
+                    (422, "illegal_action"),
+                    (status, error["error"].as_str().unwrap())
+                );
+                actions += 1;
+                let action = format!(r#"{{"seat":0,"action":{}}}"#, question["legal"][0]);
+                request(port, "POST", "/tables/1/action", &action).1
+            };
+        }
+        assert!(actions > 0);
+        assert_eq!(
+            (3, "over"),
+            (
+                table["round"].as_u64().unwrap(),
+                table["status"].as_str().unwrap()
+            )
+        );
+        let (status, error) = request(port, "POST", "/tables/1/bet", r#"{"seat":0,"amount":10}"#);
+        assert_eq!(
+            (409, "game_over"),
+            (status, error["error"].as_str().unwrap())
+        );
+
+        let (status, history) = request(port, "GET", "/tables/1/history", "");
+        assert_eq!(200, status);
+        let lines = history["lines"].as_array().unwrap();
+        assert_eq!("Ann: bets 20", lines[1]);
+        assert!(lines
+            .iter()
+            .any(|line| line.as_str().unwrap().starts_with("Banks: ")));
+        assert_eq!(table, request(port, "GET", "/tables/1", "").1);
+    }
+
+    #[test]
+    fn simultaneous_answers() {
+        let port = start();
+        for id in 1..=10 {
+            request(port, "POST", "/tables", TABLE);
+            request(port, "POST", &format!("/tables/{}/join", id), "{}");
+            let path = format!("/tables/{}/bet", id);
+            let bets: Vec<_> = (0..2)
+                .map(|_| {
+                    let path = path.clone();
+                    std::thread::spawn(move || {
+                        request(port, "POST", &path, r#"{"seat":0,"amount":20}"#).0
+                    })
+                })
+                .collect();
+            let mut statuses: Vec<u16> = bets.into_iter().map(|bet| bet.join().unwrap()).collect();
+            statuses.sort_unstable();
+            // the question is answered once, and the table plays on
+            assert_eq!(vec![200, 409], statuses);
+            let (status, table) = request(port, "GET", &format!("/tables/{}", id), "");
+            assert_eq!(
+                (200, "playing"),
+                (status, table["status"].as_str().unwrap())
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_requests() {
+        let port = start();
+        let code =
+            |(status, error): (u16, Value)| (status, error["error"].as_str().unwrap().to_string());
+        let expected = |status: u16, kind: &str| (status, kind.to_string());
+        assert_eq!(
+            expected(404, "not_found"),
+            code(request(port, "GET", "/tables/1", ""))
+        );
+        assert_eq!(
+            expected(404, "not_found"),
+            code(request(port, "GET", "/chairs", ""))
+        );
+        assert_eq!(
+            expected(405, "method_not_allowed"),
+            code(request(port, "GET", "/tables", ""))
+        );
+        assert_eq!(
+            expected(400, "bad_request"),
+            code(request(port, "POST", "/tables", "{\"rules\": "))
+        );
+        assert_eq!(
+            expected(400, "bad_request"),
+            code(request(
+                port,
+                "POST",
+                "/tables",
+                r#"{"rules": {"decks": 2}}"#
+            ))
+        );
+        assert_eq!(
+            expected(400, "bad_request"),
+            code(request(
+                port,
+                "POST",
+                "/tables",
+                r#"{"seats": [{"player": "bot"}]}"#
+            ))
+        );
+        assert_eq!(
+            expected(400, "bad_request"),
+            code(request(
+                port,
+                "POST",
+                "/tables",
+                r#"{"seats": [{"player": "remote"}, {"player": "human"}]}"#
+            ))
+        );
+        assert_eq!(201, request(port, "POST", "/tables", TABLE).0);
+        // player types are read without regard to the case
+        let shouted = TABLE.replace("\"remote\"", "\"Remote\"");
+        assert_eq!(201, request(port, "POST", "/tables", &shouted).0);
+        assert_eq!(
+            expected(400, "bad_request"),
+            code(request(
+                port,
+                "POST",
+                "/tables/1/action",
+                r#"{"seat":0,"action":"fold"}"#
+            ))
+        );
+        assert_eq!(
+            expected(405, "method_not_allowed"),
+            code(request(port, "GET", "/tables/1/join", ""))
+        );
+        // bodies are limited before they are read
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        write!(
+            stream,
+            "POST /tables HTTP/1.1\r\nContent-Length: 100000000\r\n\r\n"
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+
+    #[test]
+    fn slow_players() {
+        let port = start_with(ApiSettings {
+            turn_timeout: Duration::from_millis(50),
+            join_timeout: Duration::from_millis(200),
+            max_tables: 2,
+            ..ApiSettings::default()
+        });
+        assert_eq!(201, request(port, "POST", "/tables", TABLE).0);
+        assert_eq!(201, request(port, "POST", "/tables", TABLE).0);
+        let (status, error) = request(port, "POST", "/tables", TABLE);
+        assert_eq!(
+            (503, "too_many_tables"),
+            (status, error["error"].as_str().unwrap())
+        );
+
+        // Ann joins, then never answers : they bet the minimum and stand
+        request(port, "POST", "/tables/1/join", r#"{"name":"Ann"}"#);
+        let (status, table) = wait_for_end(port, 1);
+        assert_eq!((200, 3), (status, table["round"].as_u64().unwrap()));
+        let history = request(port, "GET", "/tables/1/history", "").1;
+        let lines = history["lines"].as_array().unwrap();
+        assert_eq!("Ann: bets 10", lines[1]);
+        assert!(lines.iter().any(|line| line == "Ann: stands"));
+
+        // nobody joins the second table, which is closed
+        let (status, table) = wait_for_end(port, 2);
+        assert_eq!((200, 0), (status, table["round"].as_u64().unwrap()));
+        let (status, error) = request(port, "POST", "/tables/2/join", "{}");
+        assert_eq!(
+            (409, "game_over"),
+            (status, error["error"].as_str().unwrap())
+        );
+        // the tables that are over leave room for new ones
+        let (status, table) = request(port, "POST", "/tables", TABLE);
+        assert_eq!((201, 3), (status, table["id"].as_u64().unwrap()));
+    }
+
+    #[test]
+    fn removed_tables() {
+        let port = start_with(ApiSettings {
+            join_timeout: Duration::from_millis(50),
+            keep_over: Duration::ZERO,
+            ..ApiSettings::default()
+        });
+        assert_eq!(201, request(port, "POST", "/tables", TABLE).0);
+        let (status, error) = wait_for_end(port, 1);
+        assert_eq!(
+            (404, "not_found"),
+            (status, error["error"].as_str().unwrap())
+        );
+    }
+
+    #[test]
+    fn long_requests() {
+        let line = format!("GET /{} HTTP/1.1\r\n\r\n", "x".repeat(MAX_LINE));
+        assert!(read_request(line.as_bytes()).is_err());
+        let headers = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "A: b\r\n".repeat(MAX_HEADERS + 1)
+        );
+        assert!(read_request(headers.as_bytes()).is_err());
+        let request = format!("GET / HTTP/1.1\r\n{}\r\n", "A: b\r\n".repeat(MAX_HEADERS));
+        let (method, path, body) = read_request(request.as_bytes()).unwrap();
+        assert_eq!(
+            ("GET", "/", ""),
+            (method.as_str(), path.as_str(), body.as_str())
+        );
+    }
+}
@@ -0,0 +1,271 @@
+use crate::card::{Card, Suit};
+use crate::cli::Args;
+
+use std::io::IsTerminal;
+use std::sync::RwLock;
+
+/// How cards are written in the terminal.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct CardStyle {
+    /// Whether hands are drawn as cards side by side,
+    /// rather than written on one line.
+    pub art: bool,
+    /// Whether suits are Unicode symbols (`♥`) rather than
+    /// letters (`H`), and card edges box-drawing characters.
+    pub unicode: bool,
+    /// Whether hearts and diamonds are coloured in red.
+    pub colour: bool,
+}
+
+/// The style used when the program does not set one.
+const DEFAULT_STYLE: CardStyle = CardStyle {
+    art: false,
+    unicode: true,
+    colour: false,
+};
+
+static STYLE: RwLock<CardStyle> = RwLock::new(DEFAULT_STYLE);
+
+/// The card style of the program, which is also the
+/// one of the [std::fmt::Display] of cards.
+pub fn card_style() -> CardStyle {
+    *STYLE.read().unwrap()
+}
+
+/// Sets the card style of the program.
+pub fn set_card_style(style: CardStyle) {
+    *STYLE.write().unwrap() = style;
+}
+
+/// What the terminal is capable of, as far as cards are concerned.
+#[derive(Clone, Copy, Debug)]
+pub struct Terminal {
+    /// Whether the output is a terminal rather than a pipe or a file.
+    pub is_terminal: bool,
+    /// Whether the locale uses UTF-8.
+    pub utf8: bool,
+    /// Whether the `NO_COLOR` variable asks for no colours.
+    pub no_color: bool,
+    /// Whether the terminal is dumb (`TERM=dumb`).
+    pub dumb: bool,
+}
+
+impl Terminal {
+    /// Inspects the output and the environment of the program.
+    pub fn detect() -> Terminal {
+        let var = |name| std::env::var(name).unwrap_or_default();
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .map(|&name| var(name))
+            .find(|value| !value.is_empty())
+            .unwrap_or_default()
+            .to_lowercase();
+        Terminal {
+            is_terminal: std::io::stdout().is_terminal(),
+            utf8: cfg!(windows) || locale.contains("utf-8") || locale.contains("utf8"),
+            no_color: !var("NO_COLOR").is_empty(),
+            dumb: var("TERM") == "dumb",
+        }
+    }
+}
+
+impl CardStyle {
+    /// Chooses the style from `--cards` and `--colour`.
+    ///
+    /// `--cards` is `unicode` or `ascii` for cards drawn side by
+    /// side with these characters, `plain` for hands written on one
+    /// line in ASCII, as in `9C AH`, and `auto` (the default), which
+    /// draws cards on terminals, in Unicode when the locale uses
+    /// UTF-8, and writes hands on one line otherwise. `--colour` is
+    /// `always`, `never` or `auto` (the default), which colours the
+    /// cards on terminals unless the `NO_COLOR` variable is set.
+    pub fn choose(cards: &str, colour: &str, terminal: Terminal) -> Result<CardStyle, String> {
+        let capable = terminal.is_terminal && !terminal.dumb;
+        let (art, unicode) = match cards {
+            "auto" => (capable, terminal.utf8),
+            "unicode" => (true, true),
+            "ascii" => (true, false),
+            "plain" => (false, false),
+            other => return Err(format!("Unknown card style '{}'", other)),
+        };
+        let colour = match colour {
+            "auto" => capable && !terminal.no_color,
+            "always" => true,
+            "never" => false,
+            other => return Err(format!("Unknown colour mode '{}'", other)),
+        };
+        Ok(CardStyle {
+            art,
+            unicode,
+            colour,
+        })
+    }
+
+    /// Chooses the style from the options of the command
+    /// line and the capabilities of the terminal.
+    pub fn from_args(args: &Args) -> Result<CardStyle, String> {
+        CardStyle::choose(
+            args.get("cards").unwrap_or("auto"),
+            args.get("colour").unwrap_or("auto"),
+            Terminal::detect(),
+        )
+    }
+}
+
+/// The options read by [CardStyle::from_args].
+pub const CARD_OPTIONS: &[&str] = &["cards", "colour"];
+
+/// The symbol of a suit : `♥` in Unicode, `H` in ASCII.
+pub fn suit_symbol(suit: Suit, unicode: bool) -> &'static str {
+    match (suit, unicode) {
+        (Suit::Spades, true) => "♠",
+        (Suit::Hearts, true) => "♥",
+        (Suit::Diamonds, true) => "♦",
+        (Suit::Clubs, true) => "♣",
+        (Suit::Spades, false) => "S",
+        (Suit::Hearts, false) => "H",
+        (Suit::Diamonds, false) => "D",
+        (Suit::Clubs, false) => "C",
+    }
+}
+
+/// Colours a text in red when the card is a heart or a diamond.
+fn paint(text: String, suit: Suit, style: CardStyle) -> String {
+    if style.colour && (suit == Suit::Hearts || suit == Suit::Diamonds) {
+        format!("\x1b[31m{}\x1b[0m", text)
+    } else {
+        text
+    }
+}
+
+/// A card written in the given style, as in `9♣` or `AH`.
+pub fn card_text(card: &Card, style: CardStyle) -> String {
+    paint(
+        format!("{}{}", card.rank, suit_symbol(card.suit, style.unicode)),
+        card.suit,
+        style,
+    )
+}
+
+/// The five lines of a hand drawn as cards side by side, as in
+/// ```text
+/// ┌─────┐ ┌─────┐
+/// │9    │ │A    │
+/// │  ♣  │ │  ♥  │
+/// │    9│ │    A│
+/// └─────┘ └─────┘
+/// ```
+pub fn hand_art(cards: &[Card], style: CardStyle) -> Vec<String> {
+    let (corner_top, corner_bottom, edge, side) = if style.unicode {
+        (("┌", "┐"), ("└", "┘"), "─────", "│")
+    } else {
+        (("+", "+"), ("+", "+"), "-----", "|")
+    };
+    let mut lines = vec![Vec::new(); 5];
+    for card in cards {
+        let rank = card.rank.to_string();
+        let suit = suit_symbol(card.suit, style.unicode);
+        let body = [
+            format!("{:<5}", rank),
+            format!("  {}  ", suit),
+            format!("{:>5}", rank),
+        ];
+        lines[0].push(format!("{}{}{}", corner_top.0, edge, corner_top.1));
+        for (line, text) in lines[1..4].iter_mut().zip(body) {
+            line.push(format!("{}{}{}", side, paint(text, card.suit, style), side));
+        }
+        lines[4].push(format!("{}{}{}", corner_bottom.0, edge, corner_bottom.1));
+    }
+    lines.into_iter().map(|line| line.join(" ")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::art::*;
+    use crate::card::Rank;
+
+    const TERMINAL: Terminal = Terminal {
+        is_terminal: true,
+        utf8: true,
+        no_color: false,
+        dumb: false,
+    };
+
+    fn hand() -> Vec<Card> {
+        vec![
+            Card {
+                rank: Rank::Ten,
+                suit: Suit::Clubs,
+            },
+            Card {
+                rank: Rank::Ace,
+                suit: Suit::Hearts,
+            },
+        ]
+    }
+
+    fn style(cards: &str, colour: &str, terminal: Terminal) -> CardStyle {
+        CardStyle::choose(cards, colour, terminal).unwrap()
+    }
+
+    #[test]
+    fn choose_test() {
+        let full = CardStyle {
+            art: true,
+            unicode: true,
+            colour: true,
+        };
+        assert_eq!(full, style("auto", "auto", TERMINAL));
+        let piped = Terminal {
+            is_terminal: false,
+            ..TERMINAL
+        };
+        assert_eq!(DEFAULT_STYLE, style("auto", "auto", piped));
+        let no_color = Terminal {
+            no_color: true,
+            utf8: false,
+            ..TERMINAL
+        };
+        let ascii = style("auto", "auto", no_color);
+        assert!(ascii.art && !ascii.unicode && !ascii.colour);
+        assert!(style("auto", "always", no_color).colour);
+        assert_eq!(
+            (false, false),
+            (
+                style("plain", "auto", TERMINAL).art,
+                style("plain", "never", TERMINAL).colour
+            )
+        );
+        assert!(CardStyle::choose("fancy", "auto", TERMINAL).is_err());
+        assert!(CardStyle::choose("auto", "sometimes", TERMINAL).is_err());
+    }
+
+    #[test]
+    fn card_text_test() {
+        let plain = style("plain", "never", TERMINAL);
+        assert_eq!("10C", card_text(&hand()[0], plain));
+        assert_eq!("AH", card_text(&hand()[1], plain));
+        let coloured = style("unicode", "always", TERMINAL);
+        assert_eq!("10♣", card_text(&hand()[0], coloured));
+        assert_eq!("\x1b[31mA♥\x1b[0m", card_text(&hand()[1], coloured));
+    }
+
+    #[test]
+    fn hand_art_test() {
+        let unicode = hand_art(&hand(), style("unicode", "never", TERMINAL));
+        assert_eq!(
+            vec![
+                "┌─────┐ ┌─────┐",
+                "│10   │ │A    │",
+                "│  ♣  │ │  ♥  │",
+                "│   10│ │    A│",
+                "└─────┘ └─────┘",
+            ],
+            unicode
+        );
+        let ascii = hand_art(&hand(), style("ascii", "always", TERMINAL));
+        assert_eq!("+-----+ +-----+", ascii[0]);
+        assert_eq!("|  C  | |\x1b[31m  H  \x1b[0m|", ascii[2]);
+        assert!(hand_art(&[], DEFAULT_STYLE).iter().all(String::is_empty));
+    }
+}
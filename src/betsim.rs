@@ -0,0 +1,194 @@
+use crate::betting::{BetContext, BetPolicy, TableLimits};
+use crate::cli::{Args, LIMIT_OPTIONS, RULE_OPTIONS};
+use crate::rules::Rules;
+use crate::shoe::Shoe;
+use crate::sim::{simulate_round_within, Accumulator};
+use crate::strategy::{strategy_from_name, Strategy};
+
+/// The betting policies played by default by the `betsim` subcommand.
+const DEFAULT_POLICIES: &str = "martingale,paroli,fibonacci,dalembert,labouchere,oscar,kelly";
+
+/// Aggregated results of several bot-only sessions played
+/// with the same betting policy.
+#[derive(Default)]
+pub struct SessionStats {
+    /// 1 for each session that ended broke, 0 otherwise.
+    pub broke: Accumulator,
+    /// The bank at the end of each session.
+    pub final_bank: Accumulator,
+    /// The number of rounds played in each session.
+    pub rounds: Accumulator,
+    /// The number of rounds where the bet was the table maximum.
+    pub capped_rounds: u64,
+}
+
+/// Plays `sessions` sessions of at most `rounds` rounds with a
+/// betting policy, each one starting with `bankroll` money and a
+/// fresh copy of the policy. A session ends early when the bank
+/// cannot cover the table minimum anymore.
+///
+/// Session `i` uses the seed `seed + i`, so that all policies
+/// face the same cards as long as they play the same hands.
+/// Bots only double or split when their bank covers the extra bet.
+pub fn play_sessions(
+    policy: &BetPolicy,
+    strategy: &dyn Strategy,
+    table: (&Rules, &TableLimits),
+    bankroll: u32,
+    rounds: u64,
+    sessions: u64,
+    seed: u64,
+) -> SessionStats {
+    let (rules, limits) = table;
+    let mut stats = SessionStats::default();
+    for session in 0..sessions {
+        let mut policy = policy.clone();
+        let mut shoe = Shoe::new(rules, seed.wrapping_add(session));
+        let mut bank = bankroll as i64;
+        let mut played = 0;
+        while played < rounds && bank >= limits.min as i64 {
+            if shoe.needs_shuffle() {
+                shoe.shuffle();
+            }
+            let bet = policy.bet(&BetContext {
+                available: bank as u32,
+                dealt: shoe.dealt(),
+                decks_remaining: shoe.decks_remaining(),
+                limits,
+                table_bets: &[],
+            });
+            if bet == limits.max {
+                stats.capped_rounds += 1;
+            }
+            let funds = bank as f64 / bet as f64;
+            let net = (bet as f64 * simulate_round_within(&mut shoe, rules, strategy, funds))
+                .floor() as i64;
+            bank += net;
+            policy.record(net);
+            played += 1;
+        }
+        stats
+            .broke
+            .add(if bank < limits.min as i64 { 1.0 } else { 0.0 });
+        stats.final_bank.add(bank as f64);
+        stats.rounds.add(played as f64);
+    }
+    stats
+}
+
+/// Runs the `betsim` subcommand, which compares betting
+/// policies over long bot-only sessions.
+pub fn run(args: &Args) -> Result<(), String> {
+    args.check_options(&[
+        &[
+            "bankroll", "policies", "rounds", "seed", "sessions", "strategy",
+        ],
+        RULE_OPTIONS,
+        LIMIT_OPTIONS,
+    ])?;
+    let rules = args.rules()?;
+    let limits = args.limits()?;
+    let name = args.get("strategy").unwrap_or("basic");
+    let strategy = strategy_from_name(name)?;
+    let policies = args.get("policies").unwrap_or(DEFAULT_POLICIES);
+    let bankroll = args.parse_or("bankroll", 1000)?;
+    let rounds = args.parse_or("rounds", 10_000)?;
+    let sessions = args.parse_or("sessions", 200)?;
+    let seed = args.parse_or("seed", 0)?;
+
+    println!(
+        "Rules : {}, strategy {}, table limits {} to {}",
+        rules, name, limits.min, limits.max
+    );
+    println!(
+        "{} sessions of up to {} rounds, starting with {} each\n",
+        sessions, rounds, bankroll
+    );
+    println!(
+        "{:<16}{:>10}{:>14}{:>14}{:>18}",
+        "Policy", "Broke", "Final bank", "Rounds", "At table max"
+    );
+    for text in policies.split(',') {
+        let policy = BetPolicy::parse(text)?;
+        let stats = play_sessions(
+            &policy,
+            strategy.as_ref(),
+            (&rules, &limits),
+            bankroll,
+            rounds,
+            sessions,
+            seed,
+        );
+        let total_rounds = stats.rounds.mean() * stats.rounds.count() as f64;
+        println!(
+            "{:<16}{:>9.1}%{:>14.0}{:>14.0}{:>17.2}%",
+            text,
+            100.0 * stats.broke.mean(),
+            stats.final_bank.mean(),
+            stats.rounds.mean(),
+            100.0 * stats.capped_rounds as f64 / total_rounds.max(1.0),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::betsim::*;
+    use crate::strategy::BasicStrategy;
+
+    #[test]
+    fn martingale_caps_out() {
+        let limits = TableLimits {
+            min: 1,
+            max: 8,
+            chip: 1,
+            secret_bets: false,
+        };
+        let policy = BetPolicy::parse("martingale").unwrap();
+        let stats = play_sessions(
+            &policy,
+            &BasicStrategy,
+            (&Rules::default(), &limits),
+            1000,
+            2000,
+            3,
+            0,
+        );
+        assert!(stats.capped_rounds > 0);
+        assert_eq!(3, stats.rounds.count());
+    }
+
+    #[test]
+    fn small_bankroll_goes_broke() {
+        let policy = BetPolicy::parse("martingale").unwrap();
+        let stats = play_sessions(
+            &policy,
+            &BasicStrategy,
+            (&Rules::default(), &TableLimits::default()),
+            3,
+            100_000,
+            10,
+            0,
+        );
+        assert_eq!(1.0, stats.broke.mean());
+        assert!(stats.rounds.mean() < 100_000.0);
+    }
+
+    #[test]
+    fn no_overdraft() {
+        let policy = BetPolicy::parse("martingale").unwrap();
+        for seed in 0..50 {
+            let stats = play_sessions(
+                &policy,
+                &BasicStrategy,
+                (&Rules::default(), &TableLimits::default()),
+                5,
+                1000,
+                1,
+                seed,
+            );
+            assert!(stats.final_bank.mean() >= 0.0);
+        }
+    }
+}
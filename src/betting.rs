@@ -0,0 +1,548 @@
+use crate::count::CountSystem;
+use crate::progression::{Progression, System};
+
+use serde::{Deserialize, Serialize};
+
+/// The betting limits of the table.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct TableLimits {
+    /// The smallest bet accepted by the table.
+    pub min: u32,
+    /// The largest bet accepted by the table.
+    pub max: u32,
+    /// Bets must be a multiple of the value of the chips.
+    pub chip: u32,
+    /// Whether bets are hidden from the other players until the
+    /// cards are dealt, as on the last hand of a tournament round.
+    pub secret_bets: bool,
+}
+
+impl Default for TableLimits {
+    fn default() -> TableLimits {
+        TableLimits {
+            min: 1,
+            max: 1000,
+            chip: 1,
+            secret_bets: false,
+        }
+    }
+}
+
+impl TableLimits {
+    /// Rounds a desired bet down to a whole number of chips,
+    /// and brings it within the limits of the table and
+    /// the available bankroll, rounded down to the chip.
+    /// A bankroll below the minimum bet goes all in.
+    pub fn clamp(&self, desired: f64, available: u32) -> u32 {
+        if available < self.min {
+            return available;
+        }
+        let chips = (desired.max(0.0) / self.chip as f64).floor() as u32;
+        let affordable = available - available % self.chip;
+        let bet = chips.saturating_mul(self.chip).max(self.min).min(self.max);
+        bet.min(affordable)
+    }
+
+    /// Whether a bet is accepted by the table : a whole number of
+    /// chips between the minimum and the maximum, or all the money
+    /// left to a player who has less than the minimum.
+    pub fn allows(&self, bet: u32, available: u32) -> bool {
+        let within = bet >= self.min && bet <= self.max && bet % self.chip == 0;
+        bet <= available && (within || (bet == available && available < self.min))
+    }
+}
+
+/// What a bot knows when it decides its bet.
+pub struct BetContext<'a> {
+    /// The money the bot has left, not including the bet.
+    pub available: u32,
+    /// The cards dealt since the last shuffle, by value
+    /// like [crate::shoe::Shoe::dealt].
+    pub dealt: &'a [u32; 10],
+    /// The number of packs left to be dealt.
+    pub decks_remaining: f64,
+    /// The limits of the table.
+    pub limits: &'a TableLimits,
+    /// The bets already placed by the players seated before
+    /// the bot this round, empty when bets are secret.
+    pub table_bets: &'a [u32],
+}
+
+/// The betting policy of a bot.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum BetPolicy {
+    /// The historical rule : always bet half of the bankroll.
+    HalfBank,
+    /// Bet a fraction of the Kelly bet.
+    Kelly(KellyBetting),
+    /// Follow a classic betting progression.
+    Progression(Progression),
+    /// Bet to finish among the players who advance in a tournament.
+    Tournament(TournamentBetting),
+}
+
+impl BetPolicy {
+    /// Parses a betting policy given on the command line :
+    /// `half-bank`, `kelly` (half Kelly with a Hi-Lo estimate of the
+    /// advantage), `kelly:FRACTION` or `kelly:FRACTION:EDGE` for a
+    /// fixed advantage. Progressions are given by their name, optionally
+    /// followed by the value of their unit : `martingale`, `paroli`,
+    /// `fibonacci`, `dalembert`, `labouchere` or `oscar`, for
+    /// instance `martingale:5`. `tournament` bets according to the
+    /// rank of the bot in a tournament.
+    pub fn parse(text: &str) -> Result<BetPolicy, String> {
+        let mut parts = text.split(':');
+        let invalid = || format!("Invalid betting policy '{}'", text);
+        let number = |part: Option<&str>| -> Result<Option<f64>, String> {
+            part.map(|p| p.parse().map_err(|_| invalid())).transpose()
+        };
+        match parts.next() {
+            Some("half-bank") if parts.next().is_none() => Ok(BetPolicy::HalfBank),
+            Some("tournament") if parts.next().is_none() => {
+                Ok(BetPolicy::Tournament(TournamentBetting::default()))
+            }
+            Some("kelly") => {
+                let mut kelly = KellyBetting::default();
+                if let Some(fraction) = number(parts.next())? {
+                    kelly.fraction = fraction;
+                }
+                if let Some(edge) = number(parts.next())? {
+                    kelly.edge = Edge::Fixed(edge);
+                }
+                if parts.next().is_some() || kelly.fraction <= 0.0 {
+                    return Err(invalid());
+                }
+                Ok(BetPolicy::Kelly(kelly))
+            }
+            Some(name) => {
+                let system = System::from_name(name).ok_or_else(invalid)?;
+                let unit = number(parts.next())?.map(|unit| unit as u32);
+                if parts.next().is_some() || unit == Some(0) {
+                    return Err(invalid());
+                }
+                Ok(BetPolicy::Progression(Progression::new(system, unit)))
+            }
+            None => Err(invalid()),
+        }
+    }
+
+    /// Decides the bet of the bot for the next round.
+    pub fn bet(&mut self, context: &BetContext) -> u32 {
+        match self {
+            BetPolicy::HalfBank => context
+                .limits
+                .clamp((context.available >> 1) as f64, context.available),
+            BetPolicy::Kelly(kelly) => kelly.bet(context),
+            BetPolicy::Progression(progression) => progression.bet(context),
+            BetPolicy::Tournament(tournament) => tournament.bet(context),
+        }
+    }
+
+    /// Tells the bot how much money it won (or lost, when
+    /// negative) during the last round.
+    pub fn record(&mut self, net: i64) {
+        if let BetPolicy::Progression(progression) = self {
+            progression.record(net);
+        }
+    }
+
+    /// Tells the bot where it stands in a tournament, before
+    /// each hand. Only tournament betting uses it.
+    pub fn update_standing(&mut self, standing: Standing) {
+        if let BetPolicy::Tournament(tournament) = self {
+            tournament.standing = standing;
+        }
+    }
+}
+
+/// How a bot estimates its advantage over the house.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Edge {
+    /// A fixed advantage, for instance obtained by simulation.
+    Fixed(f64),
+    /// An advantage estimated from the true count, as `base`
+    /// plus `per_true_count` times the true count.
+    Count {
+        system: CountSystem,
+        base: f64,
+        per_true_count: f64,
+    },
+}
+
+impl Edge {
+    /// The usual Hi-Lo estimate : each true count point is worth
+    /// half a percent, starting from the edge of basic strategy.
+    pub fn hi_lo() -> Edge {
+        Edge::Count {
+            system: CountSystem::hi_lo(),
+            base: -0.005,
+            per_true_count: 0.005,
+        }
+    }
+
+    /// The estimated advantage of the player, as a fraction of the bet.
+    pub fn advantage(&self, context: &BetContext) -> f64 {
+        match self {
+            Edge::Fixed(edge) => *edge,
+            Edge::Count {
+                system,
+                base,
+                per_true_count,
+            } => {
+                let true_count = system.true_count_of(context.dealt, context.decks_remaining);
+                base + per_true_count * true_count
+            }
+        }
+    }
+}
+
+/// Kelly betting : bet the fraction of the bankroll that
+/// maximises its expected logarithmic growth, which is the
+/// advantage divided by the variance of a round.
+///
+/// Full Kelly betting is very volatile, so bots usually bet
+/// a `fraction` of it (one half for "half Kelly"). When the
+/// player has no advantage the bet is the table minimum.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KellyBetting {
+    pub fraction: f64,
+    pub edge: Edge,
+    /// The variance of the result of a round, in squared units
+    /// of the bet. It is about 1.3 for basic strategy.
+    pub variance: f64,
+}
+
+impl Default for KellyBetting {
+    fn default() -> KellyBetting {
+        KellyBetting {
+            fraction: 0.5,
+            edge: Edge::hi_lo(),
+            variance: 1.3,
+        }
+    }
+}
+
+impl KellyBetting {
+    pub fn bet(&self, context: &BetContext) -> u32 {
+        let advantage = self.edge.advantage(context);
+        let desired = if advantage > 0.0 {
+            self.fraction * advantage / self.variance * context.available as f64
+        } else {
+            0.0
+        };
+        context.limits.clamp(desired, context.available)
+    }
+}
+
+/// The position of a player at a tournament table.
+#[derive(PartialEq, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Standing {
+    /// The chips of all the players of the table, by seat.
+    pub chips: Vec<u32>,
+    /// The seat of the player.
+    pub seat: usize,
+    /// The number of players of the table who advance
+    /// to the next round.
+    pub advance: usize,
+    /// The number of hands left in the round, including this one.
+    pub hands_left: u32,
+}
+
+impl Standing {
+    /// The seats of the rivals of the player, by decreasing chips.
+    fn rivals(&self) -> Vec<usize> {
+        let mut rivals: Vec<usize> = (0..self.chips.len()).filter(|&s| s != self.seat).collect();
+        rivals.sort_by_key(|&seat| std::cmp::Reverse(self.chips[seat]));
+        rivals
+    }
+}
+
+/// Tournament betting : only the chip counts at the end of the round
+/// matter, so the bot looks at its rank instead of its advantage.
+///
+/// Among the players who advance, it bets the minimum, unless the
+/// first player who does not advance could overtake it on the last
+/// hand : it then bets enough to stay ahead if both win. Otherwise,
+/// it spreads the chips it lacks to advance over the hands left, and
+/// bets them all on the last hand.
+#[derive(PartialEq, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TournamentBetting {
+    pub standing: Standing,
+}
+
+impl TournamentBetting {
+    pub fn bet(&self, context: &BetContext) -> u32 {
+        let standing = &self.standing;
+        let rivals = standing.rivals();
+        let available = context.available;
+        let desired = if standing.advance == 0 || rivals.len() < standing.advance {
+            0
+        } else if standing.chips[rivals[standing.advance - 1]] < available {
+            // the best player who does not advance, and what they can win
+            let chaser = rivals[standing.advance - 1];
+            let reach = match context.table_bets.get(chaser) {
+                Some(&bet) => standing.chips[chaser] + bet,
+                None => standing.chips[chaser] + standing.chips[chaser].min(context.limits.max),
+            };
+            if standing.hands_left == 1 && reach >= available {
+                reach + 1 - available
+            } else {
+                0
+            }
+        } else {
+            let lacking = standing.chips[rivals[standing.advance - 1]] + 1 - available;
+            let hands_left = standing.hands_left.max(1);
+            lacking.div_ceil(hands_left)
+        };
+        let desired =
+            (desired as f64 / context.limits.chip as f64).ceil() * context.limits.chip as f64;
+        context.limits.clamp(desired, available)
+    }
+}
+
+/// A bet ramp : the number of units a counting bot bets
+/// as a function of the true count.
+///
+/// The ramp is a list of steps `(true count, units)`, sorted by
+/// true count. The bot bets the units of the highest step whose true
+/// count has been reached, or the units of the first step if none was.
+#[derive(PartialEq, Clone, Debug)]
+pub struct BetRamp {
+    steps: Vec<(f64, u32)>,
+}
+
+impl BetRamp {
+    /// A ramp that always bets a single unit.
+    pub fn flat() -> BetRamp {
+        BetRamp {
+            steps: vec![(0.0, 1)],
+        }
+    }
+
+    /// Parses a ramp written as a comma separated list of
+    /// `true count:units` steps, for example `1:1,2:2,3:4,4:8`.
+    pub fn parse(text: &str) -> Result<BetRamp, String> {
+        let mut steps = Vec::new();
+        for step in text.split(',') {
+            let parsed = step.split_once(':').and_then(|(count, units)| {
+                Some((count.trim().parse().ok()?, units.trim().parse().ok()?))
+            });
+            match parsed {
+                Some((count, units)) if units > 0 && f64::is_finite(count) => {
+                    steps.push((count, units))
+                }
+                _ => return Err(format!("Invalid bet ramp step '{}'", step)),
+            }
+        }
+        steps.sort_by(|a: &(f64, u32), b| a.0.partial_cmp(&b.0).unwrap());
+        Ok(BetRamp { steps })
+    }
+
+    /// The number of units to bet at the given true count.
+    pub fn bet(&self, true_count: f64) -> u32 {
+        self.steps
+            .iter()
+            .rev()
+            .find(|step| true_count >= step.0)
+            .unwrap_or(&self.steps[0])
+            .1
+    }
+
+    /// The smallest bet of the ramp.
+    pub fn min_bet(&self) -> u32 {
+        self.steps.iter().map(|step| step.1).min().unwrap()
+    }
+}
+
+impl std::fmt::Display for BetRamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let steps: Vec<String> = self
+            .steps
+            .iter()
+            .map(|(count, units)| format!("{}:{}", count, units))
+            .collect();
+        write!(f, "{}", steps.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::betting::*;
+
+    fn context<'a>(
+        available: u32,
+        dealt: &'a [u32; 10],
+        limits: &'a TableLimits,
+    ) -> BetContext<'a> {
+        BetContext {
+            available,
+            dealt,
+            decks_remaining: 2.0,
+            limits,
+            table_bets: &[],
+        }
+    }
+
+    #[test]
+    fn limits_test() {
+        let limits = TableLimits {
+            min: 10,
+            max: 500,
+            chip: 5,
+            secret_bets: false,
+        };
+        assert_eq!(10, limits.clamp(3.0, 1000));
+        assert_eq!(45, limits.clamp(49.9, 1000));
+        assert_eq!(500, limits.clamp(10_000.0, 1000));
+        assert_eq!(7, limits.clamp(45.0, 7));
+        assert_eq!(20, limits.clamp(45.0, 24));
+        assert_eq!(10, limits.clamp(45.0, 14));
+    }
+
+    #[test]
+    fn kelly_test() {
+        let limits = TableLimits::default();
+        let kelly = KellyBetting {
+            fraction: 1.0,
+            edge: Edge::Fixed(0.0125),
+            variance: 1.25,
+        };
+        assert_eq!(100, kelly.bet(&context(10_000, &[0; 10], &limits)));
+
+        // high cards were dealt : no advantage, bet the minimum
+        let mut half = KellyBetting::default();
+        let dealt = [4, 0, 0, 0, 0, 0, 0, 0, 0, 12];
+        assert_eq!(1, half.bet(&context(10_000, &dealt, &limits)));
+
+        // low cards were dealt : true count of 6, 2.5% advantage
+        let dealt = [0, 4, 4, 4, 0, 0, 0, 0, 0, 0];
+        assert_eq!(96, half.bet(&context(10_000, &dealt, &limits)));
+        half.fraction = 0.25;
+        assert_eq!(
+            48,
+            BetPolicy::Kelly(half).bet(&context(10_000, &dealt, &limits))
+        );
+    }
+
+    #[test]
+    fn tournament_test() {
+        let limits = TableLimits {
+            min: 10,
+            max: 500,
+            chip: 5,
+            secret_bets: false,
+        };
+        let betting = |chips: Vec<u32>, seat, hands_left| TournamentBetting {
+            standing: Standing {
+                chips,
+                seat,
+                advance: 1,
+                hands_left,
+            },
+        };
+        let bet = |betting: &TournamentBetting, table_bets: &[u32]| {
+            betting.bet(&BetContext {
+                table_bets,
+                ..context(
+                    betting.standing.chips[betting.standing.seat],
+                    &[0; 10],
+                    &limits,
+                )
+            })
+        };
+        // the leader bets the minimum while the round is not over
+        assert_eq!(10, bet(&betting(vec![300, 200], 0, 3), &[]));
+        // on the last hand, they bet enough to stay ahead
+        assert_eq!(105, bet(&betting(vec![300, 200], 0, 1), &[]));
+        assert_eq!(10, bet(&betting(vec![200, 300], 1, 1), &[20]));
+        assert_eq!(55, bet(&betting(vec![200, 300], 1, 1), &[150]));
+        // the others spread what they lack over the hands left
+        assert_eq!(35, bet(&betting(vec![300, 200], 1, 3), &[10]));
+        assert_eq!(105, bet(&betting(vec![300, 200], 1, 1), &[]));
+        assert_eq!(50, bet(&betting(vec![300, 50], 1, 1), &[]));
+    }
+
+    #[test]
+    fn allows_test() {
+        let limits = TableLimits {
+            min: 10,
+            max: 500,
+            chip: 5,
+            secret_bets: false,
+        };
+        assert!(limits.allows(10, 100));
+        assert!(!limits.allows(5, 100));
+        assert!(!limits.allows(12, 100));
+        assert!(!limits.allows(505, 1000));
+        assert!(!limits.allows(50, 40));
+        assert!(limits.allows(7, 7));
+    }
+
+    #[test]
+    fn clamp_is_allowed() {
+        let limits = TableLimits {
+            min: 10,
+            max: 500,
+            chip: 5,
+            secret_bets: false,
+        };
+        for available in 0..1200 {
+            for desired in [-5.0, 0.0, 3.0, 12.5, 49.9, 333.0, 10_000.0, f64::INFINITY] {
+                let bet = limits.clamp(desired, available);
+                assert!(limits.allows(bet, available), "{} of {}", bet, available);
+            }
+        }
+    }
+
+    #[test]
+    fn parse_policy_test() {
+        assert!(matches!(
+            BetPolicy::parse("half-bank"),
+            Ok(BetPolicy::HalfBank)
+        ));
+        match BetPolicy::parse("kelly:0.25:0.01") {
+            Ok(BetPolicy::Kelly(kelly)) => {
+                assert_eq!(0.25, kelly.fraction);
+                assert!(matches!(kelly.edge, Edge::Fixed(edge) if edge == 0.01));
+            }
+            _ => panic!("kelly policy not parsed"),
+        }
+        assert!(matches!(BetPolicy::parse("kelly"), Ok(BetPolicy::Kelly(_))));
+        assert!(BetPolicy::parse("kelly:half").is_err());
+        assert!(BetPolicy::parse("kelly:0").is_err());
+        match BetPolicy::parse("fibonacci:5") {
+            Ok(BetPolicy::Progression(progression)) => {
+                assert_eq!(Some(5), progression.unit);
+                assert_eq!(System::Fibonacci { step: 0 }, progression.system);
+            }
+            _ => panic!("progression not parsed"),
+        }
+        assert!(BetPolicy::parse("martingale:0").is_err());
+        assert!(BetPolicy::parse("roulette").is_err());
+        assert!(matches!(
+            BetPolicy::parse("tournament"),
+            Ok(BetPolicy::Tournament(_))
+        ));
+    }
+
+    #[test]
+    fn ramp_test() {
+        let ramp = BetRamp::parse("2:2, 1:1,3:4,4:8").unwrap();
+        assert_eq!(1, ramp.bet(-3.0));
+        assert_eq!(1, ramp.bet(1.5));
+        assert_eq!(2, ramp.bet(2.0));
+        assert_eq!(8, ramp.bet(12.0));
+        assert_eq!(1, ramp.min_bet());
+        assert_eq!("1:1,2:2,3:4,4:8", &format!("{}", ramp));
+    }
+
+    #[test]
+    fn invalid_ramp() {
+        assert!(BetRamp::parse("1:1,2").is_err());
+        assert!(BetRamp::parse("a:1").is_err());
+        assert!(BetRamp::parse("1:0").is_err());
+        assert!(BetRamp::parse("nan:1,1:2").is_err());
+        assert!(BetRamp::parse("inf:1").is_err());
+        assert_eq!(1, BetRamp::flat().bet(5.0));
+    }
+}
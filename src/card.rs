@@ -1,3 +1,5 @@
+use crate::art::{card_style, suit_symbol};
+
 use std::fmt::Display;
 
 /// The four Suits of any classical card
@@ -7,7 +9,7 @@ use std::fmt::Display;
 /// Display.
 /// It also includes a static method [Suit::from_int]
 /// that maps an u32 supplied as argument to one of the suits.
-#[derive(PartialEq)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum Suit {
     Spades,
     Hearts,
@@ -15,18 +17,11 @@ pub enum Suit {
     Clubs,
 }
 
+/// Suits are displayed with their Unicode symbol (`♥`), or
+/// their initial (`H`) when the [card_style] is ASCII.
 impl Display for Suit {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Suit::Spades => "♠",
-                Suit::Hearts => "♥",
-                Suit::Diamonds => "♦",
-                Suit::Clubs => "♣",
-            }
-        )
+        write!(f, "{}", suit_symbol(*self, card_style().unicode))
     }
 }
 
@@ -48,7 +43,7 @@ impl Suit {
 /// Display.
 /// It also includes a static method [Rank::from_int]
 /// that maps an u32 supplied as argument to one of the ranks.
-#[derive(PartialEq)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum Rank {
     Two,
     Three,
@@ -116,7 +111,7 @@ impl Rank {
 /// It also includes a static method [Card::card_pack] to
 /// create a new card pack of 52 [Card]s,
 /// made of all combinations of [Suit]s and [Rank]s
-#[derive(PartialEq)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub struct Card {
     pub suit: Suit,
     pub rank: Rank,
@@ -141,6 +136,30 @@ impl std::fmt::Display for Card {
     }
 }
 
+/// Cards are parsed from their rank followed by their suit, as
+/// a letter or a Unicode symbol : `10C`, `AH` or `A♥`.
+impl std::str::FromStr for Card {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Card, String> {
+        let invalid = || format!("Invalid card '{}'", text);
+        let suit_char = text.chars().last().ok_or_else(invalid)?;
+        let suit = match suit_char.to_ascii_uppercase() {
+            'S' | '♠' => Suit::Spades,
+            'H' | '♥' => Suit::Hearts,
+            'D' | '♦' => Suit::Diamonds,
+            'C' | '♣' => Suit::Clubs,
+            _ => return Err(invalid()),
+        };
+        let rank_text = &text[..text.len() - suit_char.len_utf8()];
+        let rank = (0..13)
+            .map(Rank::from_int)
+            .find(|rank| rank.to_string().eq_ignore_ascii_case(rank_text))
+            .ok_or_else(invalid)?;
+        Ok(Card { suit, rank })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::card::*;
@@ -159,6 +178,23 @@ mod tests {
         )
     }
 
+    #[test]
+    fn card_parse() {
+        let nine_of_clubs = Card {
+            rank: Rank::Nine,
+            suit: Suit::Clubs,
+        };
+        assert_eq!(Ok(nine_of_clubs), "9C".parse());
+        assert_eq!(Ok(nine_of_clubs), "9♣".parse());
+        for card in Card::card_pack() {
+            assert_eq!(Ok(card), card.to_string().parse());
+        }
+        assert_eq!(Rank::Ten, "10h".parse::<Card>().unwrap().rank);
+        assert!("1C".parse::<Card>().is_err());
+        assert!("9".parse::<Card>().is_err());
+        assert!("".parse::<Card>().is_err());
+    }
+
     #[test]
     fn card_display() {
         assert_eq!(
@@ -0,0 +1,599 @@
+use crate::card::{Card, Suit};
+use crate::deviation::IndexHand;
+use crate::eor::representative_hand;
+use crate::math::{hand_value, is_soft, rank_of_value, rank_value};
+use crate::player::PlayerAction;
+use crate::rules::Rules;
+use crate::strategy::{upcard_value, Situation, Strategy};
+
+/// A cell of a strategy chart.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Cell {
+    /// `H` : hit.
+    Hit,
+    /// `S` : stand.
+    Stand,
+    /// `D` : double if allowed, otherwise hit.
+    Double,
+    /// `Ds` : double if allowed, otherwise stand.
+    DoubleOrStand,
+    /// `P` : split.
+    Split,
+    /// `Ph` : split if doubling after a split is allowed, otherwise hit.
+    SplitIfDas,
+    /// `Rh` : surrender if allowed, otherwise hit.
+    SurrenderOrHit,
+    /// `Rs` : surrender if allowed, otherwise stand.
+    SurrenderOrStand,
+}
+
+/// The cells a chart may contain, as they are written.
+const CELLS: [(&str, Cell); 8] = [
+    ("H", Cell::Hit),
+    ("S", Cell::Stand),
+    ("D", Cell::Double),
+    ("Ds", Cell::DoubleOrStand),
+    ("P", Cell::Split),
+    ("Ph", Cell::SplitIfDas),
+    ("Rh", Cell::SurrenderOrHit),
+    ("Rs", Cell::SurrenderOrStand),
+];
+
+impl Cell {
+    /// Parses a cell, ignoring the case of the letters.
+    pub fn parse(text: &str) -> Option<Cell> {
+        CELLS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(text))
+            .map(|&(_, cell)| cell)
+    }
+
+    /// The name of this cell in a chart file, as in `Ds`.
+    pub fn name(self) -> &'static str {
+        CELLS.iter().find(|&&(_, cell)| cell == self).unwrap().0
+    }
+
+    /// The action this cell stands for in the given situation.
+    pub fn action(self, situation: &Situation) -> PlayerAction {
+        let or = |allowed: bool, action, fallback| if allowed { action } else { fallback };
+        match self {
+            Cell::Hit => PlayerAction::Hit,
+            Cell::Stand => PlayerAction::Stand,
+            Cell::Double => or(
+                situation.can_double,
+                PlayerAction::Double,
+                PlayerAction::Hit,
+            ),
+            Cell::DoubleOrStand => or(
+                situation.can_double,
+                PlayerAction::Double,
+                PlayerAction::Stand,
+            ),
+            Cell::Split => PlayerAction::Split,
+            Cell::SplitIfDas => or(
+                situation.rules.double_after_split,
+                PlayerAction::Split,
+                PlayerAction::Hit,
+            ),
+            Cell::SurrenderOrHit => or(
+                situation.can_surrender,
+                PlayerAction::Surrender,
+                PlayerAction::Hit,
+            ),
+            Cell::SurrenderOrStand => or(
+                situation.can_surrender,
+                PlayerAction::Surrender,
+                PlayerAction::Stand,
+            ),
+        }
+    }
+}
+
+/// The cells of a row of a chart, for the dealer
+/// upcards 2 to 10 and then the Ace.
+pub type Row = [Cell; 10];
+
+/// A strategy given as a chart, usually loaded from a text file
+/// written by hand : see [Chart::parse] for the format.
+///
+/// Hands without a row in the chart hit below 17 (below 18
+/// for soft totals) and stand otherwise. Pairs without a row,
+/// or that cannot be split, are played like their total.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Chart {
+    /// The rows of the hard totals, indexed by total.
+    pub hard: [Option<Row>; 22],
+    /// The rows of the soft totals, indexed by total.
+    pub soft: [Option<Row>; 22],
+    /// The rows of the pairs, indexed by card value (1 for Aces).
+    pub pairs: [Option<Row>; 11],
+}
+
+/// A section of a chart.
+#[derive(PartialEq, Clone, Copy)]
+enum Section {
+    Hard,
+    Soft,
+    Pairs,
+}
+
+/// The words of a line along with their column, starting from 1.
+/// Words are separated by spaces, tabs, commas or semicolons.
+fn words(line: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (column, (i, c)) in line.char_indices().enumerate() {
+        let separator = c.is_whitespace() || c == ',' || c == ';';
+        match (start, separator) {
+            (None, false) => start = Some((column + 1, i)),
+            (Some((column, begin)), true) => {
+                words.push((column, &line[begin..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some((column, begin)) = start {
+        words.push((column, &line[begin..]));
+    }
+    words
+}
+
+/// The value of a dealer upcard written in a chart header,
+/// 11 for an Ace.
+fn upcard(text: &str) -> Option<u32> {
+    match text.to_ascii_uppercase().as_str() {
+        "A" => Some(11),
+        "T" => Some(10),
+        other => other.parse().ok().filter(|value| (2..=10).contains(value)),
+    }
+}
+
+/// The card value of a pair written as a row label : `8`, `88`,
+/// `A`, `AA`, `T`, `TT` or `10`.
+fn pair_value(text: &str) -> Option<u32> {
+    let text = text.to_ascii_uppercase();
+    let single = if text.len() == 2 && text.is_ascii() && text[..1] == text[1..] {
+        &text[..1]
+    } else {
+        text.as_str()
+    };
+    match single {
+        "A" => Some(1),
+        "T" => Some(10),
+        other => other.parse().ok().filter(|value| (2..=10).contains(value)),
+    }
+}
+
+/// The total of a row label : a number, or an Ace followed by the
+/// other card (as in `A7`) for soft totals.
+fn total(text: &str, section: Section) -> Option<u32> {
+    let upper = text.to_ascii_uppercase();
+    match (section, upper.strip_prefix('A')) {
+        (Section::Soft, Some(card)) => card
+            .parse::<u32>()
+            .ok()
+            .filter(|card| (1..=10).contains(card))
+            .map(|card| card + 11),
+        _ => text.parse().ok(),
+    }
+}
+
+impl Chart {
+    /// Parses a chart.
+    ///
+    /// A chart has up to three sections : hard totals, soft totals and
+    /// pairs. Each one starts with a header line naming the section
+    /// (`hard`, `soft` or `pairs`) followed by the ten dealer upcards
+    /// (`2` to `10` and `A`, in any order). The following lines each
+    /// start with a row label (a total such as `16`, a soft total such
+    /// as `18` or `A7`, or a pair such as `8`, `88` or `AA`) followed
+    /// by one cell per upcard : `H`, `S`, `D`, `Ds`, `P`, `Ph`, `Rh` or
+    /// `Rs`. Cells are separated by spaces, tabs, commas or semicolons,
+    /// so CSV files are accepted, and everything after a `#` is ignored.
+    ///
+    /// Errors give the line and column of the problem.
+    pub fn parse(text: &str) -> Result<Chart, String> {
+        let mut chart = Chart {
+            hard: [None; 22],
+            soft: [None; 22],
+            pairs: [None; 11],
+        };
+        let mut section: Option<(Section, [usize; 10])> = None;
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap();
+            let words = words(line);
+            let error = |column: usize, message: String| {
+                format!("Line {}, column {} : {}", number + 1, column, message)
+            };
+            let (label_column, label) = match words.first() {
+                Some(&first) => first,
+                None => continue,
+            };
+            let cells = &words[1..];
+
+            let header = match label.to_ascii_lowercase().as_str() {
+                "hard" => Some(Section::Hard),
+                "soft" => Some(Section::Soft),
+                "pair" | "pairs" => Some(Section::Pairs),
+                _ => None,
+            };
+            if let Some(header) = header {
+                let mut columns = [0; 10];
+                let mut seen = [false; 10];
+                for (i, &(column, text)) in cells.iter().enumerate() {
+                    let up = upcard(text).ok_or_else(|| {
+                        error(column, format!("unknown dealer upcard '{}'", text))
+                    })?;
+                    let index = up as usize - 2;
+                    if seen[index] || i >= 10 {
+                        return Err(error(column, format!("upcard '{}' is given twice", text)));
+                    }
+                    seen[index] = true;
+                    columns[i] = index;
+                }
+                if cells.len() < 10 {
+                    return Err(error(
+                        line.chars().count() + 1,
+                        format!("the header lists {} upcards instead of 10", cells.len()),
+                    ));
+                }
+                section = Some((header, columns));
+                continue;
+            }
+
+            let (section, columns) = section.ok_or_else(|| {
+                error(
+                    label_column,
+                    String::from("expected a section header : hard, soft or pairs"),
+                )
+            })?;
+            let (rows, index, name): (&mut [Option<Row>], _, _) = match section {
+                Section::Hard => (&mut chart.hard, total(label, section), "hard total"),
+                Section::Soft => (&mut chart.soft, total(label, section), "soft total"),
+                Section::Pairs => (&mut chart.pairs, pair_value(label), "pair"),
+            };
+            let valid = match section {
+                Section::Hard => 4..=21,
+                Section::Soft => 12..=21,
+                Section::Pairs => 1..=10,
+            };
+            let index = index
+                .filter(|index| valid.contains(index))
+                .ok_or_else(|| error(label_column, format!("invalid {} '{}'", name, label)))?
+                as usize;
+            if rows[index].is_some() {
+                return Err(error(
+                    label_column,
+                    format!("{} '{}' is given twice", name, label),
+                ));
+            }
+
+            let mut row = [Cell::Hit; 10];
+            for (i, &(column, text)) in cells.iter().enumerate() {
+                if i >= 10 {
+                    return Err(error(column, String::from("too many cells, expected 10")));
+                }
+                let cell = Cell::parse(text).ok_or_else(|| {
+                    error(
+                        column,
+                        format!(
+                            "unknown cell '{}', expected one of H, S, D, Ds, P, Ph, Rh or Rs",
+                            text
+                        ),
+                    )
+                })?;
+                if section != Section::Pairs && matches!(cell, Cell::Split | Cell::SplitIfDas) {
+                    return Err(error(
+                        column,
+                        format!("'{}' is only allowed for pairs", text),
+                    ));
+                }
+                row[columns[i]] = cell;
+            }
+            if cells.len() < 10 {
+                return Err(error(
+                    line.chars().count() + 1,
+                    format!("missing cells, expected 10 but found {}", cells.len()),
+                ));
+            }
+            rows[index] = Some(row);
+        }
+        if chart.hard.iter().all(Option::is_none) {
+            return Err(String::from("The chart has no hard totals"));
+        }
+        Ok(chart)
+    }
+
+    /// Loads a chart from a file.
+    pub fn load(path: &str) -> Result<Chart, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read chart {} : {}", path, e))?;
+        Chart::parse(&text).map_err(|e| format!("{} : {}", path, e))
+    }
+
+    /// Builds the chart of any strategy for the given rules, by asking
+    /// it for its action on every two-card hand against every upcard,
+    /// with a neutral count. The chart has the hard totals 5 to 17, the
+    /// soft totals 13 to 20 and all the pairs.
+    pub fn from_strategy(strategy: &dyn Strategy, rules: &Rules) -> Chart {
+        let mut chart = Chart {
+            hard: [None; 22],
+            soft: [None; 22],
+            pairs: [None; 11],
+        };
+        let row = |hand| {
+            let mut row = [Cell::Hit; 10];
+            for (column, cell) in row.iter_mut().enumerate() {
+                *cell = cell_of(strategy, hand, column as u32 + 2, rules);
+            }
+            Some(row)
+        };
+        for total in 5..=17 {
+            chart.hard[total] = row(IndexHand::Hard(total as u32));
+        }
+        for total in 13..=20 {
+            chart.soft[total] = row(IndexHand::Soft(total as u32));
+        }
+        for value in 1..=10 {
+            chart.pairs[value] = row(IndexHand::Pair(value as u32));
+        }
+        chart
+    }
+}
+
+/// The cell of a strategy for a hand against an upcard (11 for an
+/// Ace), found by asking it again when some actions are not allowed.
+fn cell_of(strategy: &dyn Strategy, hand: IndexHand, up: u32, rules: &Rules) -> Cell {
+    let card = |value| Card {
+        rank: rank_of_value(value),
+        suit: Suit::Spades,
+    };
+    let cards = representative_hand(hand).map(card);
+    let dealer_card = card(if up == 11 { 1 } else { up });
+    let pair = matches!(hand, IndexHand::Pair(_));
+    let ask = |can_double, can_surrender, rules: &Rules| {
+        strategy.decide(&Situation {
+            hand: &cards,
+            dealer_card: &dealer_card,
+            can_double,
+            can_split: pair,
+            can_surrender,
+            dealt: &[0; 10],
+            decks_remaining: rules.num_packs as f64,
+            rules,
+        })
+    };
+    match ask(true, rules.surrender, rules) {
+        PlayerAction::Split => {
+            let no_das = Rules {
+                double_after_split: false,
+                ..*rules
+            };
+            if rules.double_after_split
+                && ask(true, rules.surrender, &no_das) != PlayerAction::Split
+            {
+                Cell::SplitIfDas
+            } else {
+                Cell::Split
+            }
+        }
+        PlayerAction::Surrender => match ask(true, false, rules) {
+            PlayerAction::Stand => Cell::SurrenderOrStand,
+            _ => Cell::SurrenderOrHit,
+        },
+        PlayerAction::Double => match ask(false, false, rules) {
+            PlayerAction::Stand => Cell::DoubleOrStand,
+            _ => Cell::Double,
+        },
+        PlayerAction::Stand => Cell::Stand,
+        PlayerAction::Hit => Cell::Hit,
+    }
+}
+
+impl Strategy for Chart {
+    fn decide(&self, situation: &Situation) -> PlayerAction {
+        let column = upcard_value(situation.dealer_card) as usize - 2;
+        let hand = situation.hand;
+        if situation.can_split {
+            if let Some(row) = self.pairs[rank_value(&hand[0].rank) as usize] {
+                return row[column].action(situation);
+            }
+        }
+        let total = hand_value(hand) as usize;
+        let soft = is_soft(hand);
+        let row = match total {
+            0..=21 if soft => self.soft[total],
+            0..=21 => self.hard[total],
+            _ => None,
+        };
+        match row {
+            Some(row) => row[column].action(situation),
+            None if total < if soft { 18 } else { 17 } => PlayerAction::Hit,
+            None => PlayerAction::Stand,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::card::*;
+    use crate::chart::*;
+    use crate::math::{is_splittable, rank_of_value};
+    use crate::rules::Rules;
+    use crate::strategy::BasicStrategy;
+
+    const BASIC: &str = include_str!("../charts/basic.txt");
+
+    fn card(value: u32) -> Card {
+        Card {
+            rank: rank_of_value(value),
+            suit: Suit::Clubs,
+        }
+    }
+
+    #[test]
+    fn basic_chart_plays_basic_strategy() {
+        let chart = Chart::parse(BASIC).unwrap();
+        let tables = [
+            Rules::default(),
+            Rules {
+                double_after_split: false,
+                surrender: true,
+                ..Rules::default()
+            },
+        ];
+        for rules in &tables {
+            for first in 1..=10 {
+                for second in first..=10 {
+                    for up in 1..=10 {
+                        let hand = [card(first), card(second)];
+                        for &(can_double, can_split) in &[(true, true), (false, false)] {
+                            let situation = Situation {
+                                hand: &hand,
+                                dealer_card: &card(up),
+                                can_double,
+                                can_split: can_split && is_splittable(&hand),
+                                can_surrender: rules.surrender && can_double,
+                                dealt: &[0; 10],
+                                decks_remaining: 4.0,
+                                rules,
+                            };
+                            assert_eq!(
+                                BasicStrategy.decide(&situation),
+                                chart.decide(&situation),
+                                "{} {} against {}",
+                                first,
+                                second,
+                                up
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn chart_of_basic_strategy() {
+        let rules = Rules {
+            surrender: true,
+            ..Rules::default()
+        };
+        let chart = Chart::from_strategy(&BasicStrategy, &rules);
+        let file = Chart::parse(BASIC).unwrap();
+        let rows = |chart: &Chart| {
+            let mut rows = chart.hard.to_vec();
+            rows.extend_from_slice(&chart.soft);
+            rows.extend_from_slice(&chart.pairs);
+            rows
+        };
+        for (generated, written) in rows(&chart).iter().zip(rows(&file)) {
+            if written.is_some() {
+                assert_eq!(*generated, written);
+            }
+        }
+        assert_eq!(Some([Cell::Hit; 10]), chart.hard[5]);
+        assert_eq!("Ds", Cell::DoubleOrStand.name());
+    }
+
+    #[test]
+    fn csv_and_defaults() {
+        let chart = Chart::parse(
+            "Hard,2,3,4,5,6,7,8,9,T,A\n\
+             16,S,S,S,S,S,H,H,Rh,Rh,rh\n\
+             pairs;a;2;3;4;5;6;7;8;9;10\n\
+             AA;H;P;P;P;P;P;P;P;P;P # Aces against the Ace are hit\n",
+        )
+        .unwrap();
+        let row = chart.hard[16].unwrap();
+        assert_eq!(Cell::Stand, row[0]);
+        assert_eq!(Cell::SurrenderOrHit, row[9]);
+        let aces = chart.pairs[1].unwrap();
+        assert_eq!(Cell::Hit, aces[9]);
+        assert_eq!(Cell::Split, aces[0]);
+        assert!(chart.soft.iter().all(Option::is_none));
+
+        let rules = Rules::default();
+        let decide = |hand: &[Card], up| {
+            chart.decide(&Situation {
+                hand,
+                dealer_card: &card(up),
+                can_double: true,
+                can_split: is_splittable(hand),
+                can_surrender: false,
+                dealt: &[0; 10],
+                decks_remaining: 4.0,
+                rules: &rules,
+            })
+        };
+        assert_eq!(PlayerAction::Hit, decide(&[card(10), card(6)], 10));
+        assert_eq!(PlayerAction::Stand, decide(&[card(10), card(6)], 6));
+        assert_eq!(PlayerAction::Hit, decide(&[card(10), card(5)], 6));
+        assert_eq!(PlayerAction::Stand, decide(&[card(10), card(7)], 6));
+        assert_eq!(PlayerAction::Hit, decide(&[card(1), card(6)], 6));
+        assert_eq!(PlayerAction::Stand, decide(&[card(1), card(7)], 6));
+        assert_eq!(PlayerAction::Split, decide(&[card(1), card(1)], 5));
+    }
+
+    #[test]
+    fn errors_give_line_and_column() {
+        let error = |text: &str| Chart::parse(text).unwrap_err();
+        let header = "hard 2 3 4 5 6 7 8 9 10 A\n";
+        assert_eq!(
+            "Line 2, column 12 : unknown cell 'X', expected one of H, S, D, Ds, P, Ph, Rh or Rs",
+            error(&format!("{}16 S S S S X H H H H H", header))
+        );
+        assert_eq!(
+            "Line 2, column 1 : invalid hard total '25'",
+            error(&format!("{}25 S S S S S S S S S S", header))
+        );
+        assert_eq!(
+            "Line 2, column 21 : missing cells, expected 10 but found 9",
+            error(&format!("{}16 S S S S S H H H H", header))
+        );
+        assert_eq!(
+            "Line 2, column 24 : too many cells, expected 10",
+            error(&format!("{}16 S S S S S H H H H H H", header))
+        );
+        assert_eq!(
+            "Line 2, column 4 : 'P' is only allowed for pairs",
+            error(&format!("{}16 P S S S S H H H H H", header))
+        );
+        assert_eq!(
+            "Line 1, column 1 : expected a section header : hard, soft or pairs",
+            error("16 S S S S S H H H H H")
+        );
+        assert_eq!(
+            "Line 1, column 10 : upcard '2' is given twice",
+            error("hard 2 3 2 5 6 7 8 9 10 A")
+        );
+        assert_eq!(
+            "Line 1, column 12 : unknown dealer upcard 'J'",
+            error("soft 2 3 4 J 6 7 8 9 10 A")
+        );
+        assert_eq!(
+            "Line 3, column 1 : hard total '16' is given twice",
+            error(&format!(
+                "{}16 S S S S S H H H H H\n16 S S S S S H H H H H",
+                header
+            ))
+        );
+        assert_eq!(
+            "The chart has no hard totals",
+            error("soft 2 3 4 5 6 7 8 9 10 A\n")
+        );
+    }
+
+    #[test]
+    fn labels() {
+        assert_eq!(Some(10), pair_value("TT"));
+        assert_eq!(Some(10), pair_value("10"));
+        assert_eq!(Some(1), pair_value("aa"));
+        assert_eq!(Some(8), pair_value("88"));
+        assert_eq!(None, pair_value("89"));
+        assert_eq!(Some(18), total("A7", Section::Soft));
+        assert_eq!(Some(18), total("18", Section::Soft));
+        assert_eq!(None, total("A7", Section::Hard));
+    }
+}
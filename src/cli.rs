@@ -0,0 +1,230 @@
+use crate::betting::TableLimits;
+use crate::rules::Rules;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// The options read by [Args::rules].
+pub const RULE_OPTIONS: &[&str] = &[
+    "decks",
+    "h17",
+    "no-das",
+    "surrender",
+    "insurance",
+    "penetration",
+];
+
+/// The options read by [Args::limits].
+pub const LIMIT_OPTIONS: &[&str] = &["min-bet", "max-bet", "chip"];
+
+/// The parsed command line of the program.
+///
+/// The first positional argument, if any, is the subcommand.
+/// Options take the form `--key value` or `--key=value`.
+/// An option directly followed by another option (or by nothing)
+/// is a flag, and holds the value "true".
+///
+/// Each command checks the options it was given with
+/// [Args::check_options], so that misspelled options are reported.
+#[derive(Default, Debug)]
+pub struct Args {
+    pub command: Option<String>,
+    pub positional: Vec<String>,
+    options: HashMap<String, String>,
+}
+
+impl Args {
+    /// Parses the arguments given to the program, without
+    /// the name of the executable.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Args {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter().peekable();
+        while let Some(arg) = args.next() {
+            if let Some(option) = arg.strip_prefix("--") {
+                if let Some((key, value)) = option.split_once('=') {
+                    parsed.options.insert(key.to_string(), value.to_string());
+                } else {
+                    let value = match args.peek() {
+                        Some(next) if !next.starts_with("--") => args.next().unwrap(),
+                        _ => String::from("true"),
+                    };
+                    parsed.options.insert(option.to_string(), value);
+                }
+            } else if parsed.command.is_none() {
+                parsed.command = Some(arg);
+            } else {
+                parsed.positional.push(arg);
+            }
+        }
+        parsed
+    }
+
+    /// The raw value of an option, if it was given.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.options.get(key).map(|s| s.as_str())
+    }
+
+    /// Whether a flag was given. Its value, if any,
+    /// must be `true` or `false`.
+    pub fn flag(&self, key: &str) -> Result<bool, String> {
+        self.parse_or(key, false)
+    }
+
+    /// Checks that every option given is in one of the `known`
+    /// lists of options of the command.
+    pub fn check_options(&self, known: &[&[&str]]) -> Result<(), String> {
+        let mut keys: Vec<&String> = self.options.keys().collect();
+        keys.sort();
+        match keys
+            .into_iter()
+            .find(|key| !known.iter().any(|options| options.contains(&key.as_str())))
+        {
+            Some(key) => Err(format!("Unknown option --{}", key)),
+            None => Ok(()),
+        }
+    }
+
+    /// Parses the value of an option, or returns the default
+    /// value when the option was not given.
+    pub fn parse_or<T: FromStr>(&self, key: &str, default: T) -> Result<T, String> {
+        match self.get(key) {
+            None => Ok(default),
+            Some(value) => value
+                .parse()
+                .map_err(|_| format!("Invalid value '{}' for option --{}", value, key)),
+        }
+    }
+
+    /// The value of a flag, or the default value when the flag
+    /// was not given. Like [Args::flag], a value given with the
+    /// flag (as in `--h17=false`) must be `true` or `false`.
+    fn flag_or(&self, key: &str, default: bool) -> Result<bool, String> {
+        match self.get(key) {
+            None => Ok(default),
+            Some(_) => self.flag(key),
+        }
+    }
+
+    /// Builds the table rules from the options `--decks`, `--h17`,
+    /// `--no-das`, `--surrender`, `--insurance` and `--penetration`.
+    pub fn rules(&self) -> Result<Rules, String> {
+        self.rules_over(&Rules::default())
+    }
+
+    /// Builds the table rules like [Args::rules], taking the
+    /// rules of `base` for the options that were not given.
+    pub fn rules_over(&self, base: &Rules) -> Result<Rules, String> {
+        let rules = Rules {
+            num_packs: self.parse_or("decks", base.num_packs)?,
+            dealer_hits_soft_17: self.flag_or("h17", base.dealer_hits_soft_17)?,
+            double_after_split: !self.flag_or("no-das", !base.double_after_split)?,
+            surrender: self.flag_or("surrender", base.surrender)?,
+            insurance: self.flag_or("insurance", base.insurance)?,
+            penetration: self.parse_or("penetration", base.penetration)?,
+        };
+        if rules.num_packs == 0 {
+            return Err(String::from("The shoe needs at least one deck"));
+        }
+        if !(0.0..=1.0).contains(&rules.penetration) {
+            return Err(String::from("The penetration must be between 0 and 1"));
+        }
+        Ok(rules)
+    }
+
+    /// Builds the betting limits of the table from the options
+    /// `--min-bet`, `--max-bet` and `--chip`.
+    pub fn limits(&self) -> Result<TableLimits, String> {
+        self.limits_over(&TableLimits::default())
+    }
+
+    /// Builds the betting limits like [Args::limits], taking the
+    /// limits of `base` for the options that were not given.
+    pub fn limits_over(&self, base: &TableLimits) -> Result<TableLimits, String> {
+        let limits = TableLimits {
+            min: self.parse_or("min-bet", base.min)?,
+            max: self.parse_or("max-bet", base.max)?,
+            chip: self.parse_or("chip", base.chip)?,
+            secret_bets: base.secret_bets,
+        };
+        if limits.chip == 0 || limits.min > limits.max {
+            return Err(String::from(
+                "The chip value must be positive and the minimum bet below the maximum",
+            ));
+        }
+        if limits.min % limits.chip != 0 || limits.max % limits.chip != 0 {
+            return Err(String::from(
+                "The minimum and maximum bets must be multiples of the chip value",
+            ));
+        }
+        Ok(limits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cli::*;
+
+    fn args(line: &str) -> Args {
+        Args::parse(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn parse_test() {
+        let parsed = args("compare basic simple --rounds 10 --h17 --seed=3");
+        assert_eq!(Some("compare"), parsed.command.as_deref());
+        assert_eq!(vec!["basic", "simple"], parsed.positional);
+        assert_eq!(Some("10"), parsed.get("rounds"));
+        assert_eq!(Some("3"), parsed.get("seed"));
+        assert_eq!(Ok(true), parsed.flag("h17"));
+        assert_eq!(Ok(false), parsed.flag("no-das"));
+        assert!(args("--h17 yes").flag("h17").is_err());
+        assert_eq!(Ok(false), args("--h17 false").flag("h17"));
+    }
+
+    #[test]
+    fn check_options_test() {
+        let parsed = args("compare --rounds 10 --h17");
+        assert!(parsed.check_options(&[&["rounds"], RULE_OPTIONS]).is_ok());
+        assert_eq!(
+            Err(String::from("Unknown option --h17")),
+            parsed.check_options(&[&["rounds", "seed"]])
+        );
+        assert!(args("--round 10").check_options(&[&["rounds"]]).is_err());
+    }
+
+    #[test]
+    fn rules_test() {
+        let rules = args("--decks 6 --h17").rules().unwrap();
+        assert_eq!(6, rules.num_packs);
+        assert!(rules.dealer_hits_soft_17 && rules.double_after_split);
+        assert!(args("--decks 0").rules().is_err());
+        assert!(args("--decks six").rules().is_err());
+    }
+
+    #[test]
+    fn rules_over_test() {
+        let base = Rules {
+            num_packs: 1,
+            dealer_hits_soft_17: true,
+            double_after_split: false,
+            ..Rules::default()
+        };
+        assert_eq!(base, args("").rules_over(&base).unwrap());
+        let rules = args("--decks 2 --h17=false --no-das false")
+            .rules_over(&base)
+            .unwrap();
+        assert_eq!(2, rules.num_packs);
+        assert!(!rules.dealer_hits_soft_17 && rules.double_after_split);
+    }
+
+    #[test]
+    fn limits_test() {
+        let limits = args("--min-bet 5 --chip 5").limits().unwrap();
+        assert_eq!((5, 1000, 5), (limits.min, limits.max, limits.chip));
+        assert!(args("--min-bet 50 --max-bet 10").limits().is_err());
+        assert!(args("--chip 0").limits().is_err());
+        assert!(args("--min-bet 12 --chip 5").limits().is_err());
+        assert!(args("--min-bet 10 --max-bet 502 --chip 5")
+            .limits()
+            .is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use crate::betting::TableLimits;
+use crate::card::Card;
+use crate::display::{bank_chart, stats_text};
+use crate::graph::terminal_width;
+use crate::rules::Rules;
+use crate::save::{RoundStart, DEFAULT_SAVE_FILE};
+use crate::stats::PlayerStats;
+
+use std::io::stdin;
+
+/// What the colon commands can see of the game.
+pub struct Session<'a> {
+    /// The names of the players, without the dealer.
+    pub names: &'a [String],
+    pub rules: &'a Rules,
+    pub limits: &'a TableLimits,
+    pub stats: &'a [PlayerStats],
+    /// The banks of the players at the start of the game
+    /// and after each round.
+    pub history: &'a [Vec<u32>],
+    /// The money the players have left, not including their bets.
+    pub bank: &'a [u32],
+    /// The cards left in the shoe.
+    pub pack: &'a [Card],
+    /// The game as `:save` saves it, or None
+    /// when the game cannot be saved.
+    pub saved: Option<&'a RoundStart<'a>>,
+}
+
+/// The players ended the game, with `:quit` or by closing the
+/// input, which stops the round being played.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Quit;
+
+/// A command humans may type at any prompt of the game,
+/// starting with a colon.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum Command {
+    /// Ends the game after showing the summary of the session.
+    Quit,
+    /// Saves the game to a file, or to [DEFAULT_SAVE_FILE].
+    Save(Option<String>),
+    Stats,
+    Graph,
+    Rules,
+    Bank,
+    Shoe,
+    Help,
+}
+
+/// The colon commands, with what they do.
+const COMMANDS: [(&str, &str); 7] = [
+    (":quit", "end the game and show the summary of the session"),
+    (
+        ":save [FILE]",
+        "save the game, to resume it at the start of the round",
+    ),
+    (":stats", "show the statistics of the session"),
+    (":graph", "show the chart of the banks"),
+    (":rules", "show the rules and the limits of the table"),
+    (":bank", "show the money of each player"),
+    (":shoe", "show the cards left in the shoe"),
+];
+
+/// Parses a colon command, such as `:stats` or `:save game.txt`.
+///
+/// Returns None when the text is not a colon command, and an
+/// error when the command is unknown.
+pub fn parse_command(text: &str) -> Option<Result<Command, String>> {
+    let text = text.trim().strip_prefix(':')?;
+    let mut words = text.split_whitespace();
+    let name = words.next().unwrap_or("").to_lowercase();
+    let argument = words.next().map(String::from);
+    let command = match name.as_str() {
+        "q" | "quit" | "exit" => Command::Quit,
+        "save" => Command::Save(argument),
+        "stats" => Command::Stats,
+        "graph" => Command::Graph,
+        "rules" => Command::Rules,
+        "bank" => Command::Bank,
+        "shoe" => Command::Shoe,
+        "help" | "?" => Command::Help,
+        other => {
+            return Some(Err(format!(
+                "Unknown command ':{}'. Type :help for the commands.",
+                other
+            )))
+        }
+    };
+    Some(Ok(command))
+}
+
+/// The text a command shows, or None for the commands
+/// that do more than showing text.
+pub fn command_text(command: &Command, session: &Session) -> Option<String> {
+    let text = match command {
+        Command::Rules => {
+            let (rules, limits) = (session.rules, session.limits);
+            format!(
+                "Rules : {} with {} decks, reshuffled after {:.0}% of the shoe.\n\
+                 Bets go from {} to {}, by {}.\n",
+                rules,
+                rules.num_packs,
+                rules.penetration * 100.0,
+                limits.min,
+                limits.max,
+                limits.chip
+            )
+        }
+        Command::Bank => session
+            .names
+            .iter()
+            .zip(session.bank)
+            .map(|(name, bank)| format!("{} : {}\n", name, bank))
+            .collect(),
+        Command::Shoe => {
+            let rules = session.rules;
+            let cut = (rules.num_packs * 52) as f64 * (1.0 - rules.penetration);
+            format!(
+                "{} cards remaining ({:.1} decks left). \
+                 The shoe is reshuffled when fewer than {:.0} cards are left.\n",
+                session.pack.len(),
+                session.pack.len() as f64 / 52.0,
+                cut.ceil()
+            )
+        }
+        Command::Help => COMMANDS
+            .iter()
+            .map(|(command, help)| format!("  {:<12} : {}\n", command, help))
+            .collect(),
+        Command::Quit | Command::Save(_) | Command::Stats | Command::Graph => return None,
+    };
+    Some(text)
+}
+
+/// Runs a command, and returns the text it shows, or [Quit]
+/// when it ends the game.
+pub fn run_command(command: &Command, session: &Session) -> Result<String, Quit> {
+    let text = match command {
+        Command::Quit => return Err(Quit),
+        Command::Stats => stats_text(session.stats, session.names),
+        Command::Graph => bank_chart(session.history, session.names, terminal_width()),
+        Command::Save(path) => {
+            let path = path.as_deref().unwrap_or(DEFAULT_SAVE_FILE);
+            let saved = session
+                .saved
+                .map(|start| start.saved_game(session.stats, session.history));
+            match saved.as_ref().map(|saved| (saved, saved.save(path))) {
+                Some((saved, Ok(()))) => format!(
+                    "The game is saved to {}, to be resumed at round {} with --resume {}.\n",
+                    path,
+                    saved.history.len(),
+                    path
+                ),
+                Some((_, Err(message))) => format!("{}\n", message),
+                None => String::from("This game cannot be saved.\n"),
+            }
+        }
+        command => command_text(command, session).unwrap(),
+    };
+    Ok(text)
+}
+
+/// Reads the answer of a human from the terminal, without its line
+/// break, running the colon commands typed before it.
+///
+/// Returns None when the input is closed.
+pub fn read_answer(session: &Session) -> Result<Option<String>, Quit> {
+    loop {
+        let mut s = String::new();
+        if stdin()
+            .read_line(&mut s)
+            .expect("Did not enter a correct string")
+            == 0
+        {
+            return Ok(None);
+        }
+        match parse_command(&s) {
+            None => return Ok(Some(s.trim_end_matches(&['\n', '\r'][..]).to_string())),
+            Some(Ok(command)) => print!("{}", run_command(&command, session)?),
+            Some(Err(message)) => println!("{}", message),
+        }
+    }
+}
+
+/// Reads the answer of a human at a prompt of a round, like
+/// [read_answer], but ends the game when the input is closed.
+pub fn read_round_answer(session: &Session) -> Result<String, Quit> {
+    read_answer(session)?.ok_or(Quit)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::*;
+
+    #[test]
+    fn parse_command_test() {
+        assert_eq!(None, parse_command("25"));
+        assert_eq!(None, parse_command(""));
+        assert_eq!(Some(Ok(Command::Quit)), parse_command(":quit\n"));
+        assert_eq!(Some(Ok(Command::Shoe)), parse_command(" :SHOE "));
+        assert_eq!(
+            Some(Ok(Command::Save(Some(String::from("game.txt"))))),
+            parse_command(":save game.txt")
+        );
+        assert!(matches!(parse_command(":deal"), Some(Err(_))));
+        assert!(matches!(parse_command(":"), Some(Err(_))));
+    }
+
+    #[test]
+    fn command_text_test() {
+        let rules = Rules::default();
+        let names = [String::from("Ann"), String::from("Bob")];
+        let pack = vec![
+            Card {
+                rank: crate::card::Rank::Two,
+                suit: crate::card::Suit::Clubs,
+            };
+            78
+        ];
+        let session = Session {
+            names: &names,
+            rules: &rules,
+            limits: &TableLimits::default(),
+            stats: &[],
+            history: &[],
+            bank: &[250, 40],
+            pack: &pack,
+            saved: None,
+        };
+        let text = |command| command_text(&command, &session).unwrap();
+        assert_eq!("Ann : 250\nBob : 40\n", text(Command::Bank));
+        assert!(text(Command::Shoe).starts_with("78 cards remaining (1.5 decks left)."));
+        assert!(text(Command::Rules).contains("Bets go from 1 to 1000, by 1."));
+        assert_eq!(7, text(Command::Help).lines().count());
+        assert_eq!(None, command_text(&Command::Stats, &session));
+        assert_eq!(
+            Ok(text(Command::Bank)),
+            run_command(&Command::Bank, &session)
+        );
+        assert_eq!(Err(Quit), run_command(&Command::Quit, &session));
+    }
+}
@@ -0,0 +1,142 @@
+use crate::cli::{Args, RULE_OPTIONS};
+use crate::rules::Rules;
+use crate::shoe::Shoe;
+use crate::sim::{simulate_round, Accumulator};
+use crate::strategy::{strategy_from_name, Strategy};
+
+/// The z-score of a two-sided 95% confidence interval.
+const Z_95: f64 = 1.96;
+
+/// The outcome of a paired comparison between two strategies.
+pub struct Comparison {
+    pub first: Accumulator,
+    pub second: Accumulator,
+    pub difference: Accumulator,
+}
+
+/// Plays two strategies against exactly the same cards,
+/// using common random numbers.
+///
+/// Before every round, the shoe is cloned so that both
+/// strategies start from the same position. As long as both
+/// strategies make the same decisions they receive the same cards,
+/// and once they disagree each branch keeps drawing from the same
+/// card sequence. The shoe then continues from the branch of
+/// the first strategy. The per-round differences are therefore
+/// zero most of the time, which makes their variance much smaller
+/// than the one of two independent simulations.
+pub fn compare_strategies(
+    first: &dyn Strategy,
+    second: &dyn Strategy,
+    rules: &Rules,
+    rounds: u64,
+    seed: u64,
+) -> Comparison {
+    let mut shoe = Shoe::new(rules, seed);
+    let mut comparison = Comparison {
+        first: Accumulator::default(),
+        second: Accumulator::default(),
+        difference: Accumulator::default(),
+    };
+    for _ in 0..rounds {
+        if shoe.needs_shuffle() {
+            shoe.shuffle();
+        }
+        let mut branch = shoe.clone();
+        let result1 = simulate_round(&mut shoe, rules, first);
+        let result2 = simulate_round(&mut branch, rules, second);
+        comparison.first.add(result1);
+        comparison.second.add(result2);
+        comparison.difference.add(result1 - result2);
+    }
+    comparison
+}
+
+/// Runs the `compare` subcommand :
+/// `compare <first> <second> [--rounds N] [--seed S] [rules]`.
+pub fn run(args: &Args) -> Result<(), String> {
+    args.check_options(&[&["rounds", "seed"], RULE_OPTIONS])?;
+    if args.positional.len() != 2 {
+        return Err(String::from(
+            "Usage: compare <first strategy> <second strategy> [--rounds N] [--seed S]",
+        ));
+    }
+    let strategies = args
+        .positional
+        .iter()
+        .map(|name| strategy_from_name(name))
+        .collect::<Result<Vec<_>, _>>()?;
+    let rules = args.rules()?;
+    let rounds = args.parse_or("rounds", 1_000_000)?;
+    let seed = args.parse_or("seed", 0)?;
+
+    let comparison = compare_strategies(
+        strategies[0].as_ref(),
+        strategies[1].as_ref(),
+        &rules,
+        rounds,
+        seed,
+    );
+    println!("Rules : {}, seed {}", rules, seed);
+    print_comparison(&comparison, &args.positional[0], &args.positional[1]);
+    Ok(())
+}
+
+/// Displays the result of a comparison to the user.
+fn print_comparison(comparison: &Comparison, first: &str, second: &str) {
+    for (name, acc) in [(first, &comparison.first), (second, &comparison.second)].iter() {
+        println!(
+            "EV of {} : {:+.3}% ± {:.3}%",
+            name,
+            100.0 * acc.mean(),
+            100.0 * Z_95 * acc.std_err()
+        );
+    }
+    let diff = &comparison.difference;
+    println!(
+        "Difference {} - {} over {} rounds : {:+.4}% ± {:.4}% (95% CI [{:+.4}%, {:+.4}%])",
+        first,
+        second,
+        diff.count(),
+        100.0 * diff.mean(),
+        100.0 * Z_95 * diff.std_err(),
+        100.0 * (diff.mean() - Z_95 * diff.std_err()),
+        100.0 * (diff.mean() + Z_95 * diff.std_err()),
+    );
+    let independent = comparison.first.variance() + comparison.second.variance();
+    if diff.variance() > 0.0 {
+        println!(
+            "Variance reduction over independent simulations : {:.1}x",
+            independent / diff.variance()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compare::*;
+    use crate::strategy::{BasicStrategy, SimpleStrategy};
+
+    #[test]
+    fn same_strategy_has_no_difference() {
+        let comparison =
+            compare_strategies(&BasicStrategy, &BasicStrategy, &Rules::default(), 2000, 1);
+        assert_eq!(0.0, comparison.difference.mean());
+        assert_eq!(0.0, comparison.difference.variance());
+        assert_eq!(comparison.first.mean(), comparison.second.mean());
+    }
+
+    #[test]
+    fn basic_beats_simple() {
+        let comparison = compare_strategies(
+            &BasicStrategy,
+            &SimpleStrategy,
+            &Rules::default(),
+            50_000,
+            2,
+        );
+        let diff = &comparison.difference;
+        assert!(diff.mean() - Z_95 * diff.std_err() > 0.0);
+        assert!(diff.variance() < comparison.first.variance() + comparison.second.variance());
+    }
+}
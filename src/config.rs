@@ -0,0 +1,345 @@
+use crate::betting::{BetPolicy, TableLimits};
+use crate::player::PlayerType;
+use crate::rules::Rules;
+use crate::setup::{check_name, parse_player};
+use crate::strategy::strategy_from_name;
+
+use serde::Deserialize;
+
+/// A table setup read from a TOML file, as in
+///
+/// ```toml
+/// name = "Vegas Strip 6D S17 DAS"
+///
+/// [rules]
+/// decks = 6
+/// h17 = false
+///
+/// [limits]
+/// min_bet = 10
+///
+/// [[seats]]
+/// player = "human"
+/// name = "Ann"
+///
+/// [[seats]]
+/// player = "bot"
+/// strategy = "basic"
+/// ```
+///
+/// Every key is optional. Missing values are taken from the
+/// command line, then from the defaults of the game.
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TableConfig {
+    /// The name of the table, shown when the game starts.
+    pub name: Option<String>,
+    #[serde(default)]
+    pub rules: RulesConfig,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    #[serde(default)]
+    pub game: GameConfig,
+    #[serde(default)]
+    pub bots: BotsConfig,
+    /// The players, in their seat order.
+    pub seats: Option<Vec<SeatConfig>>,
+}
+
+/// The `[rules]` of a table, as in [Rules].
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct RulesConfig {
+    pub decks: Option<usize>,
+    pub h17: Option<bool>,
+    pub das: Option<bool>,
+    pub surrender: Option<bool>,
+    pub insurance: Option<bool>,
+    pub penetration: Option<f64>,
+}
+
+/// The betting `[limits]` of a table, as in [TableLimits].
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct LimitsConfig {
+    pub min_bet: Option<u32>,
+    pub max_bet: Option<u32>,
+    pub chip: Option<u32>,
+}
+
+/// The `[game]` section : the money of the players,
+/// the length of the game and the seed of the shuffles.
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct GameConfig {
+    pub bankroll: Option<u32>,
+    pub rounds: Option<u32>,
+    pub seed: Option<u64>,
+}
+
+/// The `[bots]` section : the strategy and betting
+/// policy of the bots that do not have their own.
+#[derive(Deserialize, Default, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct BotsConfig {
+    pub strategy: Option<String>,
+    pub betting: Option<String>,
+}
+
+/// A seat of the table, taken by a human, a bot or an external player.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SeatConfig {
+    /// `human`, `bot`, `external` or `remote`.
+    pub player: String,
+    pub name: Option<String>,
+    /// The strategy of a bot, as given to `--bot-strategy`.
+    pub strategy: Option<String>,
+    /// The betting policy of a bot, as given to `--bot-betting`.
+    pub betting: Option<String>,
+    /// The program playing an external seat, as given to `--external`.
+    pub command: Option<String>,
+}
+
+impl TableConfig {
+    /// Parses and checks a table setup.
+    pub fn parse(text: &str) -> Result<TableConfig, String> {
+        let config: TableConfig = toml::from_str(text).map_err(|e| e.to_string())?;
+        config.check()?;
+        Ok(config)
+    }
+
+    /// Parses and checks a table setup written in JSON, with
+    /// the keys of the TOML files, as the HTTP API takes it.
+    pub fn from_json(text: &str) -> Result<TableConfig, String> {
+        let config: TableConfig = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        config.check()?;
+        Ok(config)
+    }
+
+    /// Loads a table setup from a file.
+    pub fn load(path: &str) -> Result<TableConfig, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read table {} : {}", path, e))?;
+        TableConfig::parse(&text).map_err(|e| format!("{} : {}", path, e))
+    }
+
+    /// The rules of the table, the default rules
+    /// standing in for the missing keys.
+    pub fn rules(&self) -> Rules {
+        let default = Rules::default();
+        let rules = &self.rules;
+        Rules {
+            num_packs: rules.decks.unwrap_or(default.num_packs),
+            dealer_hits_soft_17: rules.h17.unwrap_or(default.dealer_hits_soft_17),
+            double_after_split: rules.das.unwrap_or(default.double_after_split),
+            surrender: rules.surrender.unwrap_or(default.surrender),
+            insurance: rules.insurance.unwrap_or(default.insurance),
+            penetration: rules.penetration.unwrap_or(default.penetration),
+        }
+    }
+
+    /// The betting limits of the table, the default
+    /// limits standing in for the missing keys.
+    pub fn limits(&self) -> TableLimits {
+        let default = TableLimits::default();
+        TableLimits {
+            min: self.limits.min_bet.unwrap_or(default.min),
+            max: self.limits.max_bet.unwrap_or(default.max),
+            chip: self.limits.chip.unwrap_or(default.chip),
+            secret_bets: false,
+        }
+    }
+
+    /// Checks the values of the setup and their combinations,
+    /// naming the offending keys.
+    fn check(&self) -> Result<(), String> {
+        let rules = self.rules();
+        if rules.num_packs == 0 {
+            return Err(String::from(
+                "rules.decks : the shoe needs at least one deck",
+            ));
+        }
+        if !(0.0..=1.0).contains(&rules.penetration) {
+            return Err(String::from(
+                "rules.penetration : the penetration must be between 0 and 1",
+            ));
+        }
+        if rules.insurance {
+            return Err(String::from(
+                "rules.insurance : insurance is not offered in the game",
+            ));
+        }
+        let limits = self.limits();
+        if limits.chip == 0 {
+            return Err(String::from(
+                "limits.chip : the chip value must be positive",
+            ));
+        }
+        if limits.min > limits.max {
+            return Err(format!(
+                "limits : the minimum bet {} is above the maximum bet {}",
+                limits.min, limits.max
+            ));
+        }
+        if limits.min % limits.chip != 0 || limits.max % limits.chip != 0 {
+            return Err(format!(
+                "limits : the minimum and maximum bets must be multiples of the chip value {}",
+                limits.chip
+            ));
+        }
+        if self.game.bankroll == Some(0) {
+            return Err(String::from(
+                "game.bankroll : the bankroll must be positive",
+            ));
+        }
+        if self.game.rounds == Some(0) {
+            return Err(String::from(
+                "game.rounds : a game needs at least one round",
+            ));
+        }
+        if let Some(strategy) = &self.bots.strategy {
+            strategy_from_name(strategy).map_err(|e| format!("bots.strategy : {}", e))?;
+        }
+        if let Some(betting) = &self.bots.betting {
+            BetPolicy::parse(betting).map_err(|e| format!("bots.betting : {}", e))?;
+        }
+
+        let seats = match &self.seats {
+            Some(seats) => seats,
+            None => return Ok(()),
+        };
+        if seats.is_empty() {
+            return Err(String::from("seats : a game needs at least one player"));
+        }
+        for (index, seat) in seats.iter().enumerate() {
+            let key = format!("seats[{}]", index + 1);
+            let player =
+                parse_player(&seat.player).map_err(|e| format!("{}.player : {}", key, e))?;
+            if player != PlayerType::Bot && (seat.strategy.is_some() || seat.betting.is_some()) {
+                return Err(format!(
+                    "{} : a {} seat cannot have a strategy or a betting policy",
+                    key, player
+                ));
+            }
+            if player != PlayerType::External && seat.command.is_some() {
+                return Err(format!(
+                    "{}.command : only external seats have a command",
+                    key
+                ));
+            }
+            if seat.command.as_deref().map(str::trim) == Some("") {
+                return Err(format!("{}.command : the command is empty", key));
+            }
+            if let Some(name) = &seat.name {
+                check_name(name.trim(), &[]).map_err(|e| format!("{}.name : {}", key, e))?;
+            }
+            if let Some(strategy) = &seat.strategy {
+                strategy_from_name(strategy).map_err(|e| format!("{}.strategy : {}", key, e))?;
+            }
+            if let Some(betting) = &seat.betting {
+                BetPolicy::parse(betting).map_err(|e| format!("{}.betting : {}", key, e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::*;
+
+    #[test]
+    fn parse_test() {
+        let config = TableConfig::parse(
+            "name = \"Downtown single deck\"\n\
+             [rules]\ndecks = 1\nh17 = true\ndas = false\n\
+             [limits]\nmin_bet = 5\nchip = 5\n\
+             [bots]\nstrategy = \"basic\"\n\
+             [[seats]]\nplayer = \"human\"\nname = \"Ann\"\n\
+             [[seats]]\nplayer = \"bot\"\nbetting = \"martingale\"\n",
+        )
+        .unwrap();
+        assert_eq!(Some("Downtown single deck"), config.name.as_deref());
+        let rules = config.rules();
+        assert_eq!(1, rules.num_packs);
+        assert!(rules.dealer_hits_soft_17 && !rules.double_after_split);
+        assert_eq!((5, 1000, 5), {
+            let limits = config.limits();
+            (limits.min, limits.max, limits.chip)
+        });
+        let seats = config.seats.unwrap();
+        assert_eq!(2, seats.len());
+        assert_eq!(Some("martingale"), seats[1].betting.as_deref());
+        assert_eq!(Rules::default(), TableConfig::parse("").unwrap().rules());
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let error = TableConfig::parse("[rules]\ndecks = 6\ndeks = 2\n").unwrap_err();
+        assert!(error.contains("line 3, column 1"), "{}", error);
+        assert!(error.contains("unknown field `deks`"), "{}", error);
+        let error = TableConfig::parse("[table]\n").unwrap_err();
+        assert!(error.contains("unknown field `table`"), "{}", error);
+        let error = TableConfig::parse("[rules]\ndecks = \"six\"\n").unwrap_err();
+        assert!(error.contains("line 2, column 9"), "{}", error);
+    }
+
+    #[test]
+    fn invalid_combinations() {
+        let error = |text: &str| TableConfig::parse(text).unwrap_err();
+        assert_eq!(
+            "limits : the minimum bet 50 is above the maximum bet 10",
+            error("[limits]\nmin_bet = 50\nmax_bet = 10\n")
+        );
+        assert_eq!(
+            "limits : the minimum and maximum bets must be multiples of the chip value 5",
+            error("[limits]\nmin_bet = 12\nchip = 5\n")
+        );
+        assert_eq!(
+            "seats[2] : a human seat cannot have a strategy or a betting policy",
+            error("[[seats]]\nplayer = \"bot\"\n[[seats]]\nplayer = \"human\"\nstrategy = \"basic\"\n")
+        );
+        assert_eq!(
+            "seats[1].player : Unknown player type 'robot', expected human, bot, external or remote",
+            error("[[seats]]\nplayer = \"robot\"\n")
+        );
+        assert_eq!(
+            "seats[1].command : only external seats have a command",
+            error("[[seats]]\nplayer = \"bot\"\ncommand = \"./bot\"\n")
+        );
+        assert_eq!(
+            "seats[1].name : Banks cannot be the name of a player",
+            error("[[seats]]\nplayer = \"bot\"\nname = \"Banks\"\n")
+        );
+        assert_eq!(
+            "bots.strategy : Unknown strategy 'best'",
+            error("[bots]\nstrategy = \"best\"\n")
+        );
+        assert!(error("[rules]\ninsurance = true\n").starts_with("rules.insurance : "));
+        assert!(error("[game]\nrounds = 0\n").starts_with("game.rounds : "));
+        assert!(error("seats = []\n").starts_with("seats : "));
+    }
+
+    #[test]
+    fn json() {
+        let config = TableConfig::from_json(
+            r#"{"rules": {"decks": 2}, "seats": [{"player": "remote", "name": "Ann"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(2, config.rules().num_packs);
+        assert_eq!("remote", config.seats.unwrap()[0].player);
+        assert!(TableConfig::from_json(r#"{"limits": {"chip": 0}}"#).is_err());
+        assert!(TableConfig::from_json(r#"{"rules": {"decks": "six"}}"#).is_err());
+    }
+
+    #[test]
+    fn table_files() {
+        for path in &[
+            "tables/vegas-strip.toml",
+            "tables/downtown-single-deck.toml",
+        ] {
+            TableConfig::load(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use crate::card::Card;
+use crate::math::rank_value;
+use crate::shoe::Shoe;
+
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+
+/// A card counting system, given by the tag added to the
+/// running count for each card value seen.
+///
+/// Tags are indexed like [Shoe::dealt] : the first one is the
+/// tag of the Aces and the last one the tag of the cards worth 10.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct CountSystem {
+    pub name: String,
+    pub tags: [i32; 10],
+}
+
+impl CountSystem {
+    /// The Hi-Lo count : +1 for 2 to 6, -1 for 10 and Aces.
+    pub fn hi_lo() -> CountSystem {
+        CountSystem {
+            name: String::from("Hi-Lo"),
+            tags: [-1, 1, 1, 1, 1, 1, 0, 0, 0, -1],
+        }
+    }
+
+    /// Parses a counting system given on the command line : either
+    /// the name of a well known balanced count (`hi-lo`, `hi-opt-i`,
+    /// `hi-opt-ii`, `omega-ii` or `zen`), or ten comma separated tags
+    /// from the Aces to the cards worth 10, as in `-1,1,1,1,1,1,0,0,0,-1`.
+    pub fn parse(text: &str) -> Result<CountSystem, String> {
+        let (name, tags) = match text {
+            "hi-lo" => return Ok(CountSystem::hi_lo()),
+            "hi-opt-i" => ("Hi-Opt-I", [0, 0, 1, 1, 1, 1, 0, 0, 0, -1]),
+            "hi-opt-ii" => ("Hi-Opt-II", [0, 1, 1, 2, 2, 1, 1, 0, 0, -2]),
+            "omega-ii" => ("Omega-II", [0, 1, 1, 2, 2, 2, 1, 0, -1, -2]),
+            "zen" => ("Zen", [-1, 1, 1, 2, 2, 2, 1, 0, 0, -2]),
+            _ => {
+                let tags: Vec<i32> = text
+                    .split(',')
+                    .map(|tag| tag.trim().parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| format!("Unknown counting system '{}'", text))?;
+                let tags: [i32; 10] = tags
+                    .try_into()
+                    .map_err(|_| String::from("A counting system needs ten tags"))?;
+                return Ok(CountSystem {
+                    name: String::from("Custom"),
+                    tags,
+                });
+            }
+        };
+        Ok(CountSystem {
+            name: String::from(name),
+            tags,
+        })
+    }
+
+    /// The running count divided by the number of packs
+    /// remaining in the shoe.
+    pub fn true_count(&self, shoe: &Shoe) -> f64 {
+        self.true_count_of(shoe.dealt(), shoe.decks_remaining())
+    }
+
+    /// The running count of the given dealt cards, counted
+    /// by value like [Shoe::dealt].
+    pub fn running_count_of(&self, dealt: &[u32; 10]) -> i32 {
+        self.tags
+            .iter()
+            .zip(dealt)
+            .map(|(&tag, &dealt)| tag * dealt as i32)
+            .sum()
+    }
+
+    /// The true count of the given dealt cards, when the given
+    /// number of packs remains to be dealt.
+    pub fn true_count_of(&self, dealt: &[u32; 10], decks_remaining: f64) -> f64 {
+        self.running_count_of(dealt) as f64 / decks_remaining.max(0.5)
+    }
+}
+
+/// Computes the cards dealt from a pile of `num_packs` packs,
+/// by value like [Shoe::dealt], from the cards that remain in it.
+pub fn dealt_from_remaining(remaining: &[Card], num_packs: usize) -> [u32; 10] {
+    let mut dealt = [4 * num_packs as u32; 10];
+    dealt[9] *= 4;
+    for card in remaining {
+        dealt[rank_value(&card.rank) as usize - 1] -= 1;
+    }
+    dealt
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::count::*;
+    use crate::rules::Rules;
+
+    #[test]
+    fn hi_lo_is_balanced() {
+        let rules = Rules::default();
+        let mut shoe = Shoe::new(&rules, 11);
+        for _ in 0..rules.num_packs * 52 {
+            shoe.draw();
+        }
+        assert_eq!(0, CountSystem::hi_lo().running_count_of(shoe.dealt()));
+    }
+
+    #[test]
+    fn parse_test() {
+        assert_eq!(CountSystem::hi_lo(), CountSystem::parse("hi-lo").unwrap());
+        let zen = CountSystem::parse("zen").unwrap();
+        assert_eq!(-2, zen.tags[9]);
+        let custom = CountSystem::parse("0, 1,1,1,1,1,0,0,0,-1").unwrap();
+        assert_eq!([0, 1, 1, 1, 1, 1, 0, 0, 0, -1], custom.tags);
+        assert!(CountSystem::parse("1,1,1").is_err());
+        assert!(CountSystem::parse("red-seven").is_err());
+    }
+
+    #[test]
+    fn true_count_test() {
+        let mut shoe = Shoe::new(&Rules::default(), 11);
+        for _ in 0..52 {
+            shoe.draw();
+        }
+        let hi_lo = CountSystem::hi_lo();
+        assert_eq!(
+            hi_lo.running_count_of(shoe.dealt()) as f64 / 3.0,
+            hi_lo.true_count(&shoe)
+        );
+    }
+
+    #[test]
+    fn dealt_from_remaining_test() {
+        let mut pack = Card::card_pack();
+        pack.extend(Card::card_pack());
+        let ace = pack.pop().unwrap();
+        let king = pack.pop().unwrap();
+        assert!(ace.rank == crate::card::Rank::Ace && king.rank == crate::card::Rank::King);
+        let dealt = dealt_from_remaining(&pack, 2);
+        assert_eq!([1, 0, 0, 0, 0, 0, 0, 0, 0, 1], dealt);
+        assert_eq!(-2, CountSystem::hi_lo().running_count_of(&dealt));
+    }
+}
@@ -0,0 +1,351 @@
+use crate::count::CountSystem;
+use crate::math::{hand_value, is_soft, rank_value};
+use crate::player::PlayerAction;
+use crate::strategy::{upcard_value, Situation, Strategy};
+
+/// The hands a playing deviation applies to.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum IndexHand {
+    /// A hard total, including pairs that are not split.
+    Hard(u32),
+    /// A soft total.
+    Soft(u32),
+    /// A pair of cards of the given value (1 for Aces).
+    Pair(u32),
+    /// The insurance decision, when the dealer shows an Ace.
+    /// The action of such an index is not used.
+    Insurance,
+}
+
+/// A playing deviation : against a given dealer upcard, play
+/// `action` instead of the basic strategy when the true count
+/// is at least the index (or below it, when `below` is true).
+///
+/// For doubling, splitting and surrendering, the index decides
+/// alone whether the action is taken : on the other side of the
+/// index, the hand is played as if the action was not allowed.
+///
+/// Indices differ between tables where the dealer hits soft 17
+/// and tables where they stand, so both are given.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Index {
+    pub hand: IndexHand,
+    /// The value of the dealer upcard, 11 for an Ace.
+    pub up: u32,
+    pub s17: f64,
+    pub h17: f64,
+    pub action: PlayerAction,
+    pub below: bool,
+}
+
+/// A named deviation of a published set.
+pub type NamedIndex = (&'static str, Index);
+
+const fn index(
+    name: &'static str,
+    hand: IndexHand,
+    up: u32,
+    s17: f64,
+    h17: f64,
+    action: PlayerAction,
+) -> NamedIndex {
+    let index = Index {
+        hand,
+        up,
+        s17,
+        h17,
+        action,
+        below: false,
+    };
+    (name, index)
+}
+
+/// Same as [index], for deviations that apply below the index.
+const fn index_below(
+    name: &'static str,
+    hand: IndexHand,
+    up: u32,
+    s17: f64,
+    h17: f64,
+    action: PlayerAction,
+) -> NamedIndex {
+    let (name, index) = index(name, hand, up, s17, h17, action);
+    (
+        name,
+        Index {
+            below: true,
+            ..index
+        },
+    )
+}
+
+use IndexHand::*;
+use PlayerAction::*;
+
+/// The Illustrious 18 : the most valuable Hi-Lo playing
+/// deviations, by order of importance (Don Schlesinger, 1986).
+///
+/// These indices were computed for games with a hole card. The
+/// doubling indices against a 10 or an Ace (10v10, 11vA and 10vA)
+/// lose money in the no-hole-card game of this table, where a
+/// dealer blackjack also takes the doubled bet, so they are left out.
+pub const ILLUSTRIOUS_18: [NamedIndex; 15] = [
+    index("insurance", Insurance, 11, 3.0, 3.0, Stand),
+    index("16v10", Hard(16), 10, 0.0, 0.0, Stand),
+    index("15v10", Hard(15), 10, 4.0, 4.0, Stand),
+    index("TTv5", Pair(10), 5, 5.0, 5.0, Split),
+    index("TTv6", Pair(10), 6, 4.0, 4.0, Split),
+    index("12v3", Hard(12), 3, 2.0, 2.0, Stand),
+    index("12v2", Hard(12), 2, 3.0, 3.0, Stand),
+    index("9v2", Hard(9), 2, 1.0, 1.0, Double),
+    index("9v7", Hard(9), 7, 3.0, 3.0, Double),
+    index("16v9", Hard(16), 9, 5.0, 5.0, Stand),
+    index_below("13v2", Hard(13), 2, -1.0, -1.0, Hit),
+    index_below("12v4", Hard(12), 4, 0.0, 0.0, Hit),
+    index_below("12v5", Hard(12), 5, -2.0, -2.0, Hit),
+    index_below("12v6", Hard(12), 6, -1.0, -1.0, Hit),
+    index_below("13v3", Hard(13), 3, -2.0, -2.0, Hit),
+];
+
+/// The Fab 4 : the most valuable Hi-Lo surrender deviations.
+pub const FAB_4: [NamedIndex; 4] = [
+    index("14v10", Hard(14), 10, 3.0, 3.0, Surrender),
+    index("15v10", Hard(15), 10, 0.0, 0.0, Surrender),
+    index("15v9", Hard(15), 9, 2.0, 2.0, Surrender),
+    index("15vA", Hard(15), 11, 1.0, -1.0, Surrender),
+];
+
+impl Index {
+    /// The index for the rules of the table.
+    pub fn threshold(&self, situation: &Situation) -> f64 {
+        if situation.rules.dealer_hits_soft_17 {
+            self.h17
+        } else {
+            self.s17
+        }
+    }
+
+    /// Whether the true count is below the index.
+    fn below_threshold(&self, situation: &Situation, true_count: f64) -> bool {
+        true_count < self.threshold(situation)
+    }
+
+    /// Whether this deviation is about the given hand and
+    /// dealer upcard, and its action is allowed.
+    fn matches(&self, situation: &Situation) -> bool {
+        let hand = situation.hand;
+        let matches_hand = match self.hand {
+            Hard(total) => !is_soft(hand) && hand_value(hand) == total,
+            Soft(total) => is_soft(hand) && hand_value(hand) == total,
+            Pair(value) => situation.can_split && rank_value(&hand[0].rank) == value,
+            Insurance => false,
+        };
+        let legal = match self.action {
+            Double => situation.can_double,
+            Split => situation.can_split,
+            Surrender => situation.can_surrender,
+            _ => true,
+        };
+        matches_hand && legal && upcard_value(situation.dealer_card) == self.up
+    }
+}
+
+/// A strategy that plays a base strategy, except for a
+/// selection of count-based deviations.
+pub struct Deviations {
+    pub base: Box<dyn Strategy>,
+    pub indices: Vec<Index>,
+    pub count: CountSystem,
+}
+
+impl Deviations {
+    /// Builds deviations on top of a base strategy from a list of
+    /// deviation sets separated by `+` : `i18` for the Illustrious 18,
+    /// `fab4` for the Fab 4, or the name of a single deviation such
+    /// as `16v10`, `insurance` or `14v10`. `15v10` is the stand
+    /// deviation of the Illustrious 18, its surrender counterpart
+    /// only comes with `fab4`.
+    pub fn parse(base: Box<dyn Strategy>, selection: &str) -> Result<Deviations, String> {
+        let mut indices = Vec::new();
+        for name in selection.split('+') {
+            match name {
+                "i18" => indices.extend(ILLUSTRIOUS_18.iter().map(|named| named.1)),
+                "fab4" => indices.extend(FAB_4.iter().map(|named| named.1)),
+                _ => {
+                    let found = ILLUSTRIOUS_18
+                        .iter()
+                        .chain(FAB_4.iter())
+                        .find(|named| named.0 == name)
+                        .ok_or_else(|| format!("Unknown deviation '{}'", name))?;
+                    indices.push(found.1);
+                }
+            }
+        }
+        Ok(Deviations {
+            base,
+            indices,
+            count: CountSystem::hi_lo(),
+        })
+    }
+
+    fn true_count(&self, situation: &Situation) -> f64 {
+        self.count
+            .true_count_of(situation.dealt, situation.decks_remaining)
+    }
+}
+
+impl Strategy for Deviations {
+    fn decide(&self, situation: &Situation) -> PlayerAction {
+        let true_count = self.true_count(situation);
+        let plays = |index: &&Index| {
+            index.matches(situation) && index.below_threshold(situation, true_count) == index.below
+        };
+        // surrendering is only possible before any other action
+        for action in [Surrender, Split, Double] {
+            if let Some(index) = self
+                .indices
+                .iter()
+                .find(|index| index.action == action && index.matches(situation))
+            {
+                if plays(&index) {
+                    return action;
+                }
+                return self.decide(&Situation {
+                    can_surrender: situation.can_surrender && action != Surrender,
+                    can_split: situation.can_split && action != Split,
+                    can_double: situation.can_double && action != Double,
+                    ..*situation
+                });
+            }
+        }
+        let base = self.base.decide(situation);
+        if matches!(base, Surrender | Split | Double) {
+            return base;
+        }
+        match self.indices.iter().find(plays) {
+            Some(index) => index.action,
+            None => base,
+        }
+    }
+
+    fn insure(&self, situation: &Situation) -> bool {
+        let true_count = self.true_count(situation);
+        self.indices
+            .iter()
+            .any(|index| index.hand == Insurance && true_count >= index.threshold(situation))
+            || self.base.insure(situation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::card::*;
+    use crate::deviation::*;
+    use crate::rules::Rules;
+    use crate::strategy::BasicStrategy;
+
+    fn card(rank: Rank) -> Card {
+        Card {
+            rank,
+            suit: Suit::Diamonds,
+        }
+    }
+
+    /// Asks a strategy for its action with the given hand, dealer
+    /// upcard and running count, with two packs remaining.
+    fn decide(
+        strategy: &dyn Strategy,
+        hand: &[Card],
+        up: Rank,
+        running: u32,
+        rules: &Rules,
+    ) -> PlayerAction {
+        // low cards raise the Hi-Lo count, high cards lower it
+        let mut dealt = [0; 10];
+        dealt[1] = running;
+        strategy.decide(&Situation {
+            hand,
+            dealer_card: &card(up),
+            can_double: true,
+            can_split: crate::math::is_splittable(hand),
+            can_surrender: rules.surrender,
+            dealt: &dealt,
+            decks_remaining: 2.0,
+            rules,
+        })
+    }
+
+    #[test]
+    fn sixteen_versus_ten() {
+        let rules = Rules::default();
+        let strategy = Deviations::parse(Box::new(BasicStrategy), "16v10").unwrap();
+        let hand = [card(Rank::Ten), card(Rank::Six)];
+        assert_eq!(Hit, decide(&BasicStrategy, &hand, Rank::King, 2, &rules));
+        assert_eq!(Stand, decide(&strategy, &hand, Rank::King, 0, &rules));
+        // only the selected deviation is played
+        let hand = [card(Rank::Ten), card(Rank::Five)];
+        assert_eq!(Hit, decide(&strategy, &hand, Rank::King, 20, &rules));
+    }
+
+    #[test]
+    fn negative_indices() {
+        let rules = Rules::default();
+        let strategy = Deviations::parse(Box::new(BasicStrategy), "i18").unwrap();
+        let hand = [card(Rank::Ten), card(Rank::Three)];
+        assert_eq!(Stand, decide(&strategy, &hand, Rank::Two, 0, &rules));
+        // a true count of -2 (running count -4 with 2 packs left)
+        let hand_and_count = |hand: &[Card], up| {
+            strategy.decide(&Situation {
+                hand,
+                dealer_card: &card(up),
+                can_double: true,
+                can_split: false,
+                can_surrender: false,
+                dealt: &[0, 0, 0, 0, 0, 0, 0, 0, 0, 4],
+                decks_remaining: 2.0,
+                rules: &rules,
+            })
+        };
+        assert_eq!(Hit, hand_and_count(&hand, Rank::Two));
+        assert_eq!(Stand, hand_and_count(&hand, Rank::Three));
+    }
+
+    #[test]
+    fn h17_and_s17_indices() {
+        let s17 = Rules {
+            surrender: true,
+            ..Rules::default()
+        };
+        let h17 = Rules {
+            dealer_hits_soft_17: true,
+            ..s17
+        };
+        let strategy = Deviations::parse(Box::new(BasicStrategy), "15vA").unwrap();
+        let hand = [card(Rank::Nine), card(Rank::Six)];
+        assert_eq!(Hit, decide(&strategy, &hand, Rank::Ace, 0, &s17));
+        assert_eq!(Surrender, decide(&strategy, &hand, Rank::Ace, 0, &h17));
+        assert_eq!(Surrender, decide(&strategy, &hand, Rank::Ace, 2, &s17));
+    }
+
+    #[test]
+    fn surrender_and_split_deviations() {
+        let rules = Rules {
+            surrender: true,
+            ..Rules::default()
+        };
+        let strategy = Deviations::parse(Box::new(BasicStrategy), "i18+fab4").unwrap();
+        let hand = [card(Rank::Ten), card(Rank::Four)];
+        assert_eq!(Hit, decide(&strategy, &hand, Rank::Ten, 4, &rules));
+        assert_eq!(Surrender, decide(&strategy, &hand, Rank::Ten, 6, &rules));
+        let hand = [card(Rank::King), card(Rank::King)];
+        assert_eq!(Stand, decide(&strategy, &hand, Rank::Five, 8, &rules));
+        assert_eq!(Split, decide(&strategy, &hand, Rank::Five, 10, &rules));
+    }
+
+    #[test]
+    fn unknown_deviation() {
+        assert!(Deviations::parse(Box::new(BasicStrategy), "17v10").is_err());
+        // the hole-card doubling indices are not offered
+        assert!(Deviations::parse(Box::new(BasicStrategy), "11vA").is_err());
+    }
+}
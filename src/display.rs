@@ -1,30 +1,39 @@
+use crate::art::{card_style, card_text, hand_art};
 use crate::card::Card;
+use crate::command::{read_answer, Quit, Session};
+use crate::graph::{line_chart, player_marker, terminal_width, Series};
 use crate::math::is_blackjack;
 use crate::player::PlayerType;
+use crate::setup::parse_player;
+use crate::stats::PlayerStats;
 
 /// This method displays the hands and scores
-/// of all players in a human-readable format.
+/// of all players in a human-readable format, drawing
+/// the cards or writing them on one line depending on
+/// the [crate::art::CardStyle] of the program.
 ///
 /// # Arguments
 ///
 /// * `scores` An array of scores obtained by
-/// the players and the dealer. All players must
-/// have at least one score, in the first part of
-/// their corresponding tuples. Players may also
-/// have another hand in case they "split", which
-/// is why another optional score is available. As
-/// the dealer cannot split, their optional hand is
-/// assumed to be None.
+///   the players and the dealer. All players must
+///   have at least one score, in the first part of
+///   their corresponding tuples. Players may also
+///   have another hand in case they "split", which
+///   is why another optional score is available. As
+///   the dealer cannot split, their optional hand is
+///   assumed to be None.
 /// * `player_hands` A vector of player hands, each
-/// composed of either one or two Vectors of [Card]s,
-/// the second one being present in case of a split.
-/// This argument must have a length equal to the number
-/// of non-dealer players.
+///   composed of either one or two Vectors of [Card]s,
+///   the second one being present in case of a split.
+///   This argument must have a length equal to the number
+///   of non-dealer players.
 /// * `dealer_hand` The hand of the dealer.
+/// * `names` The names of the non-dealer players.
 pub fn display_hands_and_scores(
     scores: &[(u32, Option<u32>)],
     player_hands: &[(Vec<Card>, Option<Vec<Card>>)],
     dealer_hand: &[Card],
+    names: &[String],
 ) {
     for (index, score) in scores.iter().enumerate() {
         let player_hand: (&[Card], Option<&[Card]>) = if index < player_hands.len() {
@@ -34,21 +43,22 @@ pub fn display_hands_and_scores(
             (dealer_hand, None)
         };
 
-        let iterate_over = if player_hand.1.is_none() {
-            vec![player_hand.0]
-        } else {
-            vec![player_hand.0, player_hand.1.unwrap()]
+        let iterate_over = match player_hand.1 {
+            None => vec![player_hand.0],
+            Some(second) => vec![player_hand.0, second],
         };
+        let style = card_style();
         for (is_second_hand, &elem) in iterate_over.iter().enumerate() {
-            let mut stri = String::from("{");
-            for card in elem {
-                stri.push_str(&format!("/ {} /", card));
-            }
-            stri.push('}');
+            let cards = if style.art {
+                String::new()
+            } else {
+                let cards: Vec<String> = elem.iter().map(|card| card_text(card, style)).collect();
+                cards.join(" ") + " "
+            };
             println!(
-                "{} got hand {} with value {}!{}",
-                player_name(index, player_hands.len()),
-                stri,
+                "{} got hand {}with value {}!{}",
+                player_name(index, names),
+                cards,
                 if is_second_hand == 0 {
                     //if we are not in the split hand
                     score.0
@@ -61,6 +71,11 @@ pub fn display_hands_and_scores(
                     ""
                 }
             );
+            if style.art {
+                for line in hand_art(elem, style) {
+                    println!("{}", line);
+                }
+            }
         }
     }
 }
@@ -70,33 +85,35 @@ pub fn display_hands_and_scores(
 ///
 /// # Arguments
 /// * `winner_index` A vector containing the index
-/// of the players whose hand beat the hand of the
-/// dealer. Along with that index is a boolean that is
-/// true if and only if this hand
-/// is a second hand for their respective player.
-/// Note that a player index may appear twice if a player
-/// split and both of their hands beat the dealer.
+///   of the players whose hand beat the hand of the
+///   dealer. Along with that index is a boolean that is
+///   true if and only if this hand
+///   is a second hand for their respective player.
+///   Note that a player index may appear twice if a player
+///   split and both of their hands beat the dealer.
 /// * `equal_index` Same as winner_index,
-/// but for hands that have the same value as the
-/// hand of the dealer, instead of winning.
+///   but for hands that have the same value as the
+///   hand of the dealer, instead of winning.
 /// * `loser_index` Same as winner_index,
-/// but for hands that have less value as the
-/// hand of the dealer, instead of winning.
+///   but for hands that have less value as the
+///   hand of the dealer, instead of winning.
+/// * `names` The names of the non-dealer players.
 pub fn display_results(
     three_two_index: &[(usize, bool)],
     winner_index: &[(usize, bool)],
     equal_index: &[(usize, bool)],
     loser_index: &[(usize, bool)],
+    names: &[String],
 ) {
-    display_result_vector(&three_two_index, "blackjack winners");
-    display_result_vector(&winner_index, "winners");
-    display_result_vector(&equal_index, "equalities");
-    display_result_vector(&loser_index, "losers");
+    display_result_vector(three_two_index, "blackjack winners", names);
+    display_result_vector(winner_index, "winners", names);
+    display_result_vector(equal_index, "equalities", names);
+    display_result_vector(loser_index, "losers", names);
 }
 
 /// Internally used by [display_results]. Displays a
 /// vector to the user.
-fn display_result_vector(index: &[(usize, bool)], name: &str) {
+fn display_result_vector(index: &[(usize, bool)], name: &str, names: &[String]) {
     if index.is_empty() {
         println!("There are no {} this turn!\n", name);
     } else {
@@ -104,9 +121,9 @@ fn display_result_vector(index: &[(usize, bool)], name: &str) {
         let mut stri = String::new();
         for (usi, bol) in index {
             if !bol {
-                stri.push_str(&format!("Player {}, ", usi + 1));
+                stri.push_str(&format!("{}, ", names[*usi]));
             } else {
-                stri.push_str(&format!("Second player {}, ", usi + 1));
+                stri.push_str(&format!("{} (second hand), ", names[*usi]));
             }
         }
         stri.pop(); // get rid of ", "
@@ -118,17 +135,22 @@ fn display_result_vector(index: &[(usize, bool)], name: &str) {
 /// This function computes the name of a player
 /// based on their index.
 ///
-/// Indices from 0 to the number
-/// of non-dealer players are mapped to the String
-/// `Player $i` and the other numbers map to "Dealer".
-fn player_name(index: usize, num_players: usize) -> String {
-    if index < num_players {
-        format!("Player {}", index + 1)
-    } else {
-        String::from("Dealer")
+/// Indices of non-dealer players are mapped to
+/// their name and the other numbers map to "Dealer".
+fn player_name(index: usize, names: &[String]) -> String {
+    match names.get(index) {
+        Some(name) => name.clone(),
+        None => String::from("Dealer"),
     }
 }
 
+/// The default names of the players : `Player 1`, `Player 2`...
+pub fn default_names(num_players: usize) -> Vec<String> {
+    (1..=num_players)
+        .map(|index| format!("Player {}", index))
+        .collect()
+}
+
 /// This method displays the current state of
 /// the bank of the players (not including current bets) to the user.
 pub fn display_bank(bank: &[u32]) {
@@ -141,6 +163,101 @@ pub fn display_bank(bank: &[u32]) {
     println!("{}", stri);
 }
 
+/// This method displays the statistics of each
+/// player over the session to the user.
+pub fn display_stats(stats: &[PlayerStats], names: &[String]) {
+    print!("{}", stats_text(stats, names));
+}
+
+/// The statistics of each player over the session, as
+/// displayed by [display_stats].
+pub fn stats_text(stats: &[PlayerStats], names: &[String]) -> String {
+    let mut out = String::new();
+    for (index, stat) in stats.iter().enumerate() {
+        out += &format!(
+            "{} : {} hands, {} won, {} pushed, {} lost ({:.1}% won)\n",
+            player_name(index, names),
+            stat.hands,
+            stat.wins,
+            stat.pushes,
+            stat.losses,
+            PlayerStats::rate(stat.wins, stat.hands)
+        );
+        out += &format!("  Blackjacks {}, busts {}\n", stat.blackjacks, stat.busts);
+        out += &format!(
+            "  Doubles {} ({:.1}% won), splits {} ({:.1}% of split hands won)\n",
+            stat.doubles,
+            PlayerStats::rate(stat.doubles_won, stat.doubles),
+            stat.splits,
+            PlayerStats::rate(stat.split_hands_won, 2 * stat.splits)
+        );
+        out += &format!(
+            "  Net {:+}, biggest win {:+}, biggest loss {:+}\n",
+            stat.net, stat.biggest_win, stat.biggest_loss
+        );
+        out += &format!(
+            "  Longest streaks : {} rounds won, {} rounds lost\n",
+            stat.longest_win_streak, stat.longest_loss_streak
+        );
+    }
+    out
+}
+
+/// This method draws the bank of each player round by round, as
+/// recorded in `history` from the start of the game, along with the
+/// net position of the house, as wide as the terminal.
+pub fn display_bank_chart(history: &[Vec<u32>], names: &[String]) {
+    print!("{}", bank_chart(history, names, terminal_width()));
+}
+
+/// The chart drawn by [display_bank_chart], `width` characters wide.
+pub fn bank_chart(history: &[Vec<u32>], names: &[String], width: usize) -> String {
+    let num_players = history.first().map_or(0, Vec::len);
+    let mut series: Vec<Series> = (0..num_players)
+        .map(|index| Series {
+            name: player_name(index, names),
+            marker: player_marker(index),
+            values: history.iter().map(|bank| bank[index] as i64).collect(),
+        })
+        .collect();
+    let total = |bank: &Vec<u32>| bank.iter().map(|&b| b as i64).sum::<i64>();
+    if let Some(start) = history.first().map(total) {
+        series.push(Series {
+            name: String::from("House net"),
+            marker: '#',
+            values: history.iter().map(|bank| start - total(bank)).collect(),
+        });
+    }
+    line_chart(&series, width, 15)
+}
+
+/// Blocks until the user presses enter. Typing `stats` or `graph`
+/// first displays the statistics of the session or the chart of
+/// the banks of the players, and colon commands are run by
+/// [read_answer].
+pub fn wait_for_enter_or_command(session: &Session) -> Result<(), Quit> {
+    println!(
+        "Please press ENTER to continue, type stats or graph for the session so far, \
+         or :help for the other commands."
+    );
+    while let Some(answer) = read_answer(session)? {
+        match answer.trim() {
+            "stats" => display_stats(session.stats, session.names),
+            "graph" => display_bank_chart(session.history, session.names),
+            _ => break,
+        }
+    }
+    Ok(())
+}
+
+/// Displays the summary of the session when the game is over :
+/// the statistics of the players and the chart of their banks.
+pub fn display_session_end(stats: &[PlayerStats], history: &[Vec<u32>], names: &[String]) {
+    println!("The game is over. Statistics of the session :");
+    display_stats(stats, names);
+    display_bank_chart(history, names);
+}
+
 /// Blocks until the user presses enter
 pub fn wait_for_enter() {
     println!("Please press ENTER to continue.");
@@ -154,7 +271,10 @@ pub fn ask_for_player_types() -> Vec<PlayerType> {
     );
     let player_num = read_num();
     for index in 0..player_num {
-        println!("Please enter type of player {} (Human or Bot).", index + 1);
+        println!(
+            "Please enter type of player {} (h)uman or (b)ot.",
+            index + 1
+        );
         ret.push(read_player_type());
     }
     ret.push(PlayerType::Dealer);
@@ -187,17 +307,9 @@ fn read_player_type() -> PlayerType {
         std::io::stdin()
             .read_line(&mut s)
             .expect("Did not enter a correct string");
-        if s.ends_with('\n') {
-            s.pop();
-        }
-        if s.ends_with('\r') {
-            s.pop();
-        }
-
-        match s.as_str() {
-            "Human" => return PlayerType::Human,
-            "Bot" => return PlayerType::Bot,
-            _ => println!("Not a valid type! Try again"),
+        match parse_player(&s) {
+            Ok(player_type) => return player_type,
+            Err(message) => println!("{}. Try again", message),
         }
     }
 }
@@ -208,19 +320,25 @@ mod tests {
 
     #[test]
     fn call_displays_do_not_crash() {
+        let names = default_names(2);
+        display_stats(&[PlayerStats::default(), PlayerStats::default()], &names);
+        display_bank_chart(&[vec![300, 300], vec![250, 330], vec![400, 0]], &names);
+        display_bank_chart(&[], &[]);
         //kind of a weak test but i don't want to add boiler plate for DI
-        display_results(&vec![], &vec![], &vec![], &vec![]);
-        display_bank(&vec![]);
+        display_results(&[(0, false)], &[], &[(1, true)], &[], &names);
+        display_bank(&[]);
         display_hands_and_scores(
-            &vec![(0, None); 3],
-            &vec![(vec![], None), (vec![], None)],
-            &vec![],
+            &[(0, None); 3],
+            &[(vec![], None), (vec![], None)],
+            &[],
+            &names,
         )
     }
 
     #[test]
     fn player_name_test() {
-        assert_eq!("Dealer", &player_name(5, 5));
-        assert_eq!("Player 1", &player_name(0, 1))
+        assert_eq!("Dealer", &player_name(5, &default_names(5)));
+        assert_eq!("Player 1", &player_name(0, &default_names(1)));
+        assert_eq!("Ann", &player_name(0, &[String::from("Ann")]))
     }
 }
@@ -0,0 +1,573 @@
+use crate::card::{Card, Suit};
+use crate::cli::{Args, RULE_OPTIONS};
+use crate::count::CountSystem;
+use crate::deviation::{IndexHand, FAB_4, ILLUSTRIOUS_18};
+use crate::indices::{compared_actions, Decision};
+use crate::math::rank_of_value;
+use crate::player::PlayerAction;
+use crate::rules::Rules;
+use crate::strategy::{strategy_from_name, Situation, Strategy};
+
+use std::collections::HashMap;
+
+/// The counting systems analysed when none is given.
+const DEFAULT_SYSTEMS: [&str; 5] = ["hi-lo", "hi-opt-i", "hi-opt-ii", "omega-ii", "zen"];
+
+/// The final hands of the dealer : 17 to 21, bust and blackjack.
+const OUTCOMES: usize = 7;
+const BUST: usize = 5;
+const BLACKJACK: usize = 6;
+
+/// The cards of a shoe, by value like [crate::shoe::Shoe::dealt].
+pub type Composition = [u32; 10];
+
+/// The composition of a full shoe of `num_packs` packs.
+pub fn full_shoe(num_packs: usize) -> Composition {
+    let mut counts = [4 * num_packs as u32; 10];
+    counts[9] *= 4;
+    counts
+}
+
+/// The composition without one card of the given value.
+fn without(counts: &Composition, value: u32) -> Composition {
+    let mut counts = *counts;
+    counts[value as usize - 1] -= 1;
+    counts
+}
+
+/// The value of a hand given by the values of its cards.
+fn value_of(hand: &[u32]) -> u32 {
+    let sum: u32 = hand.iter().sum();
+    if hand.contains(&1) && sum + 10 <= 21 {
+        sum + 10
+    } else {
+        sum
+    }
+}
+
+/// What the player may do with a hand.
+#[derive(Clone, Copy)]
+struct Turn {
+    can_double: bool,
+    can_split: bool,
+    can_surrender: bool,
+}
+
+/// Computes exactly the expected value of a strategy for a given
+/// composition of the shoe, under the rules of the table.
+///
+/// Every card drawn by the player and the dealer is removed from the
+/// shoe. The only approximation is for splits : both hands are
+/// assumed to start from the composition left after the split.
+/// Insurance is not part of the expected value.
+pub struct Calculator<'a> {
+    rules: &'a Rules,
+    strategy: &'a dyn Strategy,
+    full: Composition,
+    dealer: HashMap<(Composition, u32), [f64; OUTCOMES]>,
+}
+
+impl<'a> Calculator<'a> {
+    pub fn new(rules: &'a Rules, strategy: &'a dyn Strategy) -> Calculator<'a> {
+        Calculator {
+            rules,
+            strategy,
+            full: full_shoe(rules.num_packs),
+            dealer: HashMap::new(),
+        }
+    }
+
+    /// The expected value of a round dealt from the given shoe.
+    pub fn expected_value(&mut self, counts: &Composition) -> f64 {
+        self.dealer.clear();
+        let probability = |counts: &Composition, value: u32| {
+            counts[value as usize - 1] as f64 / counts.iter().sum::<u32>() as f64
+        };
+        let mut ev = 0.0;
+        for up in 1..=10 {
+            let p_up = probability(counts, up);
+            if p_up == 0.0 {
+                continue;
+            }
+            let counts = without(counts, up);
+            for first in 1..=10 {
+                let p_first = probability(&counts, first);
+                if p_first == 0.0 {
+                    continue;
+                }
+                let counts = without(&counts, first);
+                // both orders of two different cards are as likely
+                for second in first..=10 {
+                    let orders = if first == second { 1.0 } else { 2.0 };
+                    let p = p_up * p_first * probability(&counts, second) * orders;
+                    if p > 0.0 {
+                        let counts = without(&counts, second);
+                        ev += p * self.round_ev(&[first, second], &counts, up, None);
+                    }
+                }
+            }
+        }
+        ev
+    }
+
+    /// The expected value of a round once the two cards of the player
+    /// and the upcard are out of the shoe, optionally forcing the
+    /// first action of the player.
+    pub fn round_ev(
+        &mut self,
+        hand: &[u32],
+        counts: &Composition,
+        up: u32,
+        first: Option<PlayerAction>,
+    ) -> f64 {
+        if value_of(hand) == 21 {
+            let outcomes = self.dealer_outcomes(counts, up);
+            return 1.5 * (1.0 - outcomes[BLACKJACK]);
+        }
+        let turn = Turn {
+            can_double: true,
+            can_split: hand[0] == hand[1],
+            can_surrender: self.rules.surrender,
+        };
+        self.play(&mut hand.to_vec(), &mut counts.clone(), up, turn, first)
+    }
+
+    /// The expected value of a hand of the player, who
+    /// follows the strategy after the `first` action.
+    fn play(
+        &mut self,
+        hand: &mut Vec<u32>,
+        counts: &mut Composition,
+        up: u32,
+        turn: Turn,
+        first: Option<PlayerAction>,
+    ) -> f64 {
+        let value = value_of(hand);
+        if value > 21 {
+            return -1.0;
+        }
+        if value == 21 {
+            return self.stand_ev(value, counts, up);
+        }
+        let action = first.unwrap_or_else(|| self.decide(hand, counts, up, turn));
+        let next = Turn {
+            can_double: false,
+            can_split: false,
+            can_surrender: false,
+        };
+        match action {
+            PlayerAction::Stand => self.stand_ev(value, counts, up),
+            PlayerAction::Surrender if turn.can_surrender => {
+                let blackjack = self.dealer_outcomes(counts, up)[BLACKJACK];
+                -0.5 * (1.0 - blackjack) - blackjack
+            }
+            PlayerAction::Double if turn.can_double => {
+                2.0 * self.draw(hand, counts, |calculator, hand, counts| {
+                    let value = value_of(hand);
+                    if value > 21 {
+                        -1.0
+                    } else {
+                        calculator.stand_ev(value, counts, up)
+                    }
+                })
+            }
+            PlayerAction::Split if turn.can_split => {
+                let split = Turn {
+                    can_double: self.rules.double_after_split,
+                    ..next
+                };
+                let card = hand.pop().unwrap();
+                let ev = self.draw(hand, counts, |calculator, hand, counts| {
+                    calculator.play(hand, counts, up, split, None)
+                });
+                hand.push(card);
+                2.0 * ev
+            }
+            _ => self.draw(hand, counts, |calculator, hand, counts| {
+                calculator.play(hand, counts, up, next, None)
+            }),
+        }
+    }
+
+    /// The expected value of `then` over each card the
+    /// player can draw, averaged by their probabilities.
+    fn draw<F>(&mut self, hand: &mut Vec<u32>, counts: &mut Composition, then: F) -> f64
+    where
+        F: Fn(&mut Self, &mut Vec<u32>, &mut Composition) -> f64,
+    {
+        let remaining: u32 = counts.iter().sum();
+        let mut ev = 0.0;
+        for value in 1..=10 {
+            let n = counts[value as usize - 1];
+            if n == 0 {
+                continue;
+            }
+            counts[value as usize - 1] -= 1;
+            hand.push(value);
+            ev += n as f64 / remaining as f64 * then(self, hand, counts);
+            hand.pop();
+            counts[value as usize - 1] += 1;
+        }
+        ev
+    }
+
+    /// Asks the strategy what to do with a hand.
+    fn decide(&self, hand: &[u32], counts: &Composition, up: u32, turn: Turn) -> PlayerAction {
+        let card = |value: u32| Card {
+            rank: rank_of_value(value),
+            suit: Suit::Spades,
+        };
+        let cards: Vec<Card> = hand.iter().map(|&value| card(value)).collect();
+        let mut dealt = self.full;
+        for (dealt, count) in dealt.iter_mut().zip(counts) {
+            *dealt -= count;
+        }
+        self.strategy.decide(&Situation {
+            hand: &cards,
+            dealer_card: &card(up),
+            can_double: turn.can_double,
+            can_split: turn.can_split,
+            can_surrender: turn.can_surrender,
+            dealt: &dealt,
+            decks_remaining: counts.iter().sum::<u32>() as f64 / 52.0,
+            rules: self.rules,
+        })
+    }
+
+    /// The expected value of standing with the given value.
+    fn stand_ev(&mut self, value: u32, counts: &Composition, up: u32) -> f64 {
+        let outcomes = self.dealer_outcomes(counts, up);
+        let mut ev = outcomes[BUST] - outcomes[BLACKJACK];
+        for (dealer_value, probability) in (17..).zip(&outcomes[..BUST]) {
+            if value > dealer_value {
+                ev += probability;
+            } else if value < dealer_value {
+                ev -= probability;
+            }
+        }
+        ev
+    }
+
+    /// The probabilities of the final hands of the dealer, drawing
+    /// from the given shoe after the upcard.
+    fn dealer_outcomes(&mut self, counts: &Composition, up: u32) -> [f64; OUTCOMES] {
+        if let Some(outcomes) = self.dealer.get(&(*counts, up)) {
+            return *outcomes;
+        }
+        let mut outcomes = [0.0; OUTCOMES];
+        dealer_draw(
+            self.rules,
+            &mut counts.clone(),
+            &mut vec![up],
+            1.0,
+            &mut outcomes,
+        );
+        self.dealer.insert((*counts, up), outcomes);
+        outcomes
+    }
+}
+
+/// Adds the probabilities of the final hands of the dealer,
+/// from a hand reached with the given probability.
+fn dealer_draw(
+    rules: &Rules,
+    counts: &mut Composition,
+    hand: &mut Vec<u32>,
+    probability: f64,
+    outcomes: &mut [f64; OUTCOMES],
+) {
+    let value = value_of(hand);
+    let soft = hand.contains(&1) && hand.iter().sum::<u32>() + 10 == value;
+    if hand.len() == 2 && value == 21 {
+        outcomes[BLACKJACK] += probability;
+    } else if value > 21 {
+        outcomes[BUST] += probability;
+    } else if value >= 17 && !(value == 17 && soft && rules.dealer_hits_soft_17) {
+        outcomes[value as usize - 17] += probability;
+    } else {
+        let remaining: u32 = counts.iter().sum();
+        for value in 1..=10 {
+            let n = counts[value as usize - 1];
+            if n == 0 {
+                continue;
+            }
+            counts[value as usize - 1] -= 1;
+            hand.push(value);
+            let p = probability * n as f64 / remaining as f64;
+            dealer_draw(rules, counts, hand, p, outcomes);
+            hand.pop();
+            counts[value as usize - 1] += 1;
+        }
+    }
+}
+
+/// The change of the expected value when a single card of each
+/// value is removed from the shoe, from the Aces to the tens,
+/// along with the expected value of the full shoe.
+pub fn effects_of_removal(
+    counts: &Composition,
+    ev: impl Fn(&Composition) -> f64,
+) -> (f64, [f64; 10]) {
+    let base = ev(counts);
+    let mut effects = [0.0; 10];
+    for (value, effect) in (1..=10).zip(effects.iter_mut()) {
+        *effect = ev(&without(counts, value)) - base;
+    }
+    (base, effects)
+}
+
+/// The expected value of insurance when the dealer shows an
+/// Ace, the other cards being left in the shoe.
+fn insurance_ev(counts: &Composition) -> f64 {
+    1.5 * counts[9] as f64 / counts.iter().sum::<u32>() as f64 - 0.5
+}
+
+/// The correlation between the tags of a count and the effects of
+/// removal of the cards, weighted by how many cards of each value
+/// a pack holds.
+pub fn correlation(tags: &[i32; 10], effects: &[f64; 10]) -> f64 {
+    let weight = |i: usize| if i == 9 { 4.0 } else { 1.0 };
+    let mean = |x: &dyn Fn(usize) -> f64| (0..10).map(|i| weight(i) * x(i)).sum::<f64>() / 13.0;
+    let tag = |i: usize| tags[i] as f64;
+    let effect = |i: usize| effects[i];
+    let (mean_tag, mean_effect) = (mean(&tag), mean(&effect));
+    let covariance = mean(&|i| (tag(i) - mean_tag) * (effect(i) - mean_effect));
+    let variance_tag = mean(&|i| (tag(i) - mean_tag).powi(2));
+    let variance_effect = mean(&|i| (effect(i) - mean_effect).powi(2));
+    if variance_tag == 0.0 || variance_effect == 0.0 {
+        return 0.0;
+    }
+    covariance / (variance_tag * variance_effect).sqrt()
+}
+
+/// Two cards making a hand of an index, preferring a ten, and
+/// avoiding pairs but for hard 20, which is mostly two tens.
+pub fn representative_hand(hand: IndexHand) -> [u32; 2] {
+    match hand {
+        IndexHand::Hard(total) => {
+            let high = 10.min(total - 2);
+            let low = total - high;
+            if low == high && high < 10 {
+                [low - 1, high + 1]
+            } else {
+                [low, high]
+            }
+        }
+        IndexHand::Soft(total) => [1, total - 11],
+        IndexHand::Pair(value) => [value, value],
+        IndexHand::Insurance => [10, 10],
+    }
+}
+
+/// The analysis of the shoe for the rules and the strategy
+/// of the table, shared by all counting systems.
+pub struct Analysis {
+    pub ev: f64,
+    /// The effects of removal on the expected value.
+    pub effects: [f64; 10],
+    /// The effects of removal on the expected value of insurance.
+    pub insurance_effects: [f64; 10],
+    /// The effects of removal on the difference between the two
+    /// actions of the playing decisions of the Illustrious 18
+    /// and of the Fab 4.
+    pub playing_effects: Vec<[f64; 10]>,
+}
+
+impl Analysis {
+    pub fn new(rules: &Rules, strategy: &dyn Strategy) -> Analysis {
+        let counts = full_shoe(rules.num_packs);
+        let (ev, effects) = effects_of_removal(&counts, |counts| {
+            Calculator::new(rules, strategy).expected_value(counts)
+        });
+        let (_, insurance_effects) = effects_of_removal(&without(&counts, 1), insurance_ev);
+
+        let mut playing_effects = Vec::new();
+        let named = ILLUSTRIOUS_18.iter().chain(FAB_4.iter());
+        for (_, index) in named.filter(|named| named.1.hand != IndexHand::Insurance) {
+            if index.action == PlayerAction::Surrender && !rules.surrender {
+                continue;
+            }
+            let decision = Decision {
+                hand: index.hand,
+                up: index.up,
+                action: match index.action {
+                    PlayerAction::Hit => PlayerAction::Stand,
+                    action => action,
+                },
+            };
+            let hand = representative_hand(index.hand);
+            let up = if index.up == 11 { 1 } else { index.up };
+            let cards: Vec<Card> = hand
+                .iter()
+                .map(|&value| Card {
+                    rank: rank_of_value(value),
+                    suit: Suit::Spades,
+                })
+                .collect();
+            let dealer_card = Card {
+                rank: rank_of_value(up),
+                suit: Suit::Spades,
+            };
+            let (first, second) = compared_actions(&decision, &cards, &dealer_card, rules);
+            let mut counts = without(&without(&counts, up), hand[0]);
+            counts = without(&counts, hand[1]);
+            let (_, effects) = effects_of_removal(&counts, |counts| {
+                let mut calculator = Calculator::new(rules, strategy);
+                calculator.round_ev(&hand, counts, up, Some(first))
+                    - calculator.round_ev(&hand, counts, up, Some(second))
+            });
+            playing_effects.push(effects);
+        }
+        Analysis {
+            ev,
+            effects,
+            insurance_effects,
+            playing_effects,
+        }
+    }
+
+    /// The betting correlation of a count : how well its tags
+    /// follow the effects of removal on the expected value.
+    pub fn betting_correlation(&self, count: &CountSystem) -> f64 {
+        correlation(&count.tags, &self.effects)
+    }
+
+    /// The playing efficiency of a count : the average correlation
+    /// of its tags with the effects of removal on the playing
+    /// decisions of the Illustrious 18 and of the Fab 4.
+    pub fn playing_efficiency(&self, count: &CountSystem) -> f64 {
+        let total: f64 = self
+            .playing_effects
+            .iter()
+            .map(|effects| correlation(&count.tags, effects).abs())
+            .sum();
+        total / self.playing_effects.len() as f64
+    }
+
+    /// The insurance correlation of a count.
+    pub fn insurance_correlation(&self, count: &CountSystem) -> f64 {
+        correlation(&count.tags, &self.insurance_effects)
+    }
+}
+
+/// Runs the `eor` subcommand, which computes the effects of
+/// removal of the cards for the rules of the table, and how
+/// well counting systems capture them.
+pub fn run(args: &Args) -> Result<(), String> {
+    args.check_options(&[&["strategy"], RULE_OPTIONS])?;
+    let rules = args.rules()?;
+    let name = args.get("strategy").unwrap_or("basic");
+    let strategy = strategy_from_name(name)?;
+    let systems = if args.positional.is_empty() {
+        DEFAULT_SYSTEMS
+            .iter()
+            .map(|name| name.to_string())
+            .collect()
+    } else {
+        args.positional.clone()
+    };
+    let systems = systems
+        .iter()
+        .map(|system| CountSystem::parse(system))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let analysis = Analysis::new(&rules, strategy.as_ref());
+    println!("Rules : {}, strategy {}", rules, name);
+    println!("Exact EV of a full shoe : {:+.3}%\n", 100.0 * analysis.ev);
+    println!("Effects of removal of a single card, in % :");
+    let values = ["A", "2", "3", "4", "5", "6", "7", "8", "9", "10"];
+    println!(
+        "{:<12}{}",
+        "",
+        values
+            .iter()
+            .map(|v| format!("{:>8}", v))
+            .collect::<String>()
+    );
+    for (label, effects) in &[
+        ("EV", analysis.effects),
+        ("Insurance", analysis.insurance_effects),
+    ] {
+        let effects: String = effects
+            .iter()
+            .map(|effect| format!("{:>+8.4}", 100.0 * effect))
+            .collect();
+        println!("{:<12}{}", label, effects);
+    }
+    println!("\n{:<12}{:>8}{:>8}{:>8}", "Count", "BC", "PE", "IC");
+    for count in &systems {
+        println!(
+            "{:<12}{:>8.3}{:>8.3}{:>8.3}",
+            count.name,
+            analysis.betting_correlation(count),
+            analysis.playing_efficiency(count),
+            analysis.insurance_correlation(count)
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eor::*;
+    use crate::strategy::BasicStrategy;
+
+    #[test]
+    fn dealer_outcomes_test() {
+        let rules = Rules::default();
+        let mut calculator = Calculator::new(&rules, &BasicStrategy);
+        let counts = full_shoe(1);
+        for up in 1..=10 {
+            let outcomes = calculator.dealer_outcomes(&without(&counts, up), up);
+            assert!((outcomes.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        }
+        // a dealer showing an Ace has a blackjack when the next card is worth 10
+        let outcomes = calculator.dealer_outcomes(&without(&counts, 1), 1);
+        assert!((outcomes[BLACKJACK] - 16.0 / 51.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn tens_only_shoe() {
+        let rules = Rules::default();
+        let mut calculator = Calculator::new(&rules, &BasicStrategy);
+        // every round is a 20 against a 20
+        let tens = [0, 0, 0, 0, 0, 0, 0, 0, 0, 20];
+        assert_eq!(0.0, calculator.expected_value(&tens));
+        // a 10 against a 20 draws a ten to push, or loses when standing
+        let ev = calculator.round_ev(&[4, 6], &tens, 10, Some(PlayerAction::Double));
+        assert_eq!(0.0, ev);
+        let ev = calculator.round_ev(&[4, 6], &tens, 10, Some(PlayerAction::Stand));
+        assert_eq!(-1.0, ev);
+        // blackjack pays 3 to 2
+        assert_eq!(1.5, calculator.round_ev(&[1, 10], &tens, 10, None));
+    }
+
+    #[test]
+    fn correlation_test() {
+        let hi_lo = CountSystem::hi_lo();
+        let effects = hi_lo.tags.map(|tag| tag as f64 * 0.1);
+        assert!((correlation(&hi_lo.tags, &effects) - 1.0).abs() < 1e-12);
+        let opposite = hi_lo.tags.map(|tag| -tag as f64);
+        assert!((correlation(&hi_lo.tags, &opposite) + 1.0).abs() < 1e-12);
+        assert_eq!(0.0, correlation(&[0; 10], &effects));
+    }
+
+    #[test]
+    fn insurance_correlation() {
+        let (_, effects) = effects_of_removal(&without(&full_shoe(4), 1), insurance_ev);
+        let hi_lo = correlation(&CountSystem::hi_lo().tags, &effects);
+        assert!((hi_lo - 0.76).abs() < 0.01);
+        // a count of the tens only is perfect for insurance
+        let tens = [1, 1, 1, 1, 1, 1, 1, 1, 1, -2];
+        assert!(correlation(&tens, &effects) > 0.99);
+    }
+
+    #[test]
+    fn representative_hands() {
+        assert_eq!([6, 10], representative_hand(IndexHand::Hard(16)));
+        assert_eq!([10, 10], representative_hand(IndexHand::Hard(20)));
+        assert_eq!([2, 7], representative_hand(IndexHand::Hard(9)));
+        assert_eq!([2, 6], representative_hand(IndexHand::Hard(8)));
+        assert_eq!([1, 7], representative_hand(IndexHand::Soft(18)));
+    }
+}
@@ -0,0 +1,396 @@
+//! External players : programs, written in any language, that
+//! play a seat of the table over their standard input and output,
+//! one JSON object per line.
+//!
+//! The game sends the program messages with a `type` :
+//!
+//! - `hello`, once, before the first round : the `version` of the
+//!   protocol, the `seat` of the program (counted from 0), its
+//!   `name`, the `rules` and `limits` of the table and its `bank`.
+//! - `bet`, at the start of each round : the `round`, the `bank`
+//!   the bet is taken from, the `limits` of the table (`min`, `max`
+//!   and `chip`), the `last_bet` of the program (or null), the
+//!   `table_bets` placed by the seats before it (empty when bets
+//!   are secret) and the number of `cards_left` in the shoe, which
+//!   goes up when the shoe is reshuffled.
+//!   The program answers `{"bet": 25}`.
+//! - `action`, on each decision : the `round`, the `hand` being
+//!   played and whether it is the `second` hand of a split, the
+//!   `dealer` hand, the `table` (the `name`, `hands` and `bet` of
+//!   every seat), the `bank` of the program, the `cards_left` and
+//!   the `legal` actions among `hit`, `stand`, `double`, `split` and
+//!   `surrender`. The program answers `{"action": "hit"}`.
+//! - `result`, at the end of each round : the `round`, the `dealer`
+//!   hand, the `table`, the `net` money the program won (negative
+//!   when it lost) and its `bank`.
+//!
+//! Cards are written in ASCII, as in `10C` or `AH`. Answers may
+//! hold other fields, which are ignored. An answer that does not
+//! come within the timeout, cannot be read, or gives a bet or an
+//! action that is not allowed, is reported on the error output and
+//! replaced by the smallest bet allowed, or by standing. The input
+//! of the program is closed when the game ends.
+//!
+//! ```text
+//! > {"type":"bet","round":1,"bank":300,"limits":{"min":10,"max":500,"chip":5},...}
+//! < {"bet":10}
+//! > {"type":"action","round":1,"hand":["9C","2H"],"second":false,...,"legal":["hit","stand","double"]}
+//! < {"action":"double"}
+//! ```
+
+use crate::betting::TableLimits;
+use crate::card::Card;
+use crate::history::card_code;
+use crate::player::PlayerAction;
+use crate::rules::Rules;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// The version of the protocol spoken by the game.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The time given to external players to answer, in seconds,
+/// unless `--external-timeout` says otherwise.
+pub const DEFAULT_TIMEOUT: f64 = 5.0;
+
+/// A seat of the table, as external players see it.
+#[derive(Serialize, Debug)]
+pub struct SeatView<'a> {
+    pub name: &'a str,
+    /// The first hand of the seat, and the second one after a split.
+    pub hands: Vec<Vec<String>>,
+    pub bet: u32,
+}
+
+/// The seats of the table, as external players see them.
+pub fn table_view<'a>(
+    names: &'a [String],
+    player_hands: &[(Vec<Card>, Option<Vec<Card>>)],
+    bets: &[u32],
+) -> Vec<SeatView<'a>> {
+    names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| SeatView {
+            name,
+            hands: player_hands
+                .get(index)
+                .map(|(first, second)| {
+                    std::iter::once(first)
+                        .chain(second)
+                        .map(|hand| cards(hand))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            bet: bets.get(index).copied().unwrap_or(0),
+        })
+        .collect()
+}
+
+/// Cards as they are written in the messages.
+pub fn cards(hand: &[Card]) -> Vec<String> {
+    hand.iter().map(card_code).collect()
+}
+
+/// A message sent to an external player.
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Message<'a> {
+    Hello {
+        version: u32,
+        seat: usize,
+        name: &'a str,
+        rules: &'a Rules,
+        limits: &'a TableLimits,
+        bank: u32,
+    },
+    Bet {
+        round: usize,
+        bank: u32,
+        limits: &'a TableLimits,
+        last_bet: Option<u32>,
+        table_bets: &'a [u32],
+        cards_left: usize,
+    },
+    Action {
+        round: usize,
+        hand: Vec<String>,
+        second: bool,
+        dealer: Vec<String>,
+        table: Vec<SeatView<'a>>,
+        bank: u32,
+        cards_left: usize,
+        legal: &'a [PlayerAction],
+    },
+    Result {
+        round: usize,
+        dealer: Vec<String>,
+        table: Vec<SeatView<'a>>,
+        net: i64,
+        bank: u32,
+    },
+}
+
+#[derive(Deserialize)]
+struct BetAnswer {
+    bet: u32,
+}
+
+#[derive(Deserialize)]
+struct ActionAnswer {
+    action: PlayerAction,
+}
+
+/// Parses the answer of an external player.
+fn parse_answer<T: DeserializeOwned>(line: &str) -> Result<T, String> {
+    serde_json::from_str(line).map_err(|e| format!("cannot read the answer '{}' : {}", line, e))
+}
+
+/// Reads the lines of an input on another thread, so that the
+/// game can stop waiting for them. The lines stop when the input
+/// is closed or cannot be read.
+pub fn read_lines(input: impl Read + Send + 'static) -> Receiver<String> {
+    let (sender, lines) = channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(input).lines() {
+            if line
+                .map(|line| sender.send(line))
+                .map_or(true, |sent| sent.is_err())
+            {
+                break;
+            }
+        }
+    });
+    lines
+}
+
+/// An external player : a program started for a seat of the table.
+pub struct ExternalPlayer {
+    name: String,
+    child: Child,
+    /// The input of the program, until the game closes it.
+    input: RefCell<Option<ChildStdin>>,
+    /// The lines the program writes, as in [read_lines].
+    lines: Receiver<String>,
+    timeout: Duration,
+}
+
+impl ExternalPlayer {
+    /// Starts the program of a seat, with the shell, so that the
+    /// command may hold arguments, as in `python3 bot.py --fast`.
+    pub fn start(command: &str, name: &str, timeout: f64) -> Result<ExternalPlayer, String> {
+        let (shell, flag) = if cfg!(windows) {
+            ("cmd", "/C")
+        } else {
+            ("sh", "-c")
+        };
+        let mut child = Command::new(shell)
+            .arg(flag)
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Cannot start the player {} ({}) : {}", name, command, e))?;
+        let lines = read_lines(child.stdout.take().unwrap());
+        Ok(ExternalPlayer {
+            name: name.to_string(),
+            input: RefCell::new(child.stdin.take()),
+            child,
+            lines,
+            timeout: Duration::from_secs_f64(timeout),
+        })
+    }
+
+    /// Sends a message to the program.
+    pub fn send(&self, message: &Message) -> Result<(), String> {
+        let mut line = serde_json::to_string(message).map_err(|e| e.to_string())?;
+        line.push('\n');
+        match self.input.borrow_mut().as_mut() {
+            Some(input) => input
+                .write_all(line.as_bytes())
+                .and_then(|()| input.flush())
+                .map_err(|e| format!("cannot send a message : {}", e)),
+            None => Err(String::from("the game is over")),
+        }
+    }
+
+    /// Sends a message to the program and reads its answer.
+    fn ask<T: DeserializeOwned>(&self, message: &Message) -> Result<T, String> {
+        // the answers that came too late to earlier questions
+        while self.lines.try_recv().is_ok() {}
+        self.send(message)?;
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.lines.recv_timeout(left) {
+                Ok(line) if line.trim().is_empty() => continue,
+                Ok(line) => return parse_answer(&line),
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(format!("no answer within {:?}", self.timeout))
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(String::from("the program has stopped"))
+                }
+            }
+        }
+    }
+
+    /// Reports a problem with the program.
+    fn warn(&self, message: &str) {
+        eprintln!("{} (external) : {}", self.name, message);
+    }
+
+    /// Tells the program something, reporting the errors.
+    pub fn tell(&self, message: &Message) {
+        if let Err(e) = self.send(message) {
+            self.warn(&e);
+        }
+    }
+
+    /// Asks the program for its bet, which must be allowed by the
+    /// limits of the table. The smallest bet allowed is taken when
+    /// the program does not give one.
+    pub fn bet(&self, message: &Message, limits: &TableLimits, available: u32) -> u32 {
+        let answer = self.ask(message).and_then(|answer: BetAnswer| {
+            if limits.allows(answer.bet, available) {
+                Ok(answer.bet)
+            } else {
+                Err(format!("the bet {} is not allowed", answer.bet))
+            }
+        });
+        answer.unwrap_or_else(|e| {
+            let bet = limits.clamp(0.0, available);
+            self.warn(&format!("{}, betting {}", e, bet));
+            bet
+        })
+    }
+
+    /// Asks the program for its action, which must be one of the
+    /// `legal` ones. Standing is taken when the program does not
+    /// give one.
+    pub fn action(&self, message: &Message, legal: &[PlayerAction]) -> PlayerAction {
+        let answer = self.ask(message).and_then(|answer: ActionAnswer| {
+            if legal.contains(&answer.action) {
+                Ok(answer.action)
+            } else {
+                Err(format!("{:?} is not allowed", answer.action))
+            }
+        });
+        answer.unwrap_or_else(|e| {
+            self.warn(&format!("{}, standing", e));
+            PlayerAction::Stand
+        })
+    }
+}
+
+impl Drop for ExternalPlayer {
+    /// Closes the input of the program, which should then stop,
+    /// and stops it when it does not within the timeout.
+    fn drop(&mut self) {
+        self.input.borrow_mut().take();
+        let deadline = Instant::now() + self.timeout;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::external::*;
+
+    /// The command of the reference bot of the repository.
+    const ECHO_BOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/bots/echo-bot.sh");
+
+    fn bet_message(limits: &TableLimits) -> Message<'_> {
+        Message::Bet {
+            round: 1,
+            bank: 300,
+            limits,
+            last_bet: None,
+            table_bets: &[],
+            cards_left: 208,
+        }
+    }
+
+    fn action_message(legal: &[PlayerAction]) -> Message<'_> {
+        Message::Action {
+            round: 1,
+            hand: vec![String::from("9C"), String::from("2H")],
+            second: false,
+            dealer: vec![String::from("6S")],
+            table: Vec::new(),
+            bank: 290,
+            cards_left: 205,
+            legal,
+        }
+    }
+
+    #[test]
+    fn messages() {
+        let limits = TableLimits::default();
+        let text = serde_json::to_string(&bet_message(&limits)).unwrap();
+        assert!(text.starts_with("{\"type\":\"bet\",\"round\":1,\"bank\":300,\"limits\":{"));
+        let legal = [PlayerAction::Hit, PlayerAction::Double];
+        let text = serde_json::to_string(&action_message(&legal)).unwrap();
+        assert!(text.contains("\"hand\":[\"9C\",\"2H\"]"), "{}", text);
+        assert!(
+            text.ends_with("\"legal\":[\"hit\",\"double\"]}"),
+            "{}",
+            text
+        );
+        let answer: ActionAnswer = parse_answer("{\"action\":\"split\",\"why\":\"8s\"}").unwrap();
+        assert_eq!(PlayerAction::Split, answer.action);
+        assert!(parse_answer::<ActionAnswer>("{\"action\":\"fold\"}").is_err());
+        assert!(parse_answer::<BetAnswer>("{\"bet\":-5}").is_err());
+    }
+
+    #[test]
+    fn echo_bot() {
+        let bot = ExternalPlayer::start(ECHO_BOT, "Echo", 5.0).unwrap();
+        let limits = TableLimits {
+            min: 10,
+            ..TableLimits::default()
+        };
+        assert_eq!(10, bot.bet(&bet_message(&limits), &limits, 300));
+        let legal = [PlayerAction::Double, PlayerAction::Hit];
+        assert_eq!(
+            PlayerAction::Double,
+            bot.action(&action_message(&legal), &legal)
+        );
+    }
+
+    #[test]
+    fn invalid_answers() {
+        let limits = TableLimits {
+            min: 10,
+            ..TableLimits::default()
+        };
+        let legal = [PlayerAction::Hit, PlayerAction::Stand];
+        let greedy = "while read line; do echo '{\"bet\": 5000, \"action\": \"split\"}'; done";
+        let bot = ExternalPlayer::start(greedy, "Greedy", 5.0).unwrap();
+        assert_eq!(10, bot.bet(&bet_message(&limits), &limits, 300));
+        assert_eq!(
+            PlayerAction::Stand,
+            bot.action(&action_message(&legal), &legal)
+        );
+        let slow = ExternalPlayer::start("sleep 5", "Slow", 0.2).unwrap();
+        assert_eq!(10, slow.bet(&bet_message(&limits), &limits, 300));
+        let gone = ExternalPlayer::start("exit 0", "Gone", 5.0).unwrap();
+        assert_eq!(
+            PlayerAction::Stand,
+            gone.action(&action_message(&legal), &legal)
+        );
+    }
+}
@@ -0,0 +1,155 @@
+/// A series of values to draw, with the character marking it.
+pub struct Series {
+    pub name: String,
+    pub marker: char,
+    pub values: Vec<i64>,
+}
+
+/// The marker of the series of a player, from their index :
+/// `1` to `9`, then letters.
+pub fn player_marker(index: usize) -> char {
+    std::char::from_digit(index as u32 + 1, 36).unwrap_or('*')
+}
+
+/// The width of the terminal, or 80 columns when it is unknown.
+pub fn terminal_width() -> usize {
+    crossterm::terminal::size()
+        .map(|(width, _)| width as usize)
+        .unwrap_or(80)
+}
+
+/// The value of a series at a fraction of its length, between
+/// 0 and 1, interpolated between the two closest values.
+fn value_at(values: &[i64], fraction: f64) -> f64 {
+    let position = fraction * (values.len() - 1) as f64;
+    let before = position.floor() as usize;
+    let after = (before + 1).min(values.len() - 1);
+    let weight = position - before as f64;
+    values[before] as f64 * (1.0 - weight) + values[after] as f64 * weight
+}
+
+/// Draws series as lines in a chart of the given total width and
+/// height in characters, with the values on the left, the rounds
+/// below and a legend. Series are stretched or squeezed to the
+/// width of the chart, and each column is joined to the previous
+/// one by a vertical line of markers.
+pub fn line_chart(series: &[Series], width: usize, height: usize) -> String {
+    let series: Vec<&Series> = series.iter().filter(|s| !s.values.is_empty()).collect();
+    if series.is_empty() {
+        return String::from("Nothing to draw yet.\n");
+    }
+    let all = series.iter().flat_map(|s| s.values.iter().copied());
+    let (mut low, mut high) = (i64::MAX, i64::MIN);
+    for value in all {
+        low = low.min(value);
+        high = high.max(value);
+    }
+    if low == high {
+        low -= 1;
+        high += 1;
+    }
+    let height = height.max(3);
+    let label_width = [low, high, (low + high) / 2]
+        .iter()
+        .map(|value| value.to_string().len())
+        .max()
+        .unwrap();
+    let plot_width = width.saturating_sub(label_width + 2).max(10);
+    let row_of = |value: f64| {
+        let fraction = (high as f64 - value) / (high - low) as f64;
+        (fraction * (height - 1) as f64).round() as usize
+    };
+
+    let mut grid = vec![vec![' '; plot_width]; height];
+    if low < 0 && high > 0 {
+        let zero = row_of(0.0);
+        grid[zero] = vec!['.'; plot_width];
+    }
+    for s in series.iter() {
+        let mut previous = None;
+        for column in 0..plot_width {
+            let fraction = column as f64 / (plot_width - 1) as f64;
+            let row = row_of(value_at(&s.values, fraction));
+            let from = previous.unwrap_or(row);
+            for cell in grid.iter_mut().take(row.max(from) + 1).skip(row.min(from)) {
+                cell[column] = s.marker;
+            }
+            previous = Some(row);
+        }
+    }
+
+    let mut out = String::new();
+    for (index, line) in grid.iter().enumerate() {
+        let label = match index {
+            0 => high.to_string(),
+            i if i == height - 1 => low.to_string(),
+            i if i == (height - 1) / 2 => ((low + high) / 2).to_string(),
+            _ => String::new(),
+        };
+        let line: String = line.iter().collect();
+        out += &format!("{:>w$} |{}\n", label, line.trim_end(), w = label_width);
+    }
+    out += &format!("{:>w$} +{}\n", "", "-".repeat(plot_width), w = label_width);
+    let rounds = series.iter().map(|s| s.values.len()).max().unwrap() - 1;
+    let last = format!("round {}", rounds);
+    out += &format!(
+        "{:>w$}  round 0{:>r$}\n",
+        "",
+        last,
+        w = label_width,
+        r = plot_width.saturating_sub(7)
+    );
+    let legend: Vec<String> = series
+        .iter()
+        .map(|s| format!("{} {}", s.marker, s.name))
+        .collect();
+    out += &legend.join("   ");
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::*;
+
+    fn series(marker: char, values: Vec<i64>) -> Series {
+        Series {
+            name: format!("Series {}", marker),
+            marker,
+            values,
+        }
+    }
+
+    #[test]
+    fn rising_line() {
+        let chart = line_chart(&[series('1', vec![0, 10, 20])], 22, 5);
+        let lines: Vec<&str> = chart.lines().collect();
+        assert_eq!("20 |               111", lines[0]);
+        assert_eq!("10 |       11111", lines[2]);
+        assert_eq!(" 0 |1111", lines[4]);
+        assert_eq!("   +------------------", lines[5]);
+        assert_eq!("    round 0    round 2", lines[6]);
+        assert_eq!("1 Series 1", lines[7]);
+    }
+
+    #[test]
+    fn several_series() {
+        let chart = line_chart(
+            &[series('1', vec![5; 200]), series('#', vec![-5, 5])],
+            40,
+            5,
+        );
+        // the zero line, crossed by the second series
+        assert!(chart.contains(" 0 |..............#########....."));
+        assert!(chart.contains("round 199\n"));
+        assert_eq!("1 Series 1   # Series #", chart.lines().last().unwrap());
+        assert_eq!("Nothing to draw yet.\n", line_chart(&[], 40, 5));
+    }
+
+    #[test]
+    fn markers() {
+        assert_eq!('1', player_marker(0));
+        assert_eq!('9', player_marker(8));
+        assert_eq!('a', player_marker(9));
+    }
+}
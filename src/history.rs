@@ -0,0 +1,833 @@
+//! Hand histories : every round of a game written to a text file,
+//! one event per line, in the spirit of poker hand histories.
+//!
+//! ```text
+//! Blackjack hand history, version 2
+//! Game from round 1, seed 42
+//! Table: 4D S17 DAS
+//! Seat 1: Ann (human)
+//! Seat 2: Bob (bot)
+//! Shoe: 9C KD AH 7S 5H 3D 10S 8D 2C ...
+//!
+//! Round 1, shoe at card 0 of 208
+//! Ann: bets 25
+//! Bob: bets 10
+//! Ann: dealt 9C
+//! Ann: dealt KD
+//! Bob: dealt AH
+//! Bob: dealt 7S
+//! Dealer: dealt 5H
+//! Ann: stands
+//! Bob: doubles
+//! Bob: dealt 3D
+//! Dealer: hits
+//! Dealer: dealt 10S
+//! Dealer: hits
+//! Dealer: dealt 8D
+//! Ann: wins 25 with 19
+//! Bob: wins 20 with 21
+//! Banks: 325, 320
+//! ```
+//!
+//! After the header, each game starts with the round it starts
+//! from (games resumed with `--resume` start later) and the seed
+//! of its shuffles, then gives the rules of the table and its
+//! seats, and the cards left in the shoe in the order they are
+//! dealt. Each round starts with its number and the position of
+//! the shoe, as the number of cards dealt out of the size of the
+//! shoe, and when the shoe was reshuffled before the round, a
+//! `Shuffle` line followed by the order of the new shoe, which
+//! also comes among the events of a round the shoe runs out in.
+//! The events of the round follow, each line starting with the
+//! player it is about : a seat name, `NAME (second hand)` after a
+//! split, or `Dealer`. Players bet, are dealt cards, decide
+//! (`hits`, `stands`, `doubles`, `splits`, `surrenders`), and their
+//! hands are settled : `blackjack`, `wins`, `pushes` or `loses`,
+//! with the money won or lost and the value of the hand. The round
+//! ends with the banks of the players. Cards are written in ASCII,
+//! their rank followed by the initial of their suit.
+
+use crate::art::suit_symbol;
+use crate::card::Card;
+use crate::cli::Args;
+use crate::math::hand_value;
+use crate::player::{PlayerAction, PlayerType};
+use crate::setup::parse_player;
+use crate::stats::Outcome;
+
+use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::str::FromStr;
+
+/// The version of the hand histories written by the game.
+pub const HISTORY_VERSION: u32 = 2;
+
+/// The hand history of the games that do not give one.
+pub const DEFAULT_HISTORY_FILE: &str = "blackjack-history.txt";
+
+/// The hand an event is about.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Hand {
+    /// A hand of the player at `seat`, counted from 0, which
+    /// is their `second` hand after a split.
+    Player {
+        seat: usize,
+        second: bool,
+    },
+    Dealer,
+}
+
+/// A line of a hand history.
+#[derive(PartialEq, Clone, Debug)]
+pub enum Record {
+    Header {
+        version: u32,
+    },
+    /// The start of a game, or of a resumed game, at a round,
+    /// with the seed of its shuffles when it is known.
+    Game {
+        round: u32,
+        seed: Option<u64>,
+    },
+    /// The rules of the table, as in `4D S17 DAS`.
+    Table {
+        rules: String,
+    },
+    /// A seat of the table, counted from 0.
+    Seat {
+        seat: usize,
+        name: String,
+        player: PlayerType,
+    },
+    /// The start of a round, with the number of cards
+    /// already dealt from the shoe and its size.
+    Round {
+        number: u32,
+        position: usize,
+        size: usize,
+    },
+    /// The shoe was reshuffled.
+    Shuffle,
+    /// The cards left in the shoe, the next one first.
+    Shoe(Vec<Card>),
+    Bet {
+        seat: usize,
+        amount: u32,
+    },
+    Dealt {
+        hand: Hand,
+        card: Card,
+    },
+    Action {
+        hand: Hand,
+        action: PlayerAction,
+    },
+    /// The settlement of a hand of a player, with the money won
+    /// (negative when it was lost) and the value of the hand.
+    Settle {
+        hand: Hand,
+        outcome: Outcome,
+        net: i64,
+        value: u32,
+    },
+    /// The banks of the players at the end of a round.
+    Banks(Vec<u32>),
+}
+
+/// A card in ASCII, as written in hand histories : `10C`, `AH`.
+pub fn card_code(card: &Card) -> String {
+    format!("{}{}", card.rank, suit_symbol(card.suit, false))
+}
+
+/// A decision as written in hand histories, as in `Ann: hits`.
+pub fn action_verb(action: PlayerAction) -> &'static str {
+    match action {
+        PlayerAction::Hit => "hits",
+        PlayerAction::Stand => "stands",
+        PlayerAction::Double => "doubles",
+        PlayerAction::Split => "splits",
+        PlayerAction::Surrender => "surrenders",
+    }
+}
+
+fn outcome_verb(outcome: Outcome) -> &'static str {
+    match outcome {
+        Outcome::Blackjack => "blackjack",
+        Outcome::Win => "wins",
+        Outcome::Push => "pushes",
+        Outcome::Loss => "loses",
+    }
+}
+
+impl Record {
+    /// The line of the record, given the names of the seats.
+    pub fn to_line(&self, names: &[String]) -> String {
+        let subject = |hand: &Hand| match hand {
+            Hand::Player {
+                seat,
+                second: false,
+            } => names[*seat].clone(),
+            Hand::Player { seat, second: true } => format!("{} (second hand)", names[*seat]),
+            Hand::Dealer => String::from("Dealer"),
+        };
+        match self {
+            Record::Header { version } => format!("Blackjack hand history, version {}", version),
+            Record::Game {
+                round,
+                seed: Some(seed),
+            } => format!("Game from round {}, seed {}", round, seed),
+            Record::Game { round, seed: None } => format!("Game from round {}", round),
+            Record::Table { rules } => format!("Table: {}", rules),
+            Record::Seat { seat, name, player } => {
+                format!("Seat {}: {} ({})", seat + 1, name, player)
+            }
+            Record::Round {
+                number,
+                position,
+                size,
+            } => format!("\nRound {}, shoe at card {} of {}", number, position, size),
+            Record::Shuffle => String::from("Shuffle"),
+            Record::Shoe(cards) => format!(
+                "Shoe: {}",
+                cards.iter().map(card_code).collect::<Vec<_>>().join(" ")
+            ),
+            Record::Bet { seat, amount } => format!("{}: bets {}", names[*seat], amount),
+            Record::Dealt { hand, card } => format!("{}: dealt {}", subject(hand), card_code(card)),
+            Record::Action { hand, action } => {
+                format!("{}: {}", subject(hand), action_verb(*action))
+            }
+            Record::Settle {
+                hand,
+                outcome,
+                net,
+                value,
+            } => format!(
+                "{}: {} {} with {}",
+                subject(hand),
+                outcome_verb(*outcome),
+                net.abs(),
+                value
+            ),
+            Record::Banks(banks) => format!(
+                "Banks: {}",
+                banks
+                    .iter()
+                    .map(u32::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+/// The settlement of the hands of a round.
+///
+/// # Arguments
+/// * `results` The blackjacked, won, pushed and lost
+///   hands, as computed by [crate::math::compute_result].
+/// * `player_hands` The hands of the players.
+/// * `bets` The bets of the players, after their doubles.
+pub fn settlement(
+    results: &[Vec<(usize, bool)>; 4],
+    player_hands: &[(Vec<Card>, Option<Vec<Card>>)],
+    bets: &[u32],
+) -> Vec<Record> {
+    let mut records = Vec::new();
+    for (hands, &outcome) in results.iter().zip(Outcome::BY_RESULT.iter()) {
+        for &(seat, second) in hands {
+            let bet = bets[seat] as i64;
+            let (first_hand, second_hand) = &player_hands[seat];
+            let cards = match second {
+                false => first_hand,
+                true => second_hand.as_ref().unwrap(),
+            };
+            records.push(Record::Settle {
+                hand: Hand::Player { seat, second },
+                outcome,
+                net: match outcome {
+                    Outcome::Blackjack => (3 * bet) >> 1,
+                    Outcome::Win => bet,
+                    Outcome::Push => 0,
+                    Outcome::Loss => -bet,
+                },
+                value: hand_value(cards),
+            });
+        }
+    }
+    records.sort_by_key(|record| match record {
+        Record::Settle {
+            hand: Hand::Player { seat, second },
+            ..
+        } => (*seat, *second),
+        _ => unreachable!(),
+    });
+    records
+}
+
+/// A hand history being written, one round at a time.
+///
+/// The events of a round are recorded with [HandHistory::record]
+/// while it is played, then written with [HandHistory::write_round].
+pub struct HandHistory {
+    file: File,
+    names: Vec<String>,
+    records: RefCell<Vec<Record>>,
+}
+
+impl HandHistory {
+    /// Opens a hand history to add the rounds of a game to it,
+    /// after the start of the game, the rules and the seats. The
+    /// header is written first when the file is new or empty, and
+    /// games are only added to hand histories of [HISTORY_VERSION].
+    pub fn open(
+        path: &str,
+        game: (u32, Option<u64>),
+        rules: &str,
+        names: &[String],
+        player_types: &[PlayerType],
+    ) -> Result<HandHistory, String> {
+        let error = |e: std::io::Error| format!("Cannot write the hand history {} : {}", path, e);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(error)?;
+        let history = HandHistory {
+            names: names.to_vec(),
+            records: RefCell::new(Vec::new()),
+            file,
+        };
+        let header = Record::Header {
+            version: HISTORY_VERSION,
+        };
+        if history.file.metadata().map_err(error)?.len() == 0 {
+            history.record(header);
+        } else {
+            let mut first = String::new();
+            BufReader::new(File::open(path).map_err(error)?)
+                .read_line(&mut first)
+                .map_err(error)?;
+            if parse_line(first.trim_end(), &[]).ok() != Some(header) {
+                return Err(format!(
+                    "{} is not a hand history of version {}, give another file with --hand-history",
+                    path, HISTORY_VERSION
+                ));
+            }
+        }
+        history.record(Record::Game {
+            round: game.0,
+            seed: game.1,
+        });
+        history.record(Record::Table {
+            rules: rules.to_string(),
+        });
+        for (seat, name) in names.iter().enumerate() {
+            history.record(Record::Seat {
+                seat,
+                name: name.clone(),
+                player: player_types[seat],
+            });
+        }
+        Ok(history)
+    }
+
+    /// Records an event of the round being played.
+    pub fn record(&self, record: Record) {
+        self.records.borrow_mut().push(record);
+    }
+
+    /// Writes the events recorded since the last round.
+    pub fn write_round(&mut self) -> Result<(), String> {
+        let mut text = String::new();
+        for record in self.records.borrow_mut().drain(..) {
+            text += &record.to_line(&self.names);
+            text.push('\n');
+        }
+        self.file
+            .write_all(text.as_bytes())
+            .map_err(|e| format!("Cannot write the hand history : {}", e))
+    }
+}
+
+/// Reads a hand history back into its records.
+///
+/// Blank lines are skipped. Errors give the number of the line
+/// at fault, counted from 1.
+pub fn parse_history(text: &str) -> Result<Vec<Record>, String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut records = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        let record = parse_line(line, &names).map_err(|e| format!("line {} : {}", index + 1, e))?;
+        if let Record::Header { version } = record {
+            if version != HISTORY_VERSION {
+                return Err(format!(
+                    "line {} : the hand history is of version {}, this game reads version {}",
+                    index + 1,
+                    version,
+                    HISTORY_VERSION
+                ));
+            }
+        }
+        // the seats of each game replace those of the game before
+        if let Record::Seat { seat, name, .. } = &record {
+            names.truncate(*seat);
+            names.push(name.clone());
+        }
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Parses a number of a line of a hand history.
+fn number<T: FromStr>(text: &str, line: &str) -> Result<T, String> {
+    text.trim()
+        .parse()
+        .map_err(|_| format!("cannot read '{}'", line))
+}
+
+/// Parses a line of a hand history, given the names
+/// of the seats read so far.
+fn parse_line(line: &str, names: &[String]) -> Result<Record, String> {
+    let invalid = || format!("cannot read '{}'", line);
+    if let Some(version) = line.strip_prefix("Blackjack hand history, version ") {
+        return Ok(Record::Header {
+            version: number(version, line)?,
+        });
+    }
+    if let Some(game) = line.strip_prefix("Game from round ") {
+        let (round, seed) = match game.split_once(", seed ") {
+            Some((round, seed)) => (round, Some(number(seed, line)?)),
+            None => (game, None),
+        };
+        return Ok(Record::Game {
+            round: number(round, line)?,
+            seed,
+        });
+    }
+    if line == "Shuffle" {
+        return Ok(Record::Shuffle);
+    }
+    if let Some(rest) = line.strip_prefix("Round ") {
+        let (round, shoe) = rest.split_once(", shoe at card ").ok_or_else(invalid)?;
+        let (position, size) = shoe.split_once(" of ").ok_or_else(invalid)?;
+        return Ok(Record::Round {
+            number: number(round, line)?,
+            position: number(position, line)?,
+            size: number(size, line)?,
+        });
+    }
+    let (subject, event) = line.split_once(": ").ok_or_else(invalid)?;
+    match subject {
+        "Table" => {
+            return Ok(Record::Table {
+                rules: event.to_string(),
+            })
+        }
+        "Shoe" => {
+            return Ok(Record::Shoe(
+                event
+                    .split_whitespace()
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()?,
+            ))
+        }
+        "Banks" => {
+            return Ok(Record::Banks(
+                event
+                    .split(", ")
+                    .map(|text| number(text, line))
+                    .collect::<Result<_, _>>()?,
+            ))
+        }
+        _ => {}
+    }
+    if let Some(seat) = subject.strip_prefix("Seat ") {
+        let (name, player) = event.rsplit_once(" (").ok_or_else(invalid)?;
+        return Ok(Record::Seat {
+            seat: number::<usize>(seat, line)?
+                .checked_sub(1)
+                .ok_or_else(invalid)?,
+            name: name.to_string(),
+            player: parse_player(player.strip_suffix(')').ok_or_else(invalid)?)?,
+        });
+    }
+
+    let hand = match subject {
+        "Dealer" => Hand::Dealer,
+        _ => {
+            let (name, second) = match subject.strip_suffix(" (second hand)") {
+                Some(name) => (name, true),
+                None => (subject, false),
+            };
+            let seat = names
+                .iter()
+                .position(|n| n == name)
+                .ok_or_else(|| format!("unknown player '{}'", name))?;
+            Hand::Player { seat, second }
+        }
+    };
+    let (verb, argument) = event.split_once(' ').unwrap_or((event, ""));
+    let actions = [
+        PlayerAction::Hit,
+        PlayerAction::Stand,
+        PlayerAction::Double,
+        PlayerAction::Split,
+        PlayerAction::Surrender,
+    ];
+    let outcomes = Outcome::BY_RESULT;
+    if let Some(&action) = actions.iter().find(|&&a| action_verb(a) == event) {
+        Ok(Record::Action { hand, action })
+    } else if verb == "dealt" {
+        Ok(Record::Dealt {
+            hand,
+            card: argument.parse()?,
+        })
+    } else if let (
+        Hand::Player {
+            seat,
+            second: false,
+        },
+        "bets",
+    ) = (hand, verb)
+    {
+        Ok(Record::Bet {
+            seat,
+            amount: number(argument, line)?,
+        })
+    } else if let Some(&outcome) = outcomes.iter().find(|&&o| outcome_verb(o) == verb) {
+        let (amount, value) = argument.split_once(" with ").ok_or_else(invalid)?;
+        let amount: i64 = number(amount, line)?;
+        Ok(Record::Settle {
+            hand,
+            outcome,
+            net: if outcome == Outcome::Loss {
+                -amount
+            } else {
+                amount
+            },
+            value: number(value, line)?,
+        })
+    } else {
+        Err(invalid())
+    }
+}
+
+/// What each seat did over the rounds of a hand history.
+#[derive(PartialEq, Eq, Debug)]
+pub struct PlayerSummary {
+    pub name: String,
+    /// The number of hands settled with each outcome,
+    /// in the order of [Outcome::BY_RESULT].
+    pub outcomes: [u32; 4],
+    /// The money bet at the start of the rounds,
+    /// without the doubles and the splits.
+    pub bet: u64,
+    pub net: i64,
+}
+
+/// Sums up the settlements of the hands of each player, over
+/// all the games of the hand history, the players being told
+/// apart by their names.
+/// Returns the number of rounds along with the players.
+pub fn summarize(records: &[Record]) -> (u32, Vec<PlayerSummary>) {
+    let mut rounds = 0;
+    let mut players: Vec<PlayerSummary> = Vec::new();
+    // the player of each seat of the game being read
+    let mut seats = Vec::new();
+    for record in records {
+        match record {
+            Record::Seat { seat, name, .. } => {
+                let player = match players.iter().position(|p| &p.name == name) {
+                    Some(player) => player,
+                    None => {
+                        players.push(PlayerSummary {
+                            name: name.clone(),
+                            outcomes: [0; 4],
+                            bet: 0,
+                            net: 0,
+                        });
+                        players.len() - 1
+                    }
+                };
+                seats.truncate(*seat);
+                seats.push(player);
+            }
+            Record::Round { .. } => rounds += 1,
+            Record::Bet { seat, amount } => players[seats[*seat]].bet += *amount as u64,
+            Record::Settle {
+                hand: Hand::Player { seat, .. },
+                outcome,
+                net,
+                ..
+            } => {
+                let summary = &mut players[seats[*seat]];
+                let index = Outcome::BY_RESULT.iter().position(|o| o == outcome);
+                summary.outcomes[index.unwrap()] += 1;
+                summary.net += net;
+            }
+            _ => {}
+        }
+    }
+    (rounds, players)
+}
+
+/// Runs the `history` subcommand : `history FILE`, which reads
+/// a hand history and shows what each player did in it.
+pub fn run(args: &Args) -> Result<(), String> {
+    args.check_options(&[])?;
+    let path = match args.positional.as_slice() {
+        [path] => path,
+        _ => return Err(String::from("Usage: history FILE")),
+    };
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read the hand history {} : {}", path, e))?;
+    let records = parse_history(&text).map_err(|e| format!("{}, {}", path, e))?;
+    let (rounds, players) = summarize(&records);
+    println!("{} rounds played.", rounds);
+    println!(
+        "{:<12} {:>9} {:>6} {:>6} {:>6} {:>9} {:>9}",
+        "Player", "Blackjack", "Wins", "Pushes", "Losses", "Bets", "Net"
+    );
+    for player in players {
+        let [blackjacks, wins, pushes, losses] = player.outcomes;
+        println!(
+            "{:<12} {:>9} {:>6} {:>6} {:>6} {:>9} {:>+9}",
+            player.name, blackjacks, wins, pushes, losses, player.bet, player.net
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::card::{Rank, Suit};
+    use crate::history::*;
+    use crate::setup::check_name;
+
+    #[test]
+    fn lines_test() {
+        let names = vec![String::from("Ann"), String::from("Player 2")];
+        let records = vec![
+            Record::Header { version: 2 },
+            Record::Game {
+                round: 1,
+                seed: Some(42),
+            },
+            Record::Game {
+                round: 4,
+                seed: None,
+            },
+            Record::Table {
+                rules: String::from("4D S17 DAS"),
+            },
+            Record::Seat {
+                seat: 0,
+                name: names[0].clone(),
+                player: PlayerType::Human,
+            },
+            Record::Seat {
+                seat: 1,
+                name: names[1].clone(),
+                player: PlayerType::Bot,
+            },
+            Record::Round {
+                number: 3,
+                position: 52,
+                size: 208,
+            },
+            Record::Shuffle,
+            Record::Shoe(vec![
+                Card {
+                    rank: Rank::Ace,
+                    suit: Suit::Spades,
+                },
+                Card {
+                    rank: Rank::Nine,
+                    suit: Suit::Diamonds,
+                },
+            ]),
+            Record::Bet {
+                seat: 1,
+                amount: 10,
+            },
+            Record::Dealt {
+                hand: Hand::Player {
+                    seat: 1,
+                    second: true,
+                },
+                card: Card {
+                    rank: Rank::Ten,
+                    suit: Suit::Hearts,
+                },
+            },
+            Record::Action {
+                hand: Hand::Dealer,
+                action: PlayerAction::Hit,
+            },
+            Record::Settle {
+                hand: Hand::Player {
+                    seat: 0,
+                    second: false,
+                },
+                outcome: Outcome::Loss,
+                net: -25,
+                value: 23,
+            },
+            Record::Banks(vec![275, 310]),
+        ];
+        let text: String = records
+            .iter()
+            .map(|record| record.to_line(&names) + "\n")
+            .collect();
+        assert!(text.contains("\nPlayer 2 (second hand): dealt 10H\n"));
+        assert!(text.contains("\nAnn: loses 25 with 23\n"));
+        assert!(text.contains("\n\nRound 3, shoe at card 52 of 208\nShuffle\nShoe: AS 9D\n"));
+        assert_eq!(records, parse_history(&text).unwrap());
+    }
+
+    #[test]
+    fn player_names() {
+        let hostile = [
+            "Dealer",
+            "banks",
+            "Seat 1",
+            "Round 2",
+            "Ann: bets 10",
+            "Bob (second hand)",
+        ];
+        for name in hostile.iter() {
+            assert!(check_name(name, &[]).is_err(), "{}", name);
+        }
+        assert!(check_name("Ann", &[String::from("Ann")]).is_err());
+        // names that look like the rest of a line, but are allowed
+        let names = vec![String::from("Dealer Joe"), String::from("Bob (bot), 2")];
+        for (seat, name) in names.iter().enumerate() {
+            check_name(name, &names[..seat]).unwrap();
+        }
+        let records = vec![
+            Record::Seat {
+                seat: 1,
+                name: names[1].clone(),
+                player: PlayerType::Bot,
+            },
+            Record::Bet { seat: 0, amount: 5 },
+            Record::Action {
+                hand: Hand::Player {
+                    seat: 1,
+                    second: true,
+                },
+                action: PlayerAction::Stand,
+            },
+        ];
+        let text: String = records
+            .iter()
+            .map(|record| record.to_line(&names) + "\n")
+            .collect();
+        let header = "Blackjack hand history, version 2\nSeat 1: Dealer Joe (human)\n";
+        let parsed = parse_history(&format!("{}{}", header, text)).unwrap();
+        assert_eq!(records, parsed[2..]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let header = "Blackjack hand history, version 2\nSeat 1: Ann (human)\n";
+        assert_eq!(
+            "line 3 : unknown player 'Bob'",
+            parse_history(&format!("{}Bob: bets 10\n", header)).unwrap_err()
+        );
+        assert_eq!(
+            "line 3 : Invalid card '1X'",
+            parse_history(&format!("{}Ann: dealt 1X\n", header)).unwrap_err()
+        );
+        assert!(parse_history(&format!("{}Ann: folds\n", header)).is_err());
+        assert!(parse_history(&format!("{}Dealer: bets 10\n", header)).is_err());
+        assert_eq!(
+            "line 1 : the hand history is of version 1, this game reads version 2",
+            parse_history("Blackjack hand history, version 1\n").unwrap_err()
+        );
+    }
+
+    #[test]
+    fn settlement_test() {
+        let card = |rank| Card {
+            rank,
+            suit: Suit::Spades,
+        };
+        let hands = vec![
+            (vec![card(Rank::Ace), card(Rank::King)], None),
+            (
+                vec![card(Rank::Eight), card(Rank::Ten)],
+                Some(vec![card(Rank::Eight), card(Rank::Two)]),
+            ),
+        ];
+        let results = [vec![(0, false)], vec![], vec![(1, true)], vec![(1, false)]];
+        let records = settlement(&results, &hands, &[10, 20]);
+        let nets: Vec<(i64, u32)> = records
+            .iter()
+            .map(|record| match record {
+                Record::Settle { net, value, .. } => (*net, *value),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(vec![(15, 21), (-20, 18), (0, 10)], nets);
+    }
+
+    #[test]
+    fn summarize_test() {
+        let text = "Blackjack hand history, version 2\n\
+                    Table: 4D S17 DAS\n\
+                    Seat 1: Ann (human)\n\
+                    Seat 2: Bob (bot)\n\n\
+                    Round 1, shoe at card 0 of 208\n\
+                    Ann: bets 25\n\
+                    Bob: bets 10\n\
+                    Bob: doubles\n\
+                    Ann: wins 25 with 19\n\
+                    Bob: loses 20 with 21\n\n\
+                    Round 2, shoe at card 9 of 208\n\
+                    Ann: bets 10\n\
+                    Ann: blackjack 15 with 21\n\
+                    Game from round 3\n\
+                    Table: 4D S17 DAS\n\
+                    Seat 1: Bob (bot)\n\n\
+                    Round 3, shoe at card 13 of 208\n\
+                    Bob: bets 5\n\
+                    Bob: pushes 0 with 18\n";
+        let (rounds, players) = summarize(&parse_history(text).unwrap());
+        assert_eq!(3, rounds);
+        assert_eq!(2, players.len());
+        assert_eq!([1, 1, 0, 0], players[0].outcomes);
+        assert_eq!((35, 40), (players[0].bet, players[0].net));
+        assert_eq!([0, 0, 1, 1], players[1].outcomes);
+        assert_eq!((15, -20), (players[1].bet, players[1].net));
+    }
+
+    #[test]
+    fn append_games() {
+        let path = std::env::temp_dir().join(format!("history-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        let _ = std::fs::remove_file(path);
+        let open = |names: &[&str]| {
+            let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+            HandHistory::open(path, (1, None), "4D S17 DAS", &names, &[PlayerType::Bot; 2])
+        };
+        open(&["Ann"]).unwrap().write_round().unwrap();
+        open(&["Ann", "Bob"]).unwrap().write_round().unwrap();
+        let records = parse_history(&std::fs::read_to_string(path).unwrap()).unwrap();
+        let count = |matches: fn(&Record) -> bool| records.iter().filter(|r| matches(r)).count();
+        assert_eq!(1, count(|r| matches!(r, Record::Header { .. })));
+        assert_eq!(2, count(|r| matches!(r, Record::Game { .. })));
+        assert_eq!(3, count(|r| matches!(r, Record::Seat { .. })));
+
+        std::fs::write(path, "Ann,Bob\n").unwrap();
+        assert!(open(&["Ann"]).is_err());
+        std::fs::write(path, "Blackjack hand history, version 0\n").unwrap();
+        assert!(open(&["Ann"]).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+}
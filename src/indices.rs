@@ -0,0 +1,534 @@
+use crate::card::Card;
+use crate::cli::{Args, RULE_OPTIONS};
+use crate::count::CountSystem;
+use crate::deviation::{Deviations, Index, IndexHand};
+use crate::player::PlayerAction;
+use crate::rules::Rules;
+use crate::shoe::Shoe;
+use crate::sim::play_round_from;
+use crate::strategy::{BasicStrategy, Situation, Strategy};
+
+use rand::Rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::cell::Cell;
+
+/// Indices beyond this true count are not reported : the
+/// decision practically never changes at the table.
+const MAX_INDEX: f64 = 10.0;
+
+/// A decision an index is generated for : a hand against a
+/// dealer upcard, and the action the index is about. Double,
+/// Split and Surrender compare the action with the best play
+/// without it, Stand compares standing with hitting.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Decision {
+    pub hand: IndexHand,
+    /// The value of the dealer upcard, 11 for an Ace.
+    pub up: u32,
+    pub action: PlayerAction,
+}
+
+/// All the decisions indices are generated for with the given
+/// rules : doubling hard 8 to 11 and soft 13 to 20, standing on
+/// hard 12 to 17, splitting all pairs, and, when the table offers
+/// them, surrendering hard 14 to 17 and taking insurance.
+pub fn decisions(rules: &Rules) -> Vec<Decision> {
+    let mut decisions = Vec::new();
+    for up in 2..=11 {
+        let mut add = |hand, action| decisions.push(Decision { hand, up, action });
+        for total in 8..=11 {
+            add(IndexHand::Hard(total), PlayerAction::Double);
+        }
+        for total in 12..=17 {
+            add(IndexHand::Hard(total), PlayerAction::Stand);
+        }
+        for total in 13..=20 {
+            add(IndexHand::Soft(total), PlayerAction::Double);
+        }
+        for value in 1..=10 {
+            add(IndexHand::Pair(value), PlayerAction::Split);
+        }
+        if rules.surrender {
+            for total in 14..=17 {
+                add(IndexHand::Hard(total), PlayerAction::Surrender);
+            }
+        }
+    }
+    if rules.insurance {
+        decisions.push(Decision {
+            hand: IndexHand::Insurance,
+            up: 11,
+            action: PlayerAction::Stand,
+        });
+    }
+    decisions
+}
+
+/// Plays a given action as the first decision of a
+/// round, and follows another strategy afterwards.
+struct FirstAction<'a> {
+    action: PlayerAction,
+    then: &'a dyn Strategy,
+    played: Cell<bool>,
+}
+
+impl Strategy for FirstAction<'_> {
+    fn decide(&self, situation: &Situation) -> PlayerAction {
+        if self.played.replace(true) {
+            self.then.decide(situation)
+        } else {
+            self.action
+        }
+    }
+}
+
+/// Picks the values of two cards making the hand of a decision.
+/// Hard totals are made of two different cards, chosen at random.
+fn sample_hand(hand: IndexHand, rng: &mut ChaCha8Rng) -> [u32; 2] {
+    match hand {
+        IndexHand::Hard(total) => {
+            let smallest = (2..=10)
+                .filter(|&card| 2 * card < total && total - card <= 10)
+                .collect::<Vec<u32>>();
+            let card = smallest[rng.gen_range(0..smallest.len())];
+            [card, total - card]
+        }
+        IndexHand::Soft(total) => [1, total - 11],
+        IndexHand::Pair(value) => [value, value],
+        IndexHand::Insurance => [rng.gen_range(1..=10), rng.gen_range(1..=10)],
+    }
+}
+
+/// The two actions compared by a decision for the given hand : the
+/// action of the decision, and the best play of basic strategy
+/// without it. For a decision between hitting and standing, the
+/// first action is the one basic strategy does not play.
+pub fn compared_actions(
+    decision: &Decision,
+    hand: &[Card],
+    dealer_card: &Card,
+    rules: &Rules,
+) -> (PlayerAction, PlayerAction) {
+    let without = BasicStrategy.decide(&Situation {
+        hand,
+        dealer_card,
+        can_double: decision.action != PlayerAction::Double,
+        can_split: false,
+        can_surrender: false,
+        dealt: &[0; 10],
+        decks_remaining: rules.num_packs as f64,
+        rules,
+    });
+    match (decision.action, without) {
+        (PlayerAction::Stand, PlayerAction::Stand) => (PlayerAction::Hit, PlayerAction::Stand),
+        (PlayerAction::Stand, _) => (PlayerAction::Stand, PlayerAction::Hit),
+        (action, _) => (action, without),
+    }
+}
+
+/// A least squares fit of a line through samples.
+#[derive(Default)]
+struct LinearFit {
+    n: f64,
+    sx: f64,
+    sy: f64,
+    sxx: f64,
+    sxy: f64,
+}
+
+impl LinearFit {
+    fn add(&mut self, x: f64, y: f64) {
+        self.n += 1.0;
+        self.sx += x;
+        self.sy += y;
+        self.sxx += x * x;
+        self.sxy += x * y;
+    }
+
+    /// The intercept and slope of the line, if there
+    /// were enough different samples to fit it.
+    fn line(&self) -> Option<(f64, f64)> {
+        let denominator = self.n * self.sxx - self.sx * self.sx;
+        if denominator <= 0.0 {
+            return None;
+        }
+        let slope = (self.n * self.sxy - self.sx * self.sy) / denominator;
+        Some(((self.sy - slope * self.sx) / self.n, slope))
+    }
+}
+
+/// Finds the true count at which both actions of a decision have
+/// the same EV, for the given counting system and rules.
+///
+/// Each sample deals a random part of a freshly shuffled shoe, takes
+/// the cards of the decision out of it, and plays both actions from
+/// the same shoe, following basic strategy afterwards. The difference
+/// of their results is fitted as a linear function of the true count,
+/// whose zero is the index. Returns None when the index is beyond
+/// [MAX_INDEX] in either direction.
+///
+/// For a decision between hitting and standing, the action of
+/// the index is the one basic strategy does not play.
+pub fn generate_index(
+    decision: &Decision,
+    count: &CountSystem,
+    rules: &Rules,
+    samples: u64,
+    seed: u64,
+) -> Option<Index> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut shoe = Shoe::new(rules, seed);
+    let size = rules.num_packs * 52;
+    let max_depth = (size as f64 * rules.penetration) as usize;
+    let rules = Rules {
+        insurance: false,
+        ..rules.clone()
+    };
+    let dealer_value = if decision.up == 11 { 1 } else { decision.up };
+    let mut fit = LinearFit::default();
+    let mut actions = None;
+    for _ in 0..samples {
+        shoe.shuffle();
+        for _ in 0..rng.gen_range(0..=max_depth) {
+            shoe.draw();
+        }
+        let values = sample_hand(decision.hand, &mut rng);
+        let hand = match (shoe.take(values[0]), shoe.take(values[1])) {
+            (Some(first), Some(second)) => vec![first, second],
+            _ => continue,
+        };
+        let dealer_card = match shoe.take(dealer_value) {
+            Some(card) => card,
+            None => continue,
+        };
+        let true_count = count.true_count(&shoe);
+        if decision.hand == IndexHand::Insurance {
+            // the next card of the dealer is as likely to be worth
+            // 10 as any card left in the shoe
+            let dealt = shoe.dealt();
+            let tens = (16 * rules.num_packs) as f64 - dealt[9] as f64;
+            let remaining = size as f64 - dealt.iter().sum::<u32>() as f64;
+            fit.add(true_count, 1.5 * tens / remaining - 0.5);
+            actions = Some((PlayerAction::Stand, PlayerAction::Stand));
+            continue;
+        }
+        let (with, without) =
+            *actions.get_or_insert_with(|| compared_actions(decision, &hand, &dealer_card, &rules));
+        let play = |action| {
+            let strategy = FirstAction {
+                action,
+                then: &BasicStrategy,
+                played: Cell::new(false),
+            };
+            play_round_from(
+                &mut shoe.clone(),
+                &rules,
+                &strategy,
+                hand.clone(),
+                dealer_card,
+            )
+        };
+        fit.add(true_count, play(with) - play(without));
+    }
+
+    let (action, _) = actions?;
+    let (intercept, slope) = fit.line()?;
+    let index = -intercept / slope;
+    if !index.is_finite() || index.abs() > MAX_INDEX {
+        return None;
+    }
+    // the first action gets better than the second one as the
+    // count rises when the slope is positive
+    Some(Index {
+        hand: decision.hand,
+        up: decision.up,
+        s17: index,
+        h17: index,
+        action,
+        below: slope < 0.0,
+    })
+}
+
+/// Generates the indices of all the [decisions] of the given rules.
+pub fn generate_table(count: &CountSystem, rules: &Rules, samples: u64, seed: u64) -> Vec<Index> {
+    decisions(rules)
+        .iter()
+        .enumerate()
+        .filter_map(|(i, decision)| {
+            generate_index(decision, count, rules, samples, seed.wrapping_add(i as u64))
+        })
+        .collect()
+}
+
+/// Writes an index table as text, starting with its counting system.
+///
+/// Each line holds the hand (`hard 16`, `soft 18`, `pair 8`, or
+/// `insurance`), the dealer upcard, the action and the index, as in
+/// `hard 16 10 stand >= 0.4` or `hard 13 2 hit < -0.6`. Lines
+/// starting with `#` are comments.
+pub fn write_table(count: &CountSystem, rules: &Rules, indices: &[Index]) -> String {
+    let mut text = format!("# {} indices for {}\n", count.name, rules);
+    let tags: Vec<String> = count.tags.iter().map(|tag| tag.to_string()).collect();
+    text += &format!("count {} {}\n", count.name, tags.join(" "));
+    for index in indices {
+        let hand = match index.hand {
+            IndexHand::Hard(total) => format!("hard {}", total),
+            IndexHand::Soft(total) => format!("soft {}", total),
+            IndexHand::Pair(1) => String::from("pair A"),
+            IndexHand::Pair(value) => format!("pair {}", value),
+            IndexHand::Insurance => String::from("insurance"),
+        };
+        let action = match (index.hand, index.action) {
+            (IndexHand::Insurance, _) => "insure",
+            (_, PlayerAction::Hit) => "hit",
+            (_, PlayerAction::Stand) => "stand",
+            (_, PlayerAction::Double) => "double",
+            (_, PlayerAction::Split) => "split",
+            (_, PlayerAction::Surrender) => "surrender",
+        };
+        let up = match index.up {
+            11 => String::from("A"),
+            up => up.to_string(),
+        };
+        let threshold = if rules.dealer_hits_soft_17 {
+            index.h17
+        } else {
+            index.s17
+        };
+        text += &format!(
+            "{} {} {} {} {:.1}\n",
+            hand,
+            up,
+            action,
+            if index.below { "<" } else { ">=" },
+            threshold
+        );
+    }
+    text
+}
+
+/// The value of a card written in an index table, 11 for an Ace.
+fn card_value(text: &str) -> Option<u32> {
+    match text {
+        "A" => Some(11),
+        _ => text.parse().ok().filter(|value| (2..=10).contains(value)),
+    }
+}
+
+/// Parses an index line of a table written by [write_table].
+/// The index is used whether the dealer hits soft 17 or not.
+fn parse_index(words: &[&str]) -> Option<Index> {
+    let (hand, rest) = match words {
+        ["insurance", rest @ ..] => (IndexHand::Insurance, rest),
+        [kind, value, rest @ ..] => {
+            let hand = match *kind {
+                "hard" => IndexHand::Hard(value.parse().ok()?),
+                "soft" => IndexHand::Soft(value.parse().ok()?),
+                "pair" => match card_value(value)? {
+                    11 => IndexHand::Pair(1),
+                    value => IndexHand::Pair(value),
+                },
+                _ => return None,
+            };
+            (hand, rest)
+        }
+        _ => return None,
+    };
+    match rest {
+        [up, action, comparison, index] => {
+            let action = match (hand, *action) {
+                (IndexHand::Insurance, "insure") => PlayerAction::Stand,
+                (IndexHand::Insurance, _) => return None,
+                (_, "hit") => PlayerAction::Hit,
+                (_, "stand") => PlayerAction::Stand,
+                (_, "double") => PlayerAction::Double,
+                (_, "split") => PlayerAction::Split,
+                (_, "surrender") => PlayerAction::Surrender,
+                _ => return None,
+            };
+            let below = match *comparison {
+                ">=" => false,
+                "<" => true,
+                _ => return None,
+            };
+            let index = index.parse().ok()?;
+            Some(Index {
+                hand,
+                up: card_value(up)?,
+                s17: index,
+                h17: index,
+                action,
+                below,
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Parses an index table written by [write_table].
+pub fn parse_table(text: &str) -> Result<(CountSystem, Vec<Index>), String> {
+    let mut count = None;
+    let mut indices = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => {}
+            ["count", name, tags @ ..] => {
+                let tags = CountSystem::parse(&tags.join(","))
+                    .map_err(|e| format!("Line {} : {}", number + 1, e))?
+                    .tags;
+                count = Some(CountSystem {
+                    name: name.to_string(),
+                    tags,
+                });
+            }
+            _ => indices.push(
+                parse_index(&words)
+                    .ok_or_else(|| format!("Line {} : invalid index '{}'", number + 1, line))?,
+            ),
+        }
+    }
+    let count = count.ok_or_else(|| String::from("The index table has no count line"))?;
+    Ok((count, indices))
+}
+
+/// Loads an index table from a file, as a bot playing basic
+/// strategy with the deviations of the table.
+pub fn load_table(path: &str) -> Result<Deviations, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read index table {} : {}", path, e))?;
+    let (count, indices) = parse_table(&text).map_err(|e| format!("{} : {}", path, e))?;
+    Ok(Deviations {
+        base: Box::new(BasicStrategy),
+        indices,
+        count,
+    })
+}
+
+/// Runs the `indices` subcommand, which generates the index
+/// table of a counting system for the rules of the table.
+pub fn run(args: &Args) -> Result<(), String> {
+    args.check_options(&[&["count", "output", "samples", "seed"], RULE_OPTIONS])?;
+    let rules = args.rules()?;
+    let count = CountSystem::parse(args.get("count").unwrap_or("hi-lo"))?;
+    let samples = args.parse_or("samples", 20_000)?;
+    let seed = args.parse_or("seed", 0)?;
+    eprintln!(
+        "Simulating {} decisions with {} samples each...",
+        decisions(&rules).len(),
+        samples
+    );
+    let indices = generate_table(&count, &rules, samples, seed);
+    let table = write_table(&count, &rules, &indices);
+    match args.get("output") {
+        Some(path) => {
+            std::fs::write(path, table).map_err(|e| format!("Cannot write {} : {}", path, e))?;
+            println!("Wrote {} indices to {}", indices.len(), path);
+        }
+        None => print!("{}", table),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::indices::*;
+
+    fn decision(hand: IndexHand, up: u32, action: PlayerAction) -> Decision {
+        Decision { hand, up, action }
+    }
+
+    #[test]
+    fn decisions_test() {
+        assert_eq!(280, decisions(&Rules::default()).len());
+        let rules = Rules {
+            surrender: true,
+            insurance: true,
+            ..Rules::default()
+        };
+        assert_eq!(321, decisions(&rules).len());
+    }
+
+    #[test]
+    fn insurance_index() {
+        let insurance = decision(IndexHand::Insurance, 11, PlayerAction::Stand);
+        let index = generate_index(
+            &insurance,
+            &CountSystem::hi_lo(),
+            &Rules::default(),
+            5000,
+            1,
+        )
+        .unwrap();
+        assert!(!index.below);
+        assert!((2.0..4.0).contains(&index.s17), "{}", index.s17);
+    }
+
+    #[test]
+    fn sixteen_versus_ten_index() {
+        let sixteen = decision(IndexHand::Hard(16), 10, PlayerAction::Stand);
+        let index = generate_index(
+            &sixteen,
+            &CountSystem::hi_lo(),
+            &Rules::default(),
+            20_000,
+            1,
+        )
+        .unwrap();
+        assert_eq!(PlayerAction::Stand, index.action);
+        assert!(!index.below);
+        assert!((-2.0..2.0).contains(&index.s17), "{}", index.s17);
+    }
+
+    #[test]
+    fn table_round_trip() {
+        let rules = Rules::default();
+        let count = CountSystem::parse("zen").unwrap();
+        let indices = [
+            Index {
+                hand: IndexHand::Hard(13),
+                up: 2,
+                s17: -0.5,
+                h17: -0.5,
+                action: PlayerAction::Hit,
+                below: true,
+            },
+            Index {
+                hand: IndexHand::Pair(1),
+                up: 11,
+                s17: 2.5,
+                h17: 2.5,
+                action: PlayerAction::Split,
+                below: false,
+            },
+            Index {
+                hand: IndexHand::Insurance,
+                up: 11,
+                s17: 3.0,
+                h17: 3.0,
+                action: PlayerAction::Stand,
+                below: false,
+            },
+        ];
+        let text = write_table(&count, &rules, &indices);
+        assert!(text.contains("hard 13 2 hit < -0.5\n"));
+        assert!(text.contains("pair A A split >= 2.5\n"));
+        assert_eq!(Ok((count, indices.to_vec())), parse_table(&text));
+    }
+
+    #[test]
+    fn invalid_table() {
+        let count = "count Hi-Lo -1 1 1 1 1 1 0 0 0 -1\n";
+        assert!(parse_table("hard 16 10 stand >= 0\n").is_err());
+        assert_eq!(
+            Err(String::from(
+                "Line 3 : invalid index 'hard 16 11 stand => 0'"
+            )),
+            parse_table(&format!("{}\nhard 16 11 stand => 0", count))
+        );
+        assert!(parse_table(&format!("{}soft 18 A insure >= 0", count)).is_err());
+        assert!(parse_table("count Hi-Lo 1 1\n").is_err());
+    }
+}
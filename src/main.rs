@@ -1,71 +1,424 @@
 //! A CLI blackjack game.
 
+mod api;
+mod art;
+mod betsim;
+mod betting;
 mod card;
+mod chart;
+mod cli;
+mod command;
+mod compare;
+mod config;
+mod count;
+mod deviation;
 mod display;
+mod eor;
+mod external;
+mod graph;
+mod history;
+mod indices;
 mod math;
 mod player;
+mod progression;
+mod render;
+mod replay;
+mod resim;
 mod round;
+mod ruin;
+mod rules;
+mod save;
+mod server;
+mod setup;
+mod shoe;
+mod sim;
+mod stats;
+mod strategy;
+mod tournament;
+mod tui;
 mod utils;
 
+use art::{set_card_style, CardStyle, CARD_OPTIONS};
 use card::*;
+use cli::{Args, LIMIT_OPTIONS, RULE_OPTIONS};
+use command::{Quit, Session};
+use config::TableConfig;
 use display::*;
-use math::*;
+use external::{ExternalPlayer, Message};
+use history::{HandHistory, Record, DEFAULT_HISTORY_FILE};
+use player::PlayerType;
 use round::*;
+use save::{GameState, RoundStart, SavedGame};
+use server::{RemoteSeats, Server};
+use setup::GameSetup;
+use stats::record_round;
+use strategy::strategy_from_name;
+use tui::{TableView, Tui};
 use utils::*;
 
+use std::io::IsTerminal;
+use std::net::TcpListener;
+use std::time::Duration;
+
 fn main() {
-    print!("\n\n");
+    let args = Args::parse(std::env::args().skip(1));
+    let result = CardStyle::from_args(&args).and_then(|style| {
+        set_card_style(style);
+        match args.command.as_deref() {
+            None => play_game(&args),
+            Some("compare") => compare::run(&args),
+            Some("ruin") => ruin::run(&args),
+            Some("betsim") => betsim::run(&args),
+            Some("indices") => indices::run(&args),
+            Some("eor") => eor::run(&args),
+            Some("chart") => render::run(&args),
+            Some("tournament") => tournament::run(&args),
+            Some("history") => history::run(&args),
+            Some("replay") => replay::run(&args),
+            Some("resimulate") => resim::run(&args),
+            Some("serve") => play_game(&args),
+            Some("api") => api::run(&args),
+            Some(other) => Err(format!("Unknown command '{}'", other)),
+        }
+    });
+    if let Err(message) = result {
+        eprintln!("{}", message);
+        std::process::exit(1);
+    }
+}
 
-    let mut card_deck: Vec<Card> = Vec::new(); //will be filled with a few card packs
-    let player_types = ask_for_player_types();
-    let mut player_hands: Vec<(Vec<Card>, Option<Vec<Card>>)> = Vec::new();
+/// Plays an interactive game in the terminal until one of the
+/// players runs out of money, the `--rounds` are played or the
+/// humans `:quit`, then shows the statistics of the session and
+/// the chart of the banks.
+///
+/// The players and the table are set up by [GameSetup::from_args],
+/// from the command line and the `--table` file if there is one,
+/// unless `--resume` gives a game saved by `:save` to play on.
+/// Every game writes its seed and its rounds to a hand history,
+/// as described in [history] : `--hand-history FILE`, or
+/// [DEFAULT_HISTORY_FILE], unless `--no-history` is given. The
+/// game goes on without one when the default cannot be written.
+/// External players are programs started for their seat, which
+/// play over their input and output as described in [external],
+/// and must answer within `--external-timeout` seconds.
+/// `serve` plays the game with the players of the `remote` seats
+/// joining over TCP on `--bind` and `--port`, as described in
+/// [server], who must answer within `--remote-timeout` seconds.
+/// With `--tui`, the game is played on a full-screen interface,
+/// unless the input or the output is not a terminal.
+/// Bots bet according to their betting policy, within the limits
+/// of the table, and play with their strategy.
+fn play_game(args: &Args) -> Result<(), String> {
+    let serve_options: &[&str] = match args.command {
+        Some(_) => &["port", "bind", "remote-timeout"],
+        None => &[],
+    };
+    args.check_options(&[
+        &[
+            "table",
+            "players",
+            "names",
+            "bankroll",
+            "rounds",
+            "seed",
+            "bot-strategy",
+            "bot-betting",
+            "tui",
+            "resume",
+            "hand-history",
+            "no-history",
+            "external-timeout",
+        ],
+        RULE_OPTIONS,
+        LIMIT_OPTIONS,
+        CARD_OPTIONS,
+        serve_options,
+    ])?;
+    print!("\n\n");
+    let (mut setup, mut state) = match args.get("resume") {
+        Some(path) => {
+            let saved = SavedGame::load(path)?;
+            let (setup, state) = saved.restore()?;
+            saved.restore_shuffler()?;
+            println!("Resuming the game at round {}.\n", state.history.len());
+            (setup, state)
+        }
+        None => {
+            let config = match args.get("table") {
+                Some(path) => TableConfig::load(path)?,
+                None => TableConfig::default(),
+            };
+            let mut setup = GameSetup::from_args(args, &config)?;
+            // games without a seed draw one, so that it can be recorded
+            let seed = setup.seed.unwrap_or_else(rand::random);
+            seed_shuffles(seed);
+            setup.seed = Some(seed);
+            let state = GameState::new(&setup);
+            (setup, state)
+        }
+    };
+    if let Some(name) = &setup.table_name {
+        println!("Welcome to the {} table ({}).\n", name, setup.rules);
+    }
+    let remote_seats: Vec<usize> = (0..setup.names.len())
+        .filter(|&seat| setup.player_types[seat] == PlayerType::Remote)
+        .collect();
+    let server = match (args.command.is_some(), remote_seats.is_empty()) {
+        (true, false) => {
+            let port = args.parse_or("port", server::DEFAULT_PORT)?;
+            let bind = args.get("bind").unwrap_or(server::DEFAULT_BIND);
+            let listener = TcpListener::bind((bind, port))
+                .map_err(|e| format!("Cannot listen on {} port {} : {}", bind, port, e))?;
+            println!(
+                "Waiting for {} remote players on {} port {}.",
+                remote_seats.len(),
+                bind,
+                port
+            );
+            let timeout = args.parse_or("remote-timeout", server::DEFAULT_REMOTE_TIMEOUT)?;
+            if !timeout.is_finite() || timeout <= 0.0 {
+                return Err(String::from(
+                    "The timeout of remote players must be positive",
+                ));
+            }
+            let welcome = format!(
+                "Welcome to the {} table ({}).",
+                setup.table_name.as_deref().unwrap_or("blackjack"),
+                setup.rules
+            );
+            Some(Server::open(
+                &listener,
+                &mut setup.names,
+                &remote_seats,
+                &welcome,
+                Duration::from_secs_f64(timeout),
+            )?)
+        }
+        (true, true) => {
+            return Err(String::from(
+                "A served table needs remote seats, as in --players remote,remote,bot",
+            ))
+        }
+        (false, false) => {
+            return Err(String::from(
+                "Remote players join a table started with the serve command",
+            ))
+        }
+        (false, true) => None,
+    };
+    let mut log = if args.flag("no-history")? {
+        None
+    } else {
+        let opened = HandHistory::open(
+            args.get("hand-history").unwrap_or(DEFAULT_HISTORY_FILE),
+            (state.history.len() as u32, setup.seed),
+            &setup.rules.to_string(),
+            &setup.names,
+            &setup.player_types,
+        );
+        match opened {
+            Ok(log) => Some(log),
+            // the default hand history is not worth stopping the game for
+            Err(message) if args.get("hand-history").is_none() => {
+                eprintln!("{}, playing without a hand history.", message);
+                None
+            }
+            Err(message) => return Err(message),
+        }
+    };
+    // the cards left in the shoe, in the order they are dealt
+    let shoe = |pack: &[Card]| Record::Shoe(pack.iter().rev().copied().collect());
+    if let Some(log) = &log {
+        log.record(shoe(&state.pack));
+    }
+    let timeout = args.parse_or("external-timeout", external::DEFAULT_TIMEOUT)?;
+    if !timeout.is_finite() || timeout <= 0.0 {
+        return Err(String::from(
+            "The timeout of external players must be positive",
+        ));
+    }
+    let externals = setup
+        .commands
+        .iter()
+        .enumerate()
+        .map(|(seat, command)| match command {
+            Some(command) => {
+                let player = ExternalPlayer::start(command, &setup.names[seat], timeout)?;
+                player.tell(&Message::Hello {
+                    version: external::PROTOCOL_VERSION,
+                    seat,
+                    name: &setup.names[seat],
+                    rules: &setup.rules,
+                    limits: &setup.limits,
+                    bank: state.bank[seat],
+                });
+                Ok(Some(player))
+            }
+            None => Ok(None),
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let tui = if !args.flag("tui")? {
+        None
+    } else if std::io::stdin().is_terminal() && std::io::stdout().is_terminal() {
+        Some(Tui::enter()?)
+    } else {
+        eprintln!("The full-screen interface needs a terminal, playing line by line.");
+        None
+    };
+    let player_types = &setup.player_types;
+    let names = &setup.names;
     let mut dealer_hand: Vec<Card> = Vec::new();
-
-    let mut bank = Vec::new(); //contains the reserves of each player
     let mut bets = Vec::new(); //contains the current bets the players make
-    init_game(
-        &mut player_hands,
-        &mut card_deck,
-        &mut bank,
-        player_types.len() - 1,
-    );
+    let strategies = setup
+        .strategies
+        .iter()
+        .map(|name| strategy_from_name(name))
+        .collect::<Result<Vec<_>, _>>()?;
 
-    while is_playable(&bank) {
-        play_round(
-            &mut player_hands,
+    while is_playable(&state.bank)
+        && setup
+            .rounds
+            .map_or(true, |rounds| (state.history.len() as u32 - 1) < rounds)
+    {
+        let saved = RoundStart::new(&setup, &state);
+        if let Some(tui) = &tui {
+            tui.set_title(format!(
+                "{} ({}) : round {}",
+                setup.table_name.as_deref().unwrap_or("Blackjack"),
+                setup.rules,
+                state.history.len()
+            ));
+        }
+        let table = Table {
+            limits: &setup.limits,
+            rules: &setup.rules,
+            names,
+            tui: tui.as_ref(),
+            last_bets: &state.last_bets,
+            stats: &state.stats,
+            history: &state.history,
+            saved: Some(&saved),
+            log: log.as_ref(),
+            externals: &externals,
+            server: server.as_ref().map(|server| server as &dyn RemoteSeats),
+        };
+        start_round(&mut state.pack, state.history.len() as u32, &table);
+        let turns = match play_round(
+            &mut state.player_hands,
             &mut dealer_hand,
-            &mut card_deck,
-            &player_types,
-            &mut bets,
-            &mut bank,
-        );
-
-        let scores = compute_scores(&player_hands, &dealer_hand);
-        display_hands_and_scores(&scores, &player_hands, &dealer_hand);
-
-        println!();
-        let bj_index = compute_blackjack_index(&player_hands, &dealer_hand);
-        let [three_two_index, winner_index, equal_index, loser_index] =
-            compute_result(scores, bj_index);
-
-        update_bank(
-            &three_two_index,
-            &winner_index,
-            &equal_index,
-            &mut bank,
+            &mut state.pack,
+            player_types,
+            (&mut bets, &mut state.bank),
+            (&mut state.bet_policies, &strategies),
+            &table,
+        ) {
+            Ok(turns) => turns,
+            // the hand history keeps what was played of the round
+            Err(Quit) => {
+                if let Some(log) = &mut log {
+                    log.write_round()?;
+                }
+                break;
+            }
+        };
+        let Settlement {
+            scores,
+            results,
+            nets,
+            last_bets,
+        } = settle_round(
+            &state.player_hands,
+            &dealer_hand,
             &bets,
+            &turns,
+            &mut state.bank,
+            &mut state.bet_policies,
+            &table,
+        );
+        state.last_bets = last_bets;
+        state.history.push(state.bank.clone());
+        if let Some(log) = &mut log {
+            log.write_round()?;
+        }
+        if tui.is_none() {
+            display_hands_and_scores(&scores, &state.player_hands, &dealer_hand, names);
+            println!();
+        }
+        let [three_two_index, winner_index, equal_index, loser_index] = &results;
+        record_round(
+            &mut state.stats,
+            &state.player_hands,
+            &results,
+            &turns.doubled,
+            &nets,
         );
+        for (seat, player) in externals.iter().enumerate() {
+            if let Some(player) = player {
+                player.tell(&Message::Result {
+                    round: state.history.len() - 1,
+                    dealer: external::cards(&dealer_hand),
+                    table: external::table_view(names, &state.player_hands, &bets),
+                    net: nets[seat],
+                    bank: state.bank[seat],
+                });
+            }
+        }
 
-        display_results(&three_two_index, &winner_index, &equal_index, &loser_index);
-        display_bank(&bank);
+        let go_on = match &tui {
+            Some(tui) => tui.end_of_round(
+                &TableView {
+                    names,
+                    hands: &state.player_hands,
+                    dealer_hand: &dealer_hand,
+                    bets: &bets,
+                    bank: &state.bank,
+                    turn: None,
+                    nets: Some(&nets),
+                },
+                &stats_text(&state.stats, names),
+                &bank_chart(&state.history, names, graph::terminal_width()),
+            ),
+            None => {
+                display_results(
+                    three_two_index,
+                    winner_index,
+                    equal_index,
+                    loser_index,
+                    names,
+                );
+                display_bank(&state.bank);
+                println!();
+                // served tables go on without waiting for the terminal
+                server.is_some()
+                    || wait_for_enter_or_command(&Session {
+                        names,
+                        rules: &setup.rules,
+                        limits: &setup.limits,
+                        stats: &state.stats,
+                        history: &state.history,
+                        bank: &state.bank,
+                        pack: &state.pack,
+                        saved: Some(&RoundStart::new(&setup, &state)),
+                    })
+                    .is_ok()
+            }
+        };
 
-        for mut hand in player_hands.iter_mut() {
+        for hand in state.player_hands.iter_mut() {
             hand.0.clear();
             hand.1 = None
         }
         dealer_hand.clear();
         bets.clear();
-        println!();
-        wait_for_enter();
+        if !go_on {
+            break;
+        }
+    }
+    drop(tui);
+    if let Some(server) = &server {
+        server.broadcast("The game is over.");
     }
+    display_session_end(&state.stats, &state.history, names);
+    Ok(())
 }
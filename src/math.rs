@@ -3,6 +3,29 @@ use crate::card::*;
 /// The number of packs to use when shuffling the cards.
 pub const NUM_PACKS: usize = 4;
 
+/// Computes the value of a single card [Rank],
+/// counting Aces as 1 and face cards as 10.
+pub fn rank_value(rank: &Rank) -> u32 {
+    match rank {
+        Rank::Ace => 1,
+        Rank::Two => 2,
+        Rank::Three => 3,
+        Rank::Four => 4,
+        Rank::Five => 5,
+        Rank::Six => 6,
+        Rank::Seven => 7,
+        Rank::Eight => 8,
+        Rank::Nine => 9,
+        _ => 10,
+    }
+}
+
+/// The rank of a card of the given value, 1 for Aces. Cards
+/// worth 10 are all represented by [Rank::Ten].
+pub fn rank_of_value(value: u32) -> Rank {
+    Rank::from_int((value + 11) % 13)
+}
+
 /// Computes the total value of a playing hand.
 ///
 /// The result must be less than or equal to 21
@@ -14,33 +37,22 @@ pub const NUM_PACKS: usize = 4;
 ///
 /// For example, hand_value(&vec!(Queen of Hearts, 4 of
 /// spades, Ace of Hearts)) == 15
-fn hand_value(hand: &[Card]) -> u32 {
-    let mut value = 0;
-    let mut found_ace = false;
-
-    for elem in hand.iter() {
-        value += match &elem.rank {
-            Rank::Ace => {
-                found_ace = true;
-                1
-            }
-            Rank::Two => 2,
-            Rank::Three => 3,
-            Rank::Four => 4,
-            Rank::Five => 5,
-            Rank::Six => 6,
-            Rank::Seven => 7,
-            Rank::Eight => 8,
-            Rank::Nine => 9,
-            _ => 10,
-        }
-    }
+pub fn hand_value(hand: &[Card]) -> u32 {
+    let value: u32 = hand.iter().map(|card| rank_value(&card.rank)).sum();
 
-    if found_ace && value < 12 {
-        value += 10
+    if is_soft(hand) {
+        value + 10
+    } else {
+        value
     }
+}
 
-    value
+/// This method returns true whenever its argument
+/// is a "soft" hand, that is a hand in which an Ace
+/// is counted as 11 by [hand_value].
+pub fn is_soft(hand: &[Card]) -> bool {
+    let value: u32 = hand.iter().map(|card| rank_value(&card.rank)).sum();
+    value < 12 && hand.iter().any(|card| card.rank == Rank::Ace)
 }
 
 /// This method returns true whenever its argument
@@ -56,7 +68,7 @@ pub fn is_blackjack(hand: &[Card]) -> bool {
 /// and both his cards have the same rank.
 pub fn is_splittable(hand: &[Card]) -> bool {
     hand.len() == 2 && {
-        let hand_0 = hand.get(0).unwrap();
+        let hand_0 = hand.first().unwrap();
         let hand_1 = hand.get(1).unwrap();
         hand_0.rank == hand_1.rank
     }
@@ -73,7 +85,7 @@ pub fn compute_scores(
 ) -> Vec<(u32, Option<u32>)> {
     let mut scores = Vec::with_capacity(player_hands.len() + 1);
     for hand in player_hands {
-        scores.push((hand_value(&hand.0), hand.1.as_ref().map(|v| hand_value(&v))));
+        scores.push((hand_value(&hand.0), hand.1.as_ref().map(|v| hand_value(v))));
     }
     scores.push((hand_value(dealer_hand), None));
     scores
@@ -160,7 +172,7 @@ pub fn compute_blackjack_index(
     for hand in player_hands {
         bj.push((
             is_blackjack(&hand.0),
-            hand.1.as_ref().map(|e| is_blackjack(&e)),
+            hand.1.as_ref().map(|e| is_blackjack(e)),
         ));
     }
     bj.push((is_blackjack(dealer_hand), None));
@@ -168,13 +180,12 @@ pub fn compute_blackjack_index(
 }
 
 #[cfg(test)]
-
 mod tests {
     use crate::math::*;
 
     #[test]
     fn black_jack_test() {
-        assert!(is_blackjack(&vec!(
+        assert!(is_blackjack(&[
             Card {
                 suit: Suit::Diamonds,
                 rank: Rank::King
@@ -183,8 +194,8 @@ mod tests {
                 suit: Suit::Clubs,
                 rank: Rank::Ace
             }
-        )));
-        assert!(!is_blackjack(&vec!(
+        ]));
+        assert!(!is_blackjack(&[
             Card {
                 suit: Suit::Diamonds,
                 rank: Rank::Eight
@@ -193,8 +204,8 @@ mod tests {
                 suit: Suit::Clubs,
                 rank: Rank::Ace
             }
-        )));
-        assert!(!is_blackjack(&vec!(
+        ]));
+        assert!(!is_blackjack(&[
             Card {
                 suit: Suit::Diamonds,
                 rank: Rank::King
@@ -207,12 +218,20 @@ mod tests {
                 suit: Suit::Clubs,
                 rank: Rank::Ten
             }
-        )))
+        ]))
+    }
+
+    #[test]
+    fn rank_of_value_test() {
+        for value in 1..=10 {
+            assert_eq!(value, rank_value(&rank_of_value(value)));
+        }
+        assert_eq!(Rank::Ace, rank_of_value(1));
     }
 
     #[test]
     fn is_splittable_test() {
-        assert!(is_splittable(&vec!(
+        assert!(is_splittable(&[
             Card {
                 suit: Suit::Diamonds,
                 rank: Rank::King
@@ -221,8 +240,8 @@ mod tests {
                 suit: Suit::Clubs,
                 rank: Rank::King
             }
-        )));
-        assert!(!is_splittable(&vec!(
+        ]));
+        assert!(!is_splittable(&[
             Card {
                 suit: Suit::Diamonds,
                 rank: Rank::Eight
@@ -231,8 +250,8 @@ mod tests {
                 suit: Suit::Clubs,
                 rank: Rank::Ace
             }
-        )));
-        assert!(!is_splittable(&vec!(
+        ]));
+        assert!(!is_splittable(&[
             Card {
                 suit: Suit::Diamonds,
                 rank: Rank::King
@@ -245,7 +264,7 @@ mod tests {
                 suit: Suit::Clubs,
                 rank: Rank::King
             }
-        )))
+        ]))
     }
 
     #[test]
@@ -265,14 +284,11 @@ mod tests {
 
         assert_eq!(
             vec![(0, None); 3],
-            compute_scores(&vec!((vec!(), None), (vec!(), None)), &vec!())
+            compute_scores(&[(vec!(), None), (vec!(), None)], &[])
         );
         assert_eq!(
             vec![(2, None); 3],
-            compute_scores(
-                &vec!((vec!(card1), None), (vec!(card2), None)),
-                &vec!(card3)
-            )
+            compute_scores(&[(vec!(card1), None), (vec!(card2), None)], &[card3])
         );
     }
 
@@ -1,103 +1,309 @@
+use crate::betting::TableLimits;
 use crate::card::Card;
+use crate::command::{read_round_answer, Quit, Session};
+use crate::count::dealt_from_remaining;
 use crate::display::display_hands_and_scores;
-use crate::display::read_num;
-use std::io::stdin;
+use crate::rules::Rules;
+use crate::sim::dealer_should_hit;
+use crate::strategy::{Situation, Strategy};
+
+use serde::{Deserialize, Serialize};
 
 /// The enum for the player types.
-/// A player can be human or a bot.
+/// A player can be human or a bot, an external program
+/// playing through [crate::external], or a remote player
+/// joining the table through [crate::server].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum PlayerType {
     Bot,
     Human,
+    External,
+    Remote,
     Dealer,
 }
 
+impl std::fmt::Display for PlayerType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            PlayerType::Bot => "bot",
+            PlayerType::Human => "human",
+            PlayerType::External => "external",
+            PlayerType::Remote => "remote",
+            PlayerType::Dealer => "dealer",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 /// The enum for player actions. Currently supported
-/// actions are Hit, Stand and Split.
-#[derive(PartialEq)]
+/// actions are Hit, Stand, Double, Split and Surrender.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum PlayerAction {
     Hit,
     Stand,
     Double,
     Split,
+    Surrender,
+}
+
+/// What a human may type on their turn.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum TurnInput {
+    Action(PlayerAction),
+    /// Asks for the explanation of the actions.
+    Help,
+}
+
+/// Parses what a human typed on their turn, ignoring the case :
+/// an action (`hit`, `stand`, `double`, `split`, `surrender`) or
+/// its shortcut (`h`, `s`, `d`, `p`, `r`), or `?` and `help`.
+pub fn parse_turn_input(text: &str) -> Option<TurnInput> {
+    let action = match text.trim().to_lowercase().as_str() {
+        "h" | "hit" => PlayerAction::Hit,
+        "s" | "stand" => PlayerAction::Stand,
+        "d" | "double" => PlayerAction::Double,
+        "p" | "split" => PlayerAction::Split,
+        "r" | "surrender" => PlayerAction::Surrender,
+        "?" | "help" => return Some(TurnInput::Help),
+        _ => return None,
+    };
+    Some(TurnInput::Action(action))
+}
+
+/// Explains each action, and whether it is among the `legal` ones.
+pub fn action_help(legal: &[PlayerAction]) -> String {
+    let actions = [
+        (PlayerAction::Hit, "h, hit", "take another card"),
+        (PlayerAction::Stand, "s, stand", "keep the hand as it is"),
+        (
+            PlayerAction::Double,
+            "d, double",
+            "double the bet, take one last card and stand",
+        ),
+        (
+            PlayerAction::Split,
+            "p, split",
+            "split a pair into two hands, betting again on the second",
+        ),
+        (
+            PlayerAction::Surrender,
+            "r, surrender",
+            "give up the hand and half of the bet",
+        ),
+    ];
+    let mut out = String::new();
+    for (action, keys, explanation) in actions {
+        out += &format!(
+            "  {:<12} : {} ({})\n",
+            keys,
+            explanation,
+            if legal.contains(&action) {
+                "allowed now"
+            } else {
+                "not allowed now"
+            }
+        );
+    }
+    out
+}
+
+/// Parses the bet a human typed, which is a number of units,
+/// `a` or `all` for the largest bet the table allows with the
+/// `available` money, or `r`, `repeat` or nothing at all for the
+/// `last_bet` of the player. `?` and `help` explain these inputs.
+///
+/// The bet is not checked against the limits of the table.
+pub fn parse_bet(
+    text: &str,
+    available: u32,
+    limits: &TableLimits,
+    last_bet: Option<u32>,
+) -> Result<u32, String> {
+    match text.trim().to_lowercase().as_str() {
+        "a" | "all" | "all in" => Ok(limits.clamp(available as f64, available)),
+        "" | "r" | "repeat" => last_bet.ok_or_else(|| String::from("There is no bet to repeat.")),
+        "?" | "help" => Err(String::from(
+            "Type a number of units, a (all in) to bet as much as the table allows, \
+             or r (repeat) or nothing to bet as much as last round.",
+        )),
+        number => number
+            .parse()
+            .map_err(|_| String::from("Not a bet! Type ? for help.")),
+    }
 }
 
 /// This method asks the player at a given index for
-/// his bet at this round, by reading his answer in the terminal.
-pub fn human_bet(index: usize, available: u32) -> u32 {
+/// his bet at this round, by reading his answer in the terminal,
+/// after showing the limits of the table and the bets already
+/// placed by the other players (unless bets are secret).
+///
+/// The answer is read by [parse_bet], and asked again until it
+/// is understood. Colon commands are run by [read_round_answer],
+/// and [Quit] is returned when the players end the game.
+pub fn human_bet(
+    index: usize,
+    table_bets: &[u32],
+    last_bet: Option<u32>,
+    session: &Session,
+) -> Result<u32, Quit> {
+    let (names, limits) = (session.names, session.limits);
+    let available = session.bank[index];
     println!("------------------------");
-    println!("You are player {}.", index + 1);
+    println!("You are {}.", names[index]);
     println!("You have {} units of money available.", available);
-    println!("\nPlease enter your bet amount : ");
-    read_num()
+    println!(
+        "Bets go from {} to {}, by {}.",
+        limits.min, limits.max, limits.chip
+    );
+    if limits.secret_bets {
+        println!("Bets are secret this hand.");
+    } else {
+        for (seat, bet) in table_bets.iter().enumerate() {
+            println!("{} bets {}.", names[seat], bet);
+        }
+    }
+    match last_bet {
+        Some(bet) => println!(
+            "\nPlease enter your bet amount (a for all in, ENTER to bet {} again) : ",
+            bet
+        ),
+        None => println!("\nPlease enter your bet amount (a for all in) : "),
+    }
+    loop {
+        match parse_bet(&read_round_answer(session)?, available, limits, last_bet) {
+            Ok(bet) => return Ok(bet),
+            Err(message) => println!("{}", message),
+        }
+    }
 }
 
 /// This method asks a human player for the action
 /// by reading his answer from the terminal.
+///
+/// The answer is read by [parse_turn_input], and `?` explains
+/// the actions, telling which of them are `legal` on this hand.
+/// Colon commands are run by [read_round_answer], and [Quit] is
+/// returned when the players end the game.
 pub fn human_play(
     scores: &[(u32, Option<u32>)],
     player_hands: &[(Vec<Card>, Option<Vec<Card>>)],
     dealer_hand: &[Card],
-    is_second: bool,
-    index: usize,
-) -> PlayerAction {
+    (index, is_second): (usize, bool),
+    legal: &[PlayerAction],
+    session: &Session,
+) -> Result<PlayerAction, Quit> {
+    let names = session.names;
     println!("------------------------");
     println!(
-        "You are player {}{}.",
-        index + 1,
+        "You are {}{}.",
+        names[index],
         if is_second { ", second hand" } else { "" }
     );
     println!("The board is currently in the following state : \n");
-    display_hands_and_scores(scores, player_hands, dealer_hand);
-    println!("\nPlease enter your move : Hit, Stand, Double or Split.");
-    loop {
-        let mut s = String::new();
-        stdin()
-            .read_line(&mut s)
-            .expect("Did not enter a correct string");
-        if s.ends_with('\n') {
-            s.pop();
+    display_hands_and_scores(scores, player_hands, dealer_hand, names);
+    println!(
+        "\nPlease enter your move : {}, ? for help or :help for the commands.",
+        if legal.contains(&PlayerAction::Surrender) {
+            "(h)it, (s)tand, (d)ouble, s(p)lit or su(r)render"
+        } else {
+            "(h)it, (s)tand, (d)ouble or s(p)lit"
         }
-        if s.ends_with('\r') {
-            s.pop();
+    );
+    loop {
+        match parse_turn_input(&read_round_answer(session)?) {
+            Some(TurnInput::Action(action)) => return Ok(action),
+            Some(TurnInput::Help) => print!("{}", action_help(legal)),
+            None => println!("Unrecognized move. Please input again, or type ? for help:"),
         }
-
-        let _ = match s.as_str() {
-            "Hit" => return PlayerAction::Hit,
-            "Stand" => return PlayerAction::Stand,
-            "Split" => return PlayerAction::Split,
-            "Double" => return PlayerAction::Double,
-            _ => println!("Unrecognized move. Please input again:"),
-        };
     }
 }
 
-/// This method implements the decision algorithm used by bots to play
-/// Approximation of : <https://blog.prepscholar.com/blackjack-strategy>
+/// This method asks the strategy of a bot for its action on a hand,
+/// given the dealer upcard, the `legal` actions, the cards left in
+/// the pack and the rules of the table.
+///
+/// A strategy that picks an action which is not legal hits instead.
 pub fn bot_play(
-    scores: &[(u32, Option<u32>)],
-    is_second: bool,
-    index: usize,
-    double_is_legal: bool,
+    strategy: &dyn Strategy,
+    hand: &[Card],
+    dealer_card: &Card,
+    legal: &[PlayerAction],
+    pack: &[Card],
+    rules: &Rules,
 ) -> PlayerAction {
-    let player_score = if is_second {
-        scores[index].1.unwrap()
-    } else {
-        scores[index].0
-    };
-    let dealer_score = scores.last().unwrap().0;
-    if player_score > 16 || (player_score > 11 && dealer_score < 7) {
-        PlayerAction::Stand
-    } else if double_is_legal && dealer_score < 7 && player_score > 8 {
-        PlayerAction::Double
+    let action = strategy.decide(&Situation {
+        hand,
+        dealer_card,
+        can_double: legal.contains(&PlayerAction::Double),
+        can_split: legal.contains(&PlayerAction::Split),
+        can_surrender: legal.contains(&PlayerAction::Surrender),
+        dealt: &dealt_from_remaining(pack, rules.num_packs),
+        decks_remaining: pack.len() as f64 / 52.0,
+        rules,
+    });
+    if legal.contains(&action) {
+        action
     } else {
         PlayerAction::Hit
     }
 }
 
-pub fn dealer_play(scores: &[(u32, Option<u32>)], index: usize) -> PlayerAction {
-    if scores[index].0 < 17 {
+/// The action of the dealer, who hits below 17, and on a soft 17
+/// when the rules say so.
+pub fn dealer_play(dealer_hand: &[Card], rules: &Rules) -> PlayerAction {
+    if dealer_should_hit(dealer_hand, rules) {
         PlayerAction::Hit
     } else {
         PlayerAction::Stand
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::player::*;
+
+    #[test]
+    fn parse_turn_input_test() {
+        assert_eq!(
+            Some(TurnInput::Action(PlayerAction::Hit)),
+            parse_turn_input("H")
+        );
+        assert_eq!(
+            Some(TurnInput::Action(PlayerAction::Split)),
+            parse_turn_input(" p ")
+        );
+        assert_eq!(
+            Some(TurnInput::Action(PlayerAction::Double)),
+            parse_turn_input("DOUBLE")
+        );
+        assert_eq!(Some(TurnInput::Help), parse_turn_input("?"));
+        assert_eq!(None, parse_turn_input("x"));
+    }
+
+    #[test]
+    fn action_help_test() {
+        let help = action_help(&[PlayerAction::Hit, PlayerAction::Stand]);
+        assert!(help.contains("h, hit       : take another card (allowed now)"));
+        assert!(help.contains("(not allowed now)"));
+        assert_eq!(5, help.lines().count());
+    }
+
+    #[test]
+    fn parse_bet_test() {
+        let limits = TableLimits {
+            min: 10,
+            max: 100,
+            chip: 5,
+            secret_bets: false,
+        };
+        assert_eq!(Ok(25), parse_bet("25", 300, &limits, None));
+        assert_eq!(Ok(100), parse_bet("all", 300, &limits, None));
+        assert_eq!(Ok(60), parse_bet("A", 63, &limits, None));
+        assert_eq!(Ok(7), parse_bet("all in", 7, &limits, None));
+        assert_eq!(Ok(40), parse_bet("", 300, &limits, Some(40)));
+        assert_eq!(Ok(40), parse_bet("r", 300, &limits, Some(40)));
+        assert!(parse_bet("", 300, &limits, None).is_err());
+        assert!(parse_bet("lots", 300, &limits, None).is_err());
+    }
+}
@@ -0,0 +1,258 @@
+use crate::betting::BetContext;
+
+use serde::{Deserialize, Serialize};
+
+/// The classic betting progressions, which change the bet
+/// according to the previous wins and losses.
+///
+/// Bets are counted in betting units. None of these systems
+/// changes the expected loss of a player, they only change
+/// the shape of the distribution of the results.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub enum System {
+    /// Double the bet after each loss, go back to one unit after a win.
+    Martingale { units: u32 },
+    /// Double the bet after each win, up to three wins in a row,
+    /// go back to one unit after a loss.
+    Paroli { wins: u32 },
+    /// Move one step forward in the Fibonacci sequence after a loss,
+    /// two steps back after a win.
+    Fibonacci { step: usize },
+    /// Add a unit after a loss, remove one after a win.
+    DAlembert { units: u32 },
+    /// Bet the sum of the first and last numbers of a line. A win
+    /// crosses them out, a loss appends the lost bet to the line.
+    /// The line starts over at 1, 2, 3, 4 once it is empty.
+    Labouchere { line: Vec<u32> },
+    /// Aim for a profit of one unit per cycle : add a unit after a
+    /// win, keep the bet after a loss, and never bet more than what
+    /// completes the cycle.
+    OscarsGrind { units: u32, profit: i64 },
+}
+
+/// The first line of a Labouchère cycle.
+const LABOUCHERE_LINE: [u32; 4] = [1, 2, 3, 4];
+
+/// The number of wins in a row after which Paroli starts over.
+const PAROLI_WINS: u32 = 3;
+
+impl System {
+    /// Creates a system from its name, in its starting state.
+    pub fn from_name(name: &str) -> Option<System> {
+        match name {
+            "martingale" => Some(System::Martingale { units: 1 }),
+            "paroli" => Some(System::Paroli { wins: 0 }),
+            "fibonacci" => Some(System::Fibonacci { step: 0 }),
+            "dalembert" => Some(System::DAlembert { units: 1 }),
+            "labouchere" => Some(System::Labouchere {
+                line: LABOUCHERE_LINE.to_vec(),
+            }),
+            "oscar" => Some(System::OscarsGrind {
+                units: 1,
+                profit: 0,
+            }),
+            _ => None,
+        }
+    }
+
+    /// The number of units the system wants to bet next.
+    fn units(&self) -> u32 {
+        match self {
+            System::Martingale { units } | System::DAlembert { units } => *units,
+            System::Paroli { wins } => 1 << wins,
+            System::Fibonacci { step } => fibonacci(*step),
+            System::Labouchere { line } => match line.len() {
+                1 => line[0],
+                _ => line[0] + line[line.len() - 1],
+            },
+            System::OscarsGrind { units, profit } => {
+                let missing = (1 - *profit).max(1) as u32;
+                (*units).min(missing)
+            }
+        }
+    }
+
+    /// Updates the system after a round where `bet` units were bet
+    /// and `net` units were won (or lost, when negative).
+    fn update(&mut self, bet: u32, net: i64) {
+        if net == 0 {
+            return;
+        }
+        let won = net > 0;
+        match self {
+            System::Martingale { units } => *units = if won { 1 } else { units.saturating_mul(2) },
+            System::Paroli { wins } => {
+                *wins = if won && *wins + 1 < PAROLI_WINS {
+                    *wins + 1
+                } else {
+                    0
+                }
+            }
+            System::Fibonacci { step } => {
+                *step = if won {
+                    step.saturating_sub(2)
+                } else {
+                    *step + 1
+                }
+            }
+            System::DAlembert { units } => {
+                *units = if won { (*units - 1).max(1) } else { *units + 1 }
+            }
+            System::Labouchere { line } => {
+                if won {
+                    line.remove(0);
+                    line.pop();
+                    if line.is_empty() {
+                        *line = LABOUCHERE_LINE.to_vec();
+                    }
+                } else {
+                    line.push(bet.max(1));
+                }
+            }
+            System::OscarsGrind { units, profit } => {
+                *profit += net;
+                if *profit >= 1 {
+                    *units = 1;
+                    *profit = 0;
+                } else if won {
+                    *units += 1;
+                }
+            }
+        }
+    }
+}
+
+/// The n-th number of the Fibonacci sequence 1, 1, 2, 3, 5...
+fn fibonacci(step: usize) -> u32 {
+    let (mut a, mut b) = (1u32, 1u32);
+    for _ in 0..step {
+        let next = a.saturating_add(b);
+        a = b;
+        b = next;
+    }
+    a
+}
+
+/// A betting progression, playing a [System] with a
+/// betting unit of a given amount of money.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct Progression {
+    pub system: System,
+    /// The value of a betting unit. Defaults to the table minimum.
+    pub unit: Option<u32>,
+    last_bet: u32,
+    last_unit: u32,
+}
+
+impl Progression {
+    pub fn new(system: System, unit: Option<u32>) -> Progression {
+        Progression {
+            system,
+            unit,
+            last_bet: 0,
+            last_unit: 1,
+        }
+    }
+
+    /// The bet required by the progression, capped by the table
+    /// maximum and by the money of the bot.
+    pub fn bet(&mut self, context: &BetContext) -> u32 {
+        let unit = self.unit.unwrap_or(context.limits.min).max(1);
+        let desired = self.system.units() as f64 * unit as f64;
+        self.last_unit = unit;
+        self.last_bet = context.limits.clamp(desired, context.available);
+        self.last_bet
+    }
+
+    /// Updates the progression with the net result of the last round.
+    pub fn record(&mut self, net: i64) {
+        let unit = self.last_unit as i64;
+        let bet_units = (self.last_bet as i64 / unit) as u32;
+        let mut net_units = net / unit;
+        if net_units == 0 {
+            // a fraction of a unit still counts as a win or a loss
+            net_units = net.signum();
+        }
+        self.system.update(bet_units, net_units);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::betting::TableLimits;
+    use crate::progression::*;
+
+    /// Plays a sequence of results, given in units for a single unit
+    /// bet, and returns the bets made.
+    fn play(name: &str, results: &[i64]) -> Vec<u32> {
+        let limits = TableLimits {
+            min: 1,
+            max: 100,
+            chip: 1,
+            secret_bets: false,
+        };
+        let mut progression = Progression::new(System::from_name(name).unwrap(), None);
+        let mut bets = Vec::new();
+        for &result in results {
+            let bet = progression.bet(&BetContext {
+                available: 1000,
+                dealt: &[0; 10],
+                decks_remaining: 4.0,
+                limits: &limits,
+                table_bets: &[],
+            });
+            bets.push(bet);
+            progression.record(result * bet as i64);
+        }
+        bets
+    }
+
+    #[test]
+    fn martingale_test() {
+        assert_eq!(
+            vec![1, 2, 4, 4, 1, 2],
+            play("martingale", &[-1, -1, 0, 1, -1, 1])
+        );
+        // capped by the table maximum
+        assert_eq!(100, *play("martingale", &[-1; 9]).last().unwrap());
+    }
+
+    #[test]
+    fn paroli_test() {
+        assert_eq!(vec![1, 2, 4, 1, 2, 1], play("paroli", &[1, 1, 1, 1, -1, 1]));
+    }
+
+    #[test]
+    fn fibonacci_test() {
+        assert_eq!(
+            vec![1, 1, 2, 3, 5, 2, 1],
+            play("fibonacci", &[-1, -1, -1, -1, 1, 1, 1])
+        );
+    }
+
+    #[test]
+    fn dalembert_test() {
+        assert_eq!(
+            vec![1, 2, 3, 2, 1, 1],
+            play("dalembert", &[-1, -1, 1, 1, 1, 1])
+        );
+    }
+
+    #[test]
+    fn labouchere_test() {
+        // 1 2 3 4 -> lose 5 -> 1 2 3 4 5 -> win 6 -> 2 3 4 -> win 6 -> 3 -> win 3
+        assert_eq!(vec![5, 6, 6, 3, 5], play("labouchere", &[-1, 1, 1, 1, 1]));
+    }
+
+    #[test]
+    fn oscars_grind_test() {
+        // a first win ends the cycle, then two losses and two wins
+        assert_eq!(vec![1, 1, 1, 1, 2, 1], play("oscar", &[1, -1, -1, 1, 1, 1]));
+    }
+
+    #[test]
+    fn fibonacci_sequence() {
+        let seq: Vec<u32> = (0..7).map(fibonacci).collect();
+        assert_eq!(vec![1, 1, 2, 3, 5, 8, 13], seq);
+    }
+}
@@ -0,0 +1,314 @@
+use crate::chart::{Cell, Chart, Row};
+use crate::cli::{Args, RULE_OPTIONS};
+use crate::strategy::strategy_from_name;
+
+use std::io::IsTerminal;
+
+/// The dealer upcards, in the order of the columns of a chart.
+const UPCARDS: [&str; 10] = ["2", "3", "4", "5", "6", "7", "8", "9", "10", "A"];
+
+/// The meaning of each cell, for the legends.
+const LEGEND: [(Cell, &str); 8] = [
+    (Cell::Hit, "hit"),
+    (Cell::Stand, "stand"),
+    (Cell::Double, "double, else hit"),
+    (Cell::DoubleOrStand, "double, else stand"),
+    (Cell::Split, "split"),
+    (Cell::SplitIfDas, "split if double after split, else hit"),
+    (Cell::SurrenderOrHit, "surrender, else hit"),
+    (Cell::SurrenderOrStand, "surrender, else stand"),
+];
+
+/// The colour of a cell : an ANSI background colour code
+/// for terminals, and an RGB colour for SVG and HTML files.
+fn colour(cell: Cell) -> (u8, &'static str) {
+    match cell {
+        Cell::Hit => (41, "#e8726b"),
+        Cell::Stand => (43, "#f2d16b"),
+        Cell::Double | Cell::DoubleOrStand => (42, "#8fcf7f"),
+        Cell::Split | Cell::SplitIfDas => (46, "#7fc8e8"),
+        Cell::SurrenderOrHit | Cell::SurrenderOrStand => (47, "#d0d0d0"),
+    }
+}
+
+/// The sections of a chart with their labelled rows,
+/// leaving out the rows and sections the chart does not have.
+fn sections(chart: &Chart) -> Vec<(&'static str, Vec<(String, Row)>)> {
+    let labelled = |rows: &[Option<Row>], label: &dyn Fn(usize) -> String| {
+        rows.iter()
+            .enumerate()
+            .filter_map(|(i, row)| row.map(|row| (label(i), row)))
+            .collect::<Vec<_>>()
+    };
+    let sections = vec![
+        ("hard", labelled(&chart.hard, &|total| total.to_string())),
+        (
+            "soft",
+            labelled(&chart.soft, &|total| match total {
+                13..=20 => format!("A{}", total - 11),
+                _ => total.to_string(),
+            }),
+        ),
+        (
+            "pairs",
+            labelled(&chart.pairs, &|value| match value {
+                1 => String::from("AA"),
+                10 => String::from("TT"),
+                _ => format!("{}{}", value, value),
+            }),
+        ),
+    ];
+    sections
+        .into_iter()
+        .filter(|(_, rows)| !rows.is_empty())
+        .collect()
+}
+
+/// Renders a chart as text, in the format read by [Chart::parse],
+/// with ANSI colours for terminals when `colour_cells` is set.
+pub fn text(chart: &Chart, colour_cells: bool) -> String {
+    let mut out = String::new();
+    for (name, rows) in sections(chart) {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        let mut header = format!("{:<6}", name);
+        for up in UPCARDS.iter() {
+            header += &format!("{:<3}", up);
+        }
+        out += header.trim_end();
+        out.push('\n');
+        for (label, row) in rows {
+            let mut line = format!("{:<6}", label);
+            for cell in row.iter() {
+                if colour_cells {
+                    let (code, _) = colour(*cell);
+                    line += &format!("\x1b[30;{}m{:<2}\x1b[0m ", code, cell.name());
+                } else {
+                    line += &format!("{:<3}", cell.name());
+                }
+            }
+            out += line.trim_end();
+            out.push('\n');
+        }
+    }
+    out.push('\n');
+    for (cell, meaning) in LEGEND.iter() {
+        out += &format!("# {:<2} : {}\n", cell.name(), meaning);
+    }
+    out
+}
+
+/// Escapes the special characters of XML and HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Renders a chart as a standalone SVG image.
+pub fn svg(chart: &Chart, title: &str) -> String {
+    const MARGIN: usize = 10;
+    const LABEL: usize = 56;
+    const WIDTH: usize = 40;
+    const HEIGHT: usize = 26;
+    let text = |x: usize, y: usize, content: &str, bold: bool| {
+        format!(
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\"{}>{}</text>\n",
+            x,
+            y + HEIGHT - 8,
+            if bold { " font-weight=\"bold\"" } else { "" },
+            escape(content)
+        )
+    };
+    let rect = |x: usize, y: usize, fill: &str| {
+        format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" stroke=\"#ffffff\"/>\n",
+            x, y, WIDTH, HEIGHT, fill
+        )
+    };
+
+    let mut body = text(MARGIN + (LABEL + 10 * WIDTH) / 2, MARGIN, title, true);
+    let mut y = MARGIN + HEIGHT + MARGIN;
+    for (name, rows) in sections(chart) {
+        body += &text(MARGIN + LABEL / 2, y, name, true);
+        for (i, up) in UPCARDS.iter().enumerate() {
+            body += &text(MARGIN + LABEL + i * WIDTH + WIDTH / 2, y, up, true);
+        }
+        y += HEIGHT;
+        for (label, row) in rows {
+            body += &text(MARGIN + LABEL / 2, y, &label, true);
+            for (i, cell) in row.iter().enumerate() {
+                let x = MARGIN + LABEL + i * WIDTH;
+                body += &rect(x, y, colour(*cell).1);
+                body += &text(x + WIDTH / 2, y, cell.name(), false);
+            }
+            y += HEIGHT;
+        }
+        y += MARGIN;
+    }
+    for (cell, meaning) in LEGEND.iter() {
+        body += &rect(MARGIN, y, colour(*cell).1);
+        body += &text(MARGIN + WIDTH / 2, y, cell.name(), false);
+        body += &format!(
+            "<text x=\"{}\" y=\"{}\">{}</text>\n",
+            MARGIN + WIDTH + 8,
+            y + HEIGHT - 8,
+            meaning
+        );
+        y += HEIGHT;
+    }
+
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+         font-family=\"sans-serif\" font-size=\"14\">\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"#ffffff\"/>\n{}</svg>\n",
+        2 * MARGIN + LABEL + 10 * WIDTH,
+        y + MARGIN,
+        body
+    )
+}
+
+/// Renders a chart as a standalone HTML page.
+pub fn html(chart: &Chart, title: &str) -> String {
+    let mut style = String::new();
+    for (cell, _) in LEGEND.iter() {
+        style += &format!(
+            "td.{} {{ background: {}; }}\n",
+            cell.name(),
+            colour(*cell).1
+        );
+    }
+    let td = |cell: Cell| format!("<td class=\"{0}\">{0}</td>", cell.name());
+
+    let mut body = String::new();
+    for (name, rows) in sections(chart) {
+        body += &format!("<table>\n<tr><th>{}</th>", name);
+        for up in UPCARDS.iter() {
+            body += &format!("<th>{}</th>", up);
+        }
+        body += "</tr>\n";
+        for (label, row) in rows {
+            body += &format!("<tr><th>{}</th>", label);
+            for cell in row.iter() {
+                body += &td(*cell);
+            }
+            body += "</tr>\n";
+        }
+        body += "</table>\n";
+    }
+    body += "<table>\n";
+    for (cell, meaning) in LEGEND.iter() {
+        body += &format!(
+            "<tr>{}<td class=\"meaning\">{}</td></tr>\n",
+            td(*cell),
+            meaning
+        );
+    }
+    body += "</table>\n";
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n\
+         <style>\n\
+         body {{ font-family: sans-serif; }}\n\
+         table {{ border-collapse: collapse; margin-bottom: 1em; }}\n\
+         th, td {{ min-width: 2.5em; height: 1.6em; text-align: center; border: 1px solid #ffffff; }}\n\
+         td.meaning {{ text-align: left; padding-left: 0.5em; }}\n\
+         {1}</style>\n</head>\n<body>\n<h1>{0}</h1>\n{2}</body>\n</html>\n",
+        escape(title),
+        style,
+        body
+    )
+}
+
+/// Runs the `chart` subcommand :
+/// `chart [strategy] [--format text|svg|html] [--output FILE] [rules]`.
+///
+/// The format defaults to the extension of the output file, and
+/// text charts are coloured when they are printed to a terminal.
+pub fn run(args: &Args) -> Result<(), String> {
+    args.check_options(&[&["format", "output"], RULE_OPTIONS])?;
+    let name = match args.positional.as_slice() {
+        [] => "basic",
+        [name] => name.as_str(),
+        _ => {
+            return Err(String::from(
+                "Usage: chart [strategy] [--format text|svg|html] [--output FILE]",
+            ))
+        }
+    };
+    let strategy = strategy_from_name(name)?;
+    let rules = args.rules()?;
+    let output = args.get("output");
+    let extension = output.and_then(|path| path.rsplit('.').next());
+    let format = match (args.get("format"), extension) {
+        (Some(format), _) => format,
+        (None, Some("svg")) => "svg",
+        (None, Some("html")) | (None, Some("htm")) => "html",
+        _ => "text",
+    };
+
+    let chart = Chart::from_strategy(strategy.as_ref(), &rules);
+    let title = format!("Strategy {}, {}", name, rules);
+    let rendered = match format {
+        "text" => {
+            let colour_cells = output.is_none() && std::io::stdout().is_terminal();
+            format!("# {}\n{}", title, text(&chart, colour_cells))
+        }
+        "svg" => svg(&chart, &title),
+        "html" => html(&chart, &title),
+        other => return Err(format!("Unknown chart format '{}'", other)),
+    };
+    match output {
+        Some(path) => std::fs::write(path, rendered)
+            .map_err(|e| format!("Cannot write chart {} : {}", path, e)),
+        None => {
+            print!("{}", rendered);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::*;
+    use crate::rules::Rules;
+    use crate::strategy::BasicStrategy;
+
+    fn basic() -> Chart {
+        Chart::from_strategy(&BasicStrategy, &Rules::default())
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let chart = basic();
+        let rendered = text(&chart, false);
+        assert!(rendered.starts_with("hard  2  3  4  5  6  7  8  9  10 A\n5     H  H"));
+        assert_eq!(chart, Chart::parse(&rendered).unwrap());
+        let coloured = text(&chart, true);
+        assert!(coloured.contains("\x1b[30;42mD \x1b[0m"));
+    }
+
+    #[test]
+    fn svg_and_html() {
+        let chart = basic();
+        let cells = 10 * (13 + 8 + 10);
+        let image = svg(&chart, "Basic & co");
+        assert_eq!(cells + LEGEND.len() + 1, image.matches("<rect").count());
+        assert!(image.contains("Basic &amp; co"));
+        let page = html(&chart, "Basic");
+        assert_eq!(
+            cells + LEGEND.len(),
+            page.matches("<td class=\"").count() - LEGEND.len()
+        );
+        assert_eq!(4, page.matches("<table>").count());
+    }
+
+    #[test]
+    fn missing_sections() {
+        let chart = Chart::parse("hard 2 3 4 5 6 7 8 9 10 A\n16 S S S S S H H H H H").unwrap();
+        assert_eq!(1, sections(&chart).len());
+        assert_eq!("16", sections(&chart)[0].1[0].0);
+    }
+}
@@ -0,0 +1,473 @@
+use crate::art::CARD_OPTIONS;
+use crate::card::Card;
+use crate::cli::Args;
+use crate::display::display_hands_and_scores;
+use crate::history::{action_verb, parse_history, Hand, Record};
+use crate::math::{compute_scores, is_splittable};
+use crate::player::PlayerAction;
+use crate::rules::Rules;
+use crate::strategy::{BasicStrategy, Situation, Strategy};
+
+use std::io::stdin;
+
+/// A game of a hand history : a game played from its start,
+/// or a game resumed from a save.
+#[derive(Clone, Debug, Default)]
+pub struct RecordedGame {
+    pub seed: Option<u64>,
+    pub rules: Rules,
+    pub names: Vec<String>,
+}
+
+/// The table right after an event of a recorded round.
+#[derive(Clone, Debug)]
+pub struct Step {
+    /// The game the round belongs to, counted from 0.
+    pub game: usize,
+    pub round: u32,
+    /// The event, as written in the hand history.
+    pub event: String,
+    pub player_hands: Vec<(Vec<Card>, Option<Vec<Card>>)>,
+    pub dealer_hand: Vec<Card>,
+    pub bets: Vec<u32>,
+    /// The decision of the event and what basic strategy would
+    /// have done instead, when the event is a decision of a player.
+    pub decision: Option<(PlayerAction, PlayerAction)>,
+}
+
+/// A move through a replay, typed at its prompt.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Move {
+    Next,
+    Back,
+    NextRound,
+    PreviousRound,
+    /// Goes to the start of a round, by its number.
+    Round(u32),
+    Quit,
+    Help,
+}
+
+/// The keys of the replay, with what they do.
+const KEYS: [(&str, &str); 7] = [
+    ("ENTER", "show the next event"),
+    ("b", "go back one event"),
+    ("n", "go to the next round"),
+    ("p", "go to the previous round"),
+    ("NUMBER", "go to the round of that number"),
+    ("q", "leave the replay"),
+    ("?", "show this help"),
+];
+
+/// Parses a move typed at the prompt of the replay,
+/// without regard to the case.
+pub fn parse_move(text: &str) -> Option<Move> {
+    let text = text.trim().to_lowercase();
+    let parsed = match text.as_str() {
+        "" | "next" => Move::Next,
+        "b" | "back" => Move::Back,
+        "n" => Move::NextRound,
+        "p" => Move::PreviousRound,
+        "q" | "quit" => Move::Quit,
+        "?" | "help" => Move::Help,
+        number => Move::Round(number.parse().ok()?),
+    };
+    Some(parsed)
+}
+
+/// What basic strategy does on a hand, given the cards on the
+/// table. The player is assumed to have the money to double
+/// or split.
+fn basic_decision(step: &Step, seat: usize, second: bool, rules: &Rules) -> Option<PlayerAction> {
+    let (first_hand, second_hand) = &step.player_hands[seat];
+    let hand = if second {
+        second_hand.as_ref()?
+    } else {
+        first_hand
+    };
+    let split = second_hand.is_some();
+    Some(BasicStrategy.decide(&Situation {
+        hand,
+        dealer_card: step.dealer_hand.first()?,
+        can_double: !split || rules.double_after_split,
+        can_split: !split && is_splittable(hand),
+        can_surrender: rules.surrender && !split && hand.len() == 2,
+        dealt: &[0; 10],
+        decks_remaining: rules.num_packs as f64,
+        rules,
+    }))
+}
+
+/// Reads the rules or a seat of the last game of a hand history.
+pub fn read_table(games: &mut [RecordedGame], record: &Record) -> Result<(), String> {
+    let game = games
+        .last_mut()
+        .ok_or_else(|| String::from("The table is given before the game"))?;
+    match record {
+        Record::Table { rules } => game.rules = rules.parse()?,
+        Record::Seat { name, .. } => game.names.push(name.clone()),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// A recorded game, read from a hand history, to be
+/// stepped through one event at a time.
+#[derive(Debug, Default)]
+pub struct Replay {
+    pub games: Vec<RecordedGame>,
+    pub steps: Vec<Step>,
+    /// The steps starting the rounds.
+    rounds: Vec<usize>,
+}
+
+impl Replay {
+    /// Plays the records of a hand history back, keeping
+    /// the table after each event of each round.
+    pub fn from_records(records: &[Record]) -> Result<Replay, String> {
+        let mut replay = Replay::default();
+        let mut current: Option<Step> = None;
+        for record in records {
+            match record {
+                Record::Header { .. } | Record::Shoe(_) => continue,
+                Record::Game { seed, .. } => {
+                    replay.games.push(RecordedGame {
+                        seed: *seed,
+                        ..RecordedGame::default()
+                    });
+                    current = None;
+                    continue;
+                }
+                Record::Table { .. } | Record::Seat { .. } => {
+                    read_table(&mut replay.games, record)?;
+                    continue;
+                }
+                Record::Round { number, .. } => {
+                    let game = replay
+                        .games
+                        .len()
+                        .checked_sub(1)
+                        .ok_or_else(|| format!("Round {} is played before the game", number))?;
+                    let seats = replay.games[game].names.len();
+                    replay.rounds.push(replay.steps.len());
+                    current = Some(Step {
+                        game,
+                        round: *number,
+                        event: String::new(),
+                        player_hands: vec![(Vec::new(), None); seats],
+                        dealer_hand: Vec::new(),
+                        bets: vec![0; seats],
+                        decision: None,
+                    });
+                }
+                _ => {}
+            }
+            let step = current
+                .as_mut()
+                .ok_or_else(|| String::from("An event is recorded before the first round"))?;
+            step.decision = None;
+            let round = step.round;
+            if let Record::Bet { seat, .. }
+            | Record::Dealt {
+                hand: Hand::Player { seat, .. },
+                ..
+            }
+            | Record::Action {
+                hand: Hand::Player { seat, .. },
+                ..
+            } = record
+            {
+                if *seat >= step.bets.len() {
+                    return Err(format!("Round {} : there is no seat {}", round, seat + 1));
+                }
+            }
+            match record {
+                Record::Bet { seat, amount } => step.bets[*seat] = *amount,
+                Record::Dealt {
+                    hand: Hand::Player { seat, second },
+                    card,
+                } => {
+                    let hands = &mut step.player_hands[*seat];
+                    match second {
+                        false => hands.0.push(*card),
+                        true => hands.1.get_or_insert_with(Vec::new).push(*card),
+                    }
+                }
+                Record::Dealt {
+                    hand: Hand::Dealer,
+                    card,
+                } => step.dealer_hand.push(*card),
+                Record::Action {
+                    hand: Hand::Player { seat, second },
+                    action,
+                } => {
+                    let rules = &replay.games[step.game].rules;
+                    let advice = basic_decision(step, *seat, *second, rules);
+                    step.decision = advice.map(|advice| (*action, advice));
+                    match action {
+                        PlayerAction::Split => {
+                            let hands = &mut step.player_hands[*seat];
+                            let card = hands.0.pop().ok_or_else(|| {
+                                format!("Round {} : there is no hand to split", round)
+                            })?;
+                            hands.1 = Some(vec![card]);
+                        }
+                        PlayerAction::Double => step.bets[*seat] *= 2,
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+            step.event = record
+                .to_line(&replay.games[step.game].names)
+                .trim_start()
+                .to_string();
+            replay.steps.push(step.clone());
+        }
+        Ok(replay)
+    }
+
+    /// The index of the round of a step in [Replay::rounds].
+    fn round_index(&self, position: usize) -> usize {
+        self.rounds.partition_point(|&start| start <= position) - 1
+    }
+
+    /// The step a move goes to from a step, or a message
+    /// telling why it cannot be made.
+    pub fn go(&self, position: usize, to: Move) -> Result<usize, String> {
+        let end = || String::from("This is the end of the recording.");
+        let start = || String::from("This is the start of the recording.");
+        let round = self.round_index(position);
+        match to {
+            Move::Next if position + 1 < self.steps.len() => Ok(position + 1),
+            Move::Next => Err(end()),
+            Move::Back => position.checked_sub(1).ok_or_else(start),
+            Move::NextRound => self.rounds.get(round + 1).copied().ok_or_else(end),
+            Move::PreviousRound => round
+                .checked_sub(1)
+                .map(|round| self.rounds[round])
+                .ok_or_else(start),
+            Move::Round(number) => {
+                let game = self.steps[position].game;
+                let starts = || {
+                    self.rounds
+                        .iter()
+                        .copied()
+                        .filter(|&start| self.steps[start].round == number)
+                };
+                // the round of the game being replayed, or of another game
+                starts()
+                    .find(|&start| self.steps[start].game == game)
+                    .or_else(|| starts().next())
+                    .ok_or_else(|| format!("There is no round {} in the recording.", number))
+            }
+            Move::Quit | Move::Help => Ok(position),
+        }
+    }
+
+    /// Shows the table after a step, with the event of the step
+    /// and, for the decisions of players, what basic strategy
+    /// would have done.
+    pub fn show(&self, position: usize) {
+        let step = &self.steps[position];
+        let game = &self.games[step.game];
+        let start = self.rounds[self.round_index(position)];
+        let length = self.steps[start..]
+            .iter()
+            .take_while(|other| other.round == step.round && other.game == step.game)
+            .count();
+        print!("Round {}", step.round);
+        if let Some(seed) = game.seed {
+            print!(" of the game of seed {}", seed);
+        }
+        println!(", event {} of {}", position - start + 1, length);
+        let dealt = !step.dealer_hand.is_empty()
+            || step.player_hands.iter().any(|hands| !hands.0.is_empty());
+        if dealt {
+            display_hands_and_scores(
+                &compute_scores(&step.player_hands, &step.dealer_hand),
+                &step.player_hands,
+                &step.dealer_hand,
+                &game.names,
+            );
+        }
+        let bets: Vec<String> = game
+            .names
+            .iter()
+            .zip(&step.bets)
+            .filter(|(_, &bet)| bet > 0)
+            .map(|(name, bet)| format!("{} {}", name, bet))
+            .collect();
+        if !bets.is_empty() {
+            println!("Bets : {}", bets.join(", "));
+        }
+        println!("> {}", step.event);
+        match step.decision {
+            Some((action, advice)) if action == advice => {
+                println!("  Basic strategy also {}.", action_verb(advice))
+            }
+            Some((_, advice)) => println!("  Basic strategy {} instead.", action_verb(advice)),
+            None => {}
+        }
+    }
+}
+
+/// Runs the `replay` subcommand : `replay FILE [--round N]`, which
+/// steps through the rounds of a hand history written by the game.
+pub fn run(args: &Args) -> Result<(), String> {
+    args.check_options(&[&["round"], CARD_OPTIONS])?;
+    let path = match args.positional.as_slice() {
+        [path] => path,
+        _ => return Err(String::from("Usage: replay FILE [--round N]")),
+    };
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read the hand history {} : {}", path, e))?;
+    let records = parse_history(&text).map_err(|e| format!("{}, {}", path, e))?;
+    let replay = Replay::from_records(&records).map_err(|e| format!("{} : {}", path, e))?;
+    if replay.steps.is_empty() {
+        return Err(format!("{} has no rounds to replay", path));
+    }
+    let mut position = match args.get("round") {
+        Some(_) => replay.go(0, Move::Round(args.parse_or("round", 0)?))?,
+        None => 0,
+    };
+    println!("Type ? for the keys of the replay.\n");
+    let mut moved = true;
+    loop {
+        if moved {
+            replay.show(position);
+        }
+        let mut s = String::new();
+        if stdin()
+            .read_line(&mut s)
+            .expect("Did not enter a correct string")
+            == 0
+        {
+            return Ok(());
+        }
+        moved = false;
+        match parse_move(&s) {
+            Some(Move::Quit) => return Ok(()),
+            Some(Move::Help) => {
+                for (key, help) in KEYS.iter() {
+                    println!("  {:<6} : {}", key, help);
+                }
+            }
+            Some(to) => match replay.go(position, to) {
+                Ok(next) => {
+                    position = next;
+                    moved = true;
+                    println!();
+                }
+                Err(message) => println!("{}", message),
+            },
+            None => println!("Unknown key. Type ? for the keys of the replay."),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::replay::*;
+
+    const HISTORY: &str = "Blackjack hand history, version 2\n\
+                           Game from round 1, seed 42\n\
+                           Table: 4D S17 DAS\n\
+                           Seat 1: Ann (human)\n\
+                           Seat 2: Bob (bot)\n\n\
+                           Round 1, shoe at card 0 of 208\n\
+                           Ann: bets 25\n\
+                           Bob: bets 10\n\
+                           Ann: dealt 8C\n\
+                           Ann: dealt 8D\n\
+                           Bob: dealt 6H\n\
+                           Bob: dealt 5S\n\
+                           Dealer: dealt 10S\n\
+                           Ann: splits\n\
+                           Ann: dealt 3H\n\
+                           Ann: stands\n\
+                           Ann (second hand): dealt 10C\n\
+                           Ann (second hand): stands\n\
+                           Bob: doubles\n\
+                           Bob: dealt 9D\n\
+                           Dealer: dealt 7C\n\
+                           Dealer: stands\n\
+                           Ann: loses 25 with 11\n\
+                           Ann (second hand): wins 25 with 18\n\
+                           Bob: wins 20 with 20\n\
+                           Banks: 300, 320\n\n\
+                           Round 2, shoe at card 10 of 208\n\
+                           Ann: bets 10\n\
+                           Game from round 2\n\
+                           Table: 4D S17 DAS\n\
+                           Seat 1: Ann (human)\n\n\
+                           Round 2, shoe at card 10 of 208\n\
+                           Ann: bets 30\n";
+
+    fn replay() -> Replay {
+        Replay::from_records(&parse_history(HISTORY).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn from_records_test() {
+        let replay = replay();
+        assert_eq!(2, replay.games.len());
+        assert_eq!(Some(42), replay.games[0].seed);
+        assert_eq!(25, replay.steps.len());
+        assert_eq!("Round 1, shoe at card 0 of 208", replay.steps[0].event);
+        let split = &replay.steps[8];
+        assert_eq!("Ann: splits", split.event);
+        assert_eq!(1, split.player_hands[0].0.len());
+        assert_eq!(Some(1), split.player_hands[0].1.as_ref().map(Vec::len));
+        // without a hole card, eights are not split against a ten
+        assert_eq!(
+            Some((PlayerAction::Split, PlayerAction::Hit)),
+            split.decision
+        );
+        // 11 against a 10 is hit, not doubled, without a hole card
+        assert_eq!(
+            Some((PlayerAction::Stand, PlayerAction::Hit)),
+            replay.steps[10].decision
+        );
+        assert_eq!(vec![25, 20], replay.steps[14].bets);
+        assert_eq!(None, replay.steps[15].decision);
+        assert_eq!(vec![30], replay.steps[24].bets);
+        let history = parse_history("Game from round 1\nShuffle\n").unwrap();
+        assert!(Replay::from_records(&history).is_err());
+        let history = HISTORY.replace("Ann: dealt 8C\nAnn: dealt 8D\n", "");
+        assert!(Replay::from_records(&parse_history(&history).unwrap()).is_err());
+        // the second game gives no seats, but the names of the first
+        // game are enough to read the history
+        let history = HISTORY.replace("Seat 1: Ann (human)\n\n", "\n");
+        assert_eq!(
+            Err(String::from("Round 2 : there is no seat 1")),
+            Replay::from_records(&parse_history(&history).unwrap()).map(|_| ())
+        );
+    }
+
+    #[test]
+    fn go_test() {
+        let replay = replay();
+        assert_eq!(Ok(1), replay.go(0, Move::Next));
+        assert_eq!(Ok(4), replay.go(5, Move::Back));
+        assert!(replay.go(0, Move::Back).is_err());
+        assert!(replay.go(24, Move::Next).is_err());
+        assert_eq!(Ok(21), replay.go(3, Move::NextRound));
+        assert_eq!(Ok(0), replay.go(22, Move::PreviousRound));
+        assert!(replay.go(3, Move::PreviousRound).is_err());
+        // round 2 of the game being replayed
+        assert_eq!(Ok(21), replay.go(3, Move::Round(2)));
+        assert_eq!(Ok(23), replay.go(24, Move::Round(2)));
+        assert_eq!(Ok(0), replay.go(24, Move::Round(1)));
+        assert!(replay.go(0, Move::Round(7)).is_err());
+    }
+
+    #[test]
+    fn parse_move_test() {
+        assert_eq!(Some(Move::Next), parse_move("\n"));
+        assert_eq!(Some(Move::Back), parse_move("B"));
+        assert_eq!(Some(Move::Round(12)), parse_move(" 12 "));
+        assert_eq!(Some(Move::Quit), parse_move("q"));
+        assert_eq!(None, parse_move("x"));
+    }
+}
@@ -0,0 +1,578 @@
+use crate::betting::{BetContext, BetPolicy, TableLimits};
+use crate::card::Card;
+use crate::cli::{Args, LIMIT_OPTIONS};
+use crate::count::dealt_from_remaining;
+use crate::history::{parse_history, Hand, Record};
+use crate::math::{
+    compute_blackjack_index, compute_result, compute_scores, hand_value, is_blackjack,
+    is_splittable,
+};
+use crate::player::{bot_play, PlayerAction};
+use crate::replay::{read_table, RecordedGame};
+use crate::rules::Rules;
+use crate::sim::dealer_should_hit;
+use crate::strategy::{strategy_from_name, Strategy};
+use crate::utils::{net_results, settle_surrenders};
+
+/// A round of a hand history, with what a re-simulation needs.
+#[derive(Clone, Debug, Default)]
+pub struct RecordedRound {
+    /// The game the round belongs to, counted from 0.
+    pub game: usize,
+    pub number: u32,
+    /// The cards of the shoe from the start of the round,
+    /// the next one last, as in the pack of the game.
+    pub pack: Vec<Card>,
+    pub bets: Vec<u32>,
+    /// The decisions of the first and second hands of each seat.
+    pub decisions: Vec<[Vec<PlayerAction>; 2]>,
+    /// The money each seat won or lost.
+    pub nets: Vec<i64>,
+    /// The banks of the players after the round.
+    pub banks: Vec<u32>,
+}
+
+/// Reads the games and the rounds of a hand history. Every round
+/// must come with the order of its shoe, which the game records
+/// at the start of each game and after each shuffle.
+pub fn recorded_rounds(
+    records: &[Record],
+) -> Result<(Vec<RecordedGame>, Vec<RecordedRound>), String> {
+    let mut games = Vec::new();
+    let mut rounds: Vec<RecordedRound> = Vec::new();
+    // the shoe being dealt, and where the last round starts in it
+    let mut shoe: Option<&[Card]> = None;
+    let mut start = (0, 0);
+    let mut in_round = false;
+    for record in records {
+        match record {
+            Record::Game { seed, .. } => {
+                games.push(RecordedGame {
+                    seed: *seed,
+                    ..RecordedGame::default()
+                });
+                in_round = false;
+            }
+            Record::Table { .. } | Record::Seat { .. } => read_table(&mut games, record)?,
+            Record::Shoe(cards) => {
+                // a shoe running out during a round is replaced by a
+                // new one, dealt once the old one is used up
+                if let Some(round) = rounds.last_mut().filter(|_| in_round) {
+                    if !round.pack.is_empty() {
+                        let mut pack: Vec<Card> = cards.iter().rev().copied().collect();
+                        pack.append(&mut round.pack);
+                        round.pack = pack;
+                    }
+                }
+                shoe = Some(cards);
+            }
+            Record::Header { .. } | Record::Shuffle => {}
+            Record::Round {
+                number,
+                position,
+                size,
+            } => {
+                let game = games
+                    .len()
+                    .checked_sub(1)
+                    .ok_or_else(|| format!("Round {} is played before the game", number))?;
+                let seats = games[game].names.len();
+                rounds.push(RecordedRound {
+                    game,
+                    number: *number,
+                    bets: vec![0; seats],
+                    decisions: vec![[Vec::new(), Vec::new()]; seats],
+                    nets: vec![0; seats],
+                    ..RecordedRound::default()
+                });
+                start = (*position, *size);
+                in_round = true;
+            }
+            _ if !in_round => {}
+            record => {
+                let round = rounds.last_mut().unwrap();
+                if round.pack.is_empty() {
+                    // the shoe lines of the round come before its events
+                    let (position, size) = start;
+                    let cards = shoe
+                        .filter(|cards| position + cards.len() >= size)
+                        .map(|cards| &cards[position + cards.len() - size..])
+                        .ok_or_else(|| {
+                            format!(
+                                "Round {} : the order of the shoe is not recorded",
+                                round.number
+                            )
+                        })?;
+                    round.pack = cards.iter().rev().copied().collect();
+                }
+                match record {
+                    Record::Bet { seat, amount } => round.bets[*seat] = *amount,
+                    Record::Action {
+                        hand: Hand::Player { seat, second },
+                        action,
+                    } => round.decisions[*seat][*second as usize].push(*action),
+                    Record::Settle {
+                        hand: Hand::Player { seat, .. },
+                        net,
+                        ..
+                    } => round.nets[*seat] += net,
+                    Record::Banks(banks) => {
+                        round.banks = banks.clone();
+                        in_round = false;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok((games, rounds))
+}
+
+/// Draws the next card of the recorded shoe.
+fn draw(pack: &mut Vec<Card>, round: u32) -> Result<Card, String> {
+    pack.pop()
+        .ok_or_else(|| format!("Round {} : the recorded shoe runs out", round))
+}
+
+/// Plays a recorded round again, the player at `seat` betting with
+/// the given policy and playing with the given strategy, or as
+/// recorded when there is none. The other players make the decisions
+/// they made, even if they get other cards, and the dealer plays by
+/// the rules. A double or a split the player cannot afford is a hit,
+/// as is a recorded surrender of a hand that may not be surrendered,
+/// and a recorded bet larger than their bank is cut to the bank.
+///
+/// Returns the bet of the player and the money they won or lost.
+pub fn resimulate_round(
+    round: &RecordedRound,
+    (seat, bank): (usize, u32),
+    player: (Option<&dyn Strategy>, Option<&mut BetPolicy>),
+    rules: &Rules,
+    limits: &TableLimits,
+) -> Result<(u32, i64), String> {
+    let (strategy, policy) = player;
+    let mut pack = round.pack.clone();
+    let mut bets = round.bets.clone();
+    if let Some(policy) = policy {
+        bets[seat] = policy.bet(&BetContext {
+            available: bank,
+            dealt: &dealt_from_remaining(&pack, rules.num_packs),
+            decks_remaining: pack.len() as f64 / 52.0,
+            limits,
+            table_bets: if limits.secret_bets {
+                &[]
+            } else {
+                &bets[..seat]
+            },
+        });
+    }
+    let bet = bets[seat].min(bank);
+    bets[seat] = bet;
+    let mut available = bank - bet;
+
+    let mut player_hands: Vec<(Vec<Card>, Option<Vec<Card>>)> =
+        vec![(Vec::new(), None); bets.len()];
+    for hand in player_hands.iter_mut() {
+        hand.0.push(draw(&mut pack, round.number)?);
+        hand.0.push(draw(&mut pack, round.number)?);
+    }
+    let mut dealer_hand = vec![draw(&mut pack, round.number)?];
+    let mut surrendered = vec![false; player_hands.len()];
+
+    for index in 0..player_hands.len() {
+        for second in [false, true].iter().copied() {
+            if second && player_hands[index].1.is_none() {
+                break;
+            }
+            let mut recorded = round.decisions[index][second as usize].iter().copied();
+            loop {
+                let (first_hand, second_hand) = &player_hands[index];
+                let hand: &[Card] = match second_hand {
+                    Some(second_hand) if second => second_hand,
+                    _ => first_hand,
+                };
+                let split = second_hand.is_some();
+                if hand_value(hand) >= 21 {
+                    break;
+                }
+                let affordable = index != seat || available >= bets[index];
+                let can_surrender = rules.surrender && !split && hand.len() == 2;
+                let action = match strategy {
+                    Some(strategy) if index == seat => {
+                        let mut legal = vec![PlayerAction::Hit, PlayerAction::Stand];
+                        if affordable && (!split || rules.double_after_split) {
+                            legal.push(PlayerAction::Double);
+                        }
+                        if affordable && !split && is_splittable(hand) {
+                            legal.push(PlayerAction::Split);
+                        }
+                        if can_surrender {
+                            legal.push(PlayerAction::Surrender);
+                        }
+                        bot_play(strategy, hand, &dealer_hand[0], &legal, &pack, rules)
+                    }
+                    _ => match recorded.next().unwrap_or(PlayerAction::Stand) {
+                        PlayerAction::Double | PlayerAction::Split if !affordable => {
+                            PlayerAction::Hit
+                        }
+                        PlayerAction::Surrender if !can_surrender => PlayerAction::Hit,
+                        action => action,
+                    },
+                };
+                let card = match action {
+                    PlayerAction::Hit | PlayerAction::Double => {
+                        Some(draw(&mut pack, round.number)?)
+                    }
+                    _ => None,
+                };
+                let hands = &mut player_hands[index];
+                let hand = match &mut hands.1 {
+                    Some(second_hand) if second => second_hand,
+                    _ => &mut hands.0,
+                };
+                match action {
+                    PlayerAction::Hit => hand.push(card.unwrap()),
+                    PlayerAction::Double => {
+                        hand.push(card.unwrap());
+                        if index == seat {
+                            available -= bets[index];
+                        }
+                        bets[index] *= 2;
+                        break;
+                    }
+                    PlayerAction::Split if !split && is_splittable(hand) => {
+                        let card = hand.pop().unwrap();
+                        hands.1 = Some(vec![card]);
+                        if index == seat {
+                            available -= bets[index];
+                        }
+                    }
+                    PlayerAction::Surrender => {
+                        surrendered[index] = true;
+                        break;
+                    }
+                    _ => break,
+                }
+            }
+        }
+    }
+    while dealer_should_hit(&dealer_hand, rules) {
+        dealer_hand.push(draw(&mut pack, round.number)?);
+    }
+
+    let scores = compute_scores(&player_hands, &dealer_hand);
+    let bj_index = compute_blackjack_index(&player_hands, &dealer_hand);
+    let mut results = compute_result(scores, bj_index);
+    let stakes = settle_surrenders(
+        &mut results,
+        &bets,
+        &surrendered,
+        is_blackjack(&dealer_hand),
+    );
+    let [three_two_index, winner_index, _, loser_index] = &results;
+    let nets = net_results(three_two_index, winner_index, loser_index, &stakes);
+    Ok((bet, nets[seat]))
+}
+
+/// A round as it was played and as it is re-simulated :
+/// the bet of the player and the money they won or lost.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct RoundComparison {
+    pub game: usize,
+    pub number: u32,
+    pub original: (u32, i64),
+    /// None once the player ran out of money.
+    pub resimulated: Option<(u32, i64)>,
+}
+
+/// Re-simulates the rounds the player of the given name played.
+/// Each game of the hand history starts from the bank the player
+/// had at its start, and the betting policy starts over. Rounds
+/// that were quit before their end are skipped.
+pub fn resimulate(
+    games: &[RecordedGame],
+    rounds: &[RecordedRound],
+    name: &str,
+    player: (Option<&dyn Strategy>, Option<&BetPolicy>),
+    limits: &TableLimits,
+) -> Result<Vec<RoundComparison>, String> {
+    let (strategy, policy) = player;
+    let mut comparisons = Vec::new();
+    let mut game = None;
+    let mut bank = 0;
+    let mut policy = policy.cloned();
+    for round in rounds {
+        let seat = match games[round.game].names.iter().position(|n| n == name) {
+            Some(seat) => seat,
+            None => continue,
+        };
+        // a round the players quit was not settled
+        if round.banks.is_empty() {
+            continue;
+        }
+        let original = (round.bets[seat], round.nets[seat]);
+        if game != Some(round.game) {
+            game = Some(round.game);
+            let after = *round
+                .banks
+                .get(seat)
+                .ok_or_else(|| format!("Round {} is not finished", round.number))?;
+            bank = (after as i64 - original.1) as u32;
+            policy = player.1.cloned();
+        }
+        let resimulated = if bank == 0 {
+            None
+        } else {
+            let (bet, net) = resimulate_round(
+                round,
+                (seat, bank),
+                (strategy, policy.as_mut()),
+                &games[round.game].rules,
+                limits,
+            )?;
+            if let Some(policy) = &mut policy {
+                policy.record(net);
+            }
+            bank = (bank as i64 + net) as u32;
+            Some((bet, net))
+        };
+        comparisons.push(RoundComparison {
+            game: round.game,
+            number: round.number,
+            original,
+            resimulated,
+        });
+    }
+    Ok(comparisons)
+}
+
+/// Runs the `resimulate` subcommand :
+/// `resimulate FILE [--seat NAME] [--strategy NAME] [--betting POLICY]`,
+/// which plays the rounds of a hand history again on the same
+/// shoes, and compares the results of a player with the original.
+pub fn run(args: &Args) -> Result<(), String> {
+    let path = match args.positional.as_slice() {
+        [path] => path,
+        _ => {
+            return Err(String::from(
+                "Usage: resimulate FILE [--seat NAME] [--strategy NAME] [--betting POLICY]",
+            ))
+        }
+    };
+    args.check_options(&[&["betting", "seat", "strategy"], LIMIT_OPTIONS])?;
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read the hand history {} : {}", path, e))?;
+    let records = parse_history(&text).map_err(|e| format!("{}, {}", path, e))?;
+    let (games, rounds) = recorded_rounds(&records).map_err(|e| format!("{} : {}", path, e))?;
+    let name = match args.get("seat") {
+        Some(name) => name,
+        None => games
+            .first()
+            .and_then(|game| game.names.first())
+            .ok_or_else(|| format!("{} has no players", path))?,
+    };
+    let strategy = args.get("strategy").map(strategy_from_name).transpose()?;
+    let policy = args.get("betting").map(BetPolicy::parse).transpose()?;
+    let comparisons = resimulate(
+        &games,
+        &rounds,
+        name,
+        (strategy.as_deref(), policy.as_ref()),
+        &args.limits()?,
+    )?;
+    if comparisons.is_empty() {
+        return Err(format!("{} did not play in {}", name, path));
+    }
+
+    println!(
+        "{} with the strategy {} and the betting {}, on the recorded shoes :",
+        name,
+        args.get("strategy").unwrap_or("as played"),
+        args.get("betting").unwrap_or("as played")
+    );
+    println!(
+        "{:>6} {:>8} {:>8} {:>8} {:>8} {:>10}",
+        "Round", "Bet", "Net", "New bet", "New net", "Difference"
+    );
+    let mut totals = (0, 0);
+    let mut broke = None;
+    for comparison in &comparisons {
+        let (bet, net) = comparison.original;
+        let (new_bet, new_net) = comparison.resimulated.unwrap_or((0, 0));
+        if comparison.resimulated.is_none() && broke.is_none() {
+            broke = Some(comparison.number);
+        }
+        totals.0 += net;
+        totals.1 += new_net;
+        println!(
+            "{:>6} {:>8} {:>+8} {:>8} {:>+8} {:>+10}",
+            comparison.number,
+            bet,
+            net,
+            new_bet,
+            new_net,
+            new_net - net
+        );
+    }
+    println!(
+        "{:>6} {:>8} {:>+8} {:>8} {:>+8} {:>+10}",
+        "Total",
+        "",
+        totals.0,
+        "",
+        totals.1,
+        totals.1 - totals.0
+    );
+    if let Some(round) = broke {
+        println!(
+            "The re-simulated player ran out of money before round {}.",
+            round
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::resim::*;
+
+    const HISTORY: &str = "Blackjack hand history, version 2\n\
+                           Game from round 1, seed 42\n\
+                           Table: 4D S17 DAS\n\
+                           Seat 1: Ann (bot)\n\
+                           Shoe: 2C 2C\n\n\
+                           Round 1, shoe at card 0 of 6\n\
+                           Shuffle\n\
+                           Shoe: 10C 6H 9D 3S 8S 2D\n\
+                           Ann: bets 10\n\
+                           Ann: dealt 10C\n\
+                           Ann: dealt 6H\n\
+                           Dealer: dealt 9D\n\
+                           Ann: stands\n\
+                           Dealer: hits\n\
+                           Dealer: dealt 3S\n\
+                           Dealer: hits\n\
+                           Dealer: dealt 8S\n\
+                           Dealer: stands\n\
+                           Ann: loses 10 with 16\n\
+                           Banks: 90\n";
+
+    fn compare(
+        history: &str,
+        strategy: Option<&str>,
+        policy: Option<&str>,
+    ) -> Result<Vec<RoundComparison>, String> {
+        let (games, rounds) = recorded_rounds(&parse_history(history).unwrap())?;
+        let strategy = strategy.map(|name| strategy_from_name(name).unwrap());
+        let policy = policy.map(|text| BetPolicy::parse(text).unwrap());
+        resimulate(
+            &games,
+            &rounds,
+            "Ann",
+            (strategy.as_deref(), policy.as_ref()),
+            &TableLimits::default(),
+        )
+    }
+
+    #[test]
+    fn recorded_rounds_test() {
+        let (games, rounds) = recorded_rounds(&parse_history(HISTORY).unwrap()).unwrap();
+        assert_eq!(vec![String::from("Ann")], games[0].names);
+        assert_eq!(1, rounds.len());
+        assert_eq!(6, rounds[0].pack.len());
+        assert_eq!(Some(&"10C".parse().unwrap()), rounds[0].pack.last());
+        assert_eq!(
+            vec![[vec![PlayerAction::Stand], vec![]]],
+            rounds[0].decisions
+        );
+        assert_eq!(
+            (vec![10], vec![-10], vec![90]),
+            (
+                rounds[0].bets.clone(),
+                rounds[0].nets.clone(),
+                rounds[0].banks.clone()
+            )
+        );
+        let unrecorded = HISTORY.replace("Shuffle\nShoe: 10C 6H 9D 3S 8S 2D\n", "");
+        assert!(recorded_rounds(&parse_history(&unrecorded).unwrap()).is_err());
+    }
+
+    #[test]
+    fn resimulate_test() {
+        // as played
+        let same = compare(HISTORY, None, None).unwrap();
+        assert_eq!(Some((10, -10)), same[0].resimulated);
+        // hitting 16 against a 9 takes the 3 the dealer drew
+        let basic = compare(HISTORY, Some("basic"), None).unwrap();
+        assert_eq!((10, -10), basic[0].original);
+        assert_eq!(Some((10, 10)), basic[0].resimulated);
+        // the bank of 100 before the round
+        let half = compare(HISTORY, None, Some("half-bank")).unwrap();
+        assert_eq!(Some((50, -50)), half[0].resimulated);
+        let quit = &HISTORY[..HISTORY.find("Ann: stands").unwrap()];
+        assert!(compare(quit, Some("basic"), None).unwrap().is_empty());
+        let short = HISTORY.replace("3S 8S 2D", "3S").replace("of 6", "of 4");
+        assert!(compare(&short, Some("basic"), None).is_err());
+        // a new shoe is shuffled when the old one runs out during the round
+        let refilled = HISTORY
+            .replace(" 3S 8S 2D\n", "\n")
+            .replace("of 6", "of 3")
+            .replace(
+                "hits\nDealer: dealt 3S",
+                "hits\nShuffle\nShoe: 3S 8S 2D\nDealer: dealt 3S",
+            );
+        let basic = compare(&refilled, Some("basic"), None).unwrap();
+        assert_eq!(Some((10, 10)), basic[0].resimulated);
+        // with late surrender, basic strategy gives up 16 against a 9
+        let surrender = HISTORY.replace("DAS\n", "DAS LS\n");
+        let basic = compare(&surrender, Some("basic"), None).unwrap();
+        assert_eq!(Some((10, -5)), basic[0].resimulated);
+        let surrendered = surrender.replace("Ann: stands", "Ann: surrenders");
+        let same = compare(&surrendered, None, None).unwrap();
+        assert_eq!(Some((10, -5)), same[0].resimulated);
+        // and a recorded surrender hits where it is not offered
+        let unoffered = HISTORY.replace("Ann: stands", "Ann: surrenders");
+        let same = compare(&unoffered, None, None).unwrap();
+        assert_eq!(Some((10, 10)), same[0].resimulated);
+    }
+
+    #[test]
+    fn smaller_bank() {
+        let history = "Blackjack hand history, version 2\n\
+                       Game from round 1, seed 42\n\
+                       Table: 4D S17 DAS\n\
+                       Seat 1: Ann (bot)\n\
+                       Shoe: 2C 2C\n\n\
+                       Round 1, shoe at card 0 of 12\n\
+                       Shuffle\n\
+                       Shoe: 6C 5H 6D 2S 10D 5C 10H 10S 9C 2D 10C 3D\n\
+                       Ann: bets 40\n\
+                       Ann: dealt 6C\n\
+                       Ann: dealt 5H\n\
+                       Dealer: dealt 6D\n\
+                       Ann: stands\n\
+                       Dealer: hits\n\
+                       Dealer: dealt 2S\n\
+                       Dealer: hits\n\
+                       Dealer: dealt 10D\n\
+                       Dealer: stands\n\
+                       Ann: loses 40 with 11\n\
+                       Banks: 60\n\n\
+                       Round 2, shoe at card 5 of 12\n\
+                       Ann: bets 50\n\
+                       Ann: dealt 5C\n\
+                       Ann: dealt 10H\n\
+                       Dealer: dealt 10S\n\
+                       Ann: stands\n\
+                       Dealer: hits\n\
+                       Dealer: dealt 9C\n\
+                       Dealer: stands\n\
+                       Ann: loses 50 with 15\n\
+                       Banks: 10\n";
+        // doubling 11 and losing leaves 20 of the bank of 100
+        let basic = compare(history, Some("basic"), None).unwrap();
+        assert_eq!(Some((40, -80)), basic[0].resimulated);
+        assert_eq!((50, -50), basic[1].original);
+        assert_eq!(Some((20, -20)), basic[1].resimulated);
+    }
+}
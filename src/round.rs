@@ -1,90 +1,348 @@
+use crate::betting::{BetContext, BetPolicy, TableLimits};
 use crate::card::Card;
+use crate::command::{Quit, Session};
+use crate::count::dealt_from_remaining;
+use crate::external::{cards, table_view, ExternalPlayer, Message};
+use crate::history::{settlement, Hand, HandHistory, Record};
 use crate::math::*;
 use crate::player::*;
-use crate::utils::pick_card;
+use crate::rules::Rules;
+use crate::save::RoundStart;
+use crate::server::RemoteSeats;
+use crate::stats::PlayerStats;
+use crate::strategy::Strategy;
+use crate::tui::{TableView, Tui};
+use crate::utils::{net_results, pick_card, reshuffle_if_needed, settle_surrenders, update_bank};
+
+/// The table a round is played at.
+pub struct Table<'a> {
+    pub limits: &'a TableLimits,
+    pub rules: &'a Rules,
+    /// The names of the players, without the dealer.
+    pub names: &'a [String],
+    /// The full-screen interface humans play with,
+    /// or None to play in the terminal line by line.
+    pub tui: Option<&'a Tui>,
+    /// The bets the players placed at the start of the
+    /// last round, which humans may bet again.
+    pub last_bets: &'a [u32],
+    /// The statistics of the session so far.
+    pub stats: &'a [PlayerStats],
+    /// The banks of the players at the start of the
+    /// game and after each round so far.
+    pub history: &'a [Vec<u32>],
+    /// The game as it was at the start of the round, which
+    /// `:save` saves, or None when the game cannot be saved.
+    pub saved: Option<&'a RoundStart<'a>>,
+    /// The hand history the events of the round
+    /// are recorded to, if there is one.
+    pub log: Option<&'a HandHistory>,
+    /// The programs playing the external seats, by seat,
+    /// None for the other seats.
+    pub externals: &'a [Option<ExternalPlayer>],
+    /// The server the remote players joined, which is
+    /// told the events of the round, if there is one.
+    pub server: Option<&'a dyn RemoteSeats>,
+}
+
+impl Table<'_> {
+    /// Tells the players about something, on the
+    /// full-screen interface if there is one.
+    fn notify(&self, text: &str) {
+        match self.tui {
+            Some(tui) => tui.notify(text),
+            None => println!("{}", text),
+        }
+    }
+
+    /// Records an event of the round to the hand history,
+    /// and tells it to the remote players.
+    fn record(&self, record: Record) {
+        if let Some(server) = self.server {
+            server.record(&record);
+        }
+        if let Some(log) = self.log {
+            log.record(record);
+        }
+    }
+
+    /// Draws the next card of the shoe. A shoe running out during
+    /// the round is replaced by a new one, which is recorded with
+    /// its order, but only told to the remote players as a shuffle.
+    fn draw(&self, pack: &mut Vec<Card>) -> Card {
+        let refilled = pack.is_empty();
+        let card = pick_card(pack, self.rules.num_packs);
+        if refilled {
+            let mut shoe = pack.clone();
+            shoe.push(card);
+            self.record_shuffle(&shoe);
+        }
+        card
+    }
+
+    /// Records that the shoe was shuffled, with the order of the
+    /// new `pack`, which only the hand history is told.
+    fn record_shuffle(&self, pack: &[Card]) {
+        if let Some(server) = self.server {
+            server.record(&Record::Shuffle);
+        }
+        if let Some(log) = self.log {
+            log.record(Record::Shuffle);
+            log.record(Record::Shoe(pack.iter().rev().copied().collect()));
+        }
+    }
+
+    /// The program playing a seat, which must be external.
+    fn external(&self, index: usize) -> &ExternalPlayer {
+        self.externals
+            .get(index)
+            .and_then(Option::as_ref)
+            .expect("External players have a program")
+    }
+
+    /// The server the remote players joined.
+    fn server(&self) -> &dyn RemoteSeats {
+        self.server.expect("Remote players join a server")
+    }
+
+    /// What the colon commands typed during the
+    /// round can see of the game.
+    fn session<'b>(&'b self, bank: &'b [u32], pack: &'b [Card]) -> Session<'b> {
+        Session {
+            names: self.names,
+            rules: self.rules,
+            limits: self.limits,
+            stats: self.stats,
+            history: self.history,
+            bank,
+            pack,
+            saved: self.saved,
+        }
+    }
+}
+
+/// Starts the round of the given `number` : reshuffles the shoe
+/// when its penetration was dealt, and records the start of
+/// the round and the new shoe.
+pub fn start_round(pack: &mut Vec<Card>, number: u32, table: &Table) {
+    let reshuffled = reshuffle_if_needed(pack, table.rules);
+    let size = table.rules.num_packs * 52;
+    table.record(Record::Round {
+        number,
+        position: size.saturating_sub(pack.len()),
+        size,
+    });
+    if reshuffled {
+        table.record_shuffle(pack);
+    }
+}
+
+/// How the hands of a round were settled.
+pub struct Settlement {
+    /// The scores of the hands, as in [compute_scores].
+    pub scores: Vec<(u32, Option<u32>)>,
+    /// The blackjacked, won, pushed and lost hands,
+    /// as in [compute_result].
+    pub results: [Vec<(usize, bool)>; 4],
+    /// The money each player won or lost.
+    pub nets: Vec<i64>,
+    /// The bets the players placed at the start of the round,
+    /// before their doubles, which humans may bet again.
+    pub last_bets: Vec<u32>,
+}
+
+/// What the players did with their hands during a round.
+pub struct Turns {
+    /// Whether the first and second hands of each player were doubled.
+    pub doubled: Vec<(bool, bool)>,
+    /// Whether each player surrendered their hand.
+    pub surrendered: Vec<bool>,
+}
+
+/// Settles a round played by [play_round] : pays the bets of the
+/// players back to their `bank` with their winnings, and half of the
+/// bet of a surrendered hand, records the outcome of each hand and
+/// the banks, and tells the betting policies what each player won
+/// or lost.
+pub fn settle_round(
+    player_hands: &[(Vec<Card>, Option<Vec<Card>>)],
+    dealer_hand: &[Card],
+    bets: &[u32],
+    turns: &Turns,
+    bank: &mut [u32],
+    bet_policies: &mut [BetPolicy],
+    table: &Table,
+) -> Settlement {
+    // each double doubled the bet placed at the start of the round
+    let last_bets = bets
+        .iter()
+        .zip(&turns.doubled)
+        .map(|(&bet, &(first, second))| bet >> (first as u32 + second as u32))
+        .collect();
+    let scores = compute_scores(player_hands, dealer_hand);
+    let bj_index = compute_blackjack_index(player_hands, dealer_hand);
+    let mut results = compute_result(scores.clone(), bj_index);
+    let stakes = settle_surrenders(
+        &mut results,
+        bets,
+        &turns.surrendered,
+        is_blackjack(dealer_hand),
+    );
+    for (seat, &stake) in stakes.iter().enumerate() {
+        bank[seat] += bets[seat] - stake;
+    }
+    let [three_two_index, winner_index, equal_index, loser_index] = &results;
+    update_bank(three_two_index, winner_index, equal_index, bank, &stakes);
+    for record in settlement(&results, player_hands, &stakes) {
+        table.record(record);
+    }
+    table.record(Record::Banks(bank.to_vec()));
+    let nets = net_results(three_two_index, winner_index, loser_index, &stakes);
+    for (policy, &net) in bet_policies.iter_mut().zip(&nets) {
+        policy.record(net);
+    }
+    Settlement {
+        scores,
+        results,
+        nets,
+        last_bets,
+    }
+}
 
 /// Plays a full round by dealing the cards and
 /// calling [play_turn] for each of the players.
+///
+/// Bots bet with their [BetPolicy] and play with their
+/// [Strategy], both given per player.
+///
+/// Returns, for each player, whether their first and
+/// second hands were doubled and whether they surrendered,
+/// or [Quit] when the humans ended the game during the round.
 pub fn play_round(
-    player_hands: &mut Vec<(Vec<Card>, Option<Vec<Card>>)>,
+    player_hands: &mut [(Vec<Card>, Option<Vec<Card>>)],
     dealer_hand: &mut Vec<Card>,
     pack: &mut Vec<Card>,
     player_types: &[PlayerType],
-    bets: &mut Vec<u32>,
-    bank: &mut Vec<u32>,
-) {
+    bets_and_bank: (&mut Vec<u32>, &mut Vec<u32>),
+    bots: (&mut [BetPolicy], &[Box<dyn Strategy>]),
+    table: &Table,
+) -> Result<Turns, Quit> {
+    let (bets, bank) = bets_and_bank;
+    let (bet_policies, strategies) = bots;
     //ask for bets
     for (index, typ) in player_types.iter().enumerate() {
         if index < player_hands.len() {
-            let mut try_bet = pick_bet(index, typ, bank[index]);
+            let policy = &mut bet_policies[index];
+            let table_bets = if table.limits.secret_bets {
+                &[]
+            } else {
+                &bets[..]
+            };
+            let mut try_bet = pick_bet(index, typ, bank, policy, pack, (table, table_bets))?;
             while try_bet > bank[index] {
-                try_bet = pick_bet(index, typ, bank[index]);
+                try_bet = pick_bet(index, typ, bank, policy, pack, (table, table_bets))?;
             }
             bets.push(try_bet);
             bank[index] -= try_bet;
+            table.record(Record::Bet {
+                seat: index,
+                amount: try_bet,
+            });
         }
     }
 
     //deal cards
-    for hand in player_hands.iter_mut() {
-        hand.0.push(pick_card(pack));
-        hand.0.push(pick_card(pack));
+    for (seat, hand) in player_hands.iter_mut().enumerate() {
+        for _ in 0..2 {
+            let card = table.draw(pack);
+            hand.0.push(card);
+            table.record(Record::Dealt {
+                hand: Hand::Player {
+                    seat,
+                    second: false,
+                },
+                card,
+            });
+        }
     }
-    dealer_hand.push(pick_card(pack));
+    let card = table.draw(pack);
+    dealer_hand.push(card);
+    table.record(Record::Dealt {
+        hand: Hand::Dealer,
+        card,
+    });
 
     //ask each player to play
+    let mut doubled = vec![(false, false); player_hands.len()];
+    let mut surrendered = vec![false; player_hands.len()];
     for (index, player_type) in player_types.iter().enumerate() {
-        play_turn(
+        let strategy = strategies.get(index).map(Box::as_ref);
+        let first = play_turn(
             player_hands,
             dealer_hand,
             pack,
-            index,
-            player_type,
+            (index, player_type, strategy),
             false,
             (bets, bank),
-        );
+            table,
+        )?;
+        if index < player_hands.len() {
+            doubled[index].0 = first == PlayerAction::Double;
+            surrendered[index] = first == PlayerAction::Surrender;
+        }
         //if player has split, play the split
         if index < player_hands.len() && player_hands[index].1.is_some() {
-            play_turn(
+            doubled[index].1 = play_turn(
                 player_hands,
                 dealer_hand,
                 pack,
-                index,
-                player_type,
+                (index, player_type, strategy),
                 true,
                 (bets, bank),
-            )
+                table,
+            )? == PlayerAction::Double;
         }
     }
+    Ok(Turns {
+        doubled,
+        surrendered,
+    })
 }
 
 /// Asks one player to play their turn,
 /// using repeated calls to the helper function [pick_action]
-/// until the action is Stand.
+/// until the action is Stand. Returns Double when the hand was
+/// doubled, Surrender when it was surrendered and Stand otherwise,
+/// or [Quit] when the humans ended the game.
 fn play_turn(
-    player_hands: &mut Vec<(Vec<Card>, Option<Vec<Card>>)>,
+    player_hands: &mut [(Vec<Card>, Option<Vec<Card>>)],
     dealer_hand: &mut Vec<Card>,
     pack: &mut Vec<Card>,
-    index: usize,
-    player_type: &PlayerType,
+    seat: (usize, &PlayerType, Option<&dyn Strategy>),
     is_second_turn: bool,
     bets_and_bank: (&mut Vec<u32>, &mut Vec<u32>),
-) {
+    table: &Table,
+) -> Result<PlayerAction, Quit> {
     let (bets, bank) = bets_and_bank;
-    let mut score = compute_scores(player_hands, dealer_hand);
+    let index = seat.0;
+    let mut ending = PlayerAction::Stand;
     let mut action = pick_action(
-        &score,
         player_hands,
         dealer_hand,
-        index,
+        pack,
+        seat,
         is_second_turn,
-        player_type,
         (bets, bank),
-    );
+        table,
+    )?;
     while action != PlayerAction::Stand {
         match action {
             PlayerAction::Hit => {
-                let new_card = pick_card(pack);
+                let new_card = table.draw(pack);
+                table.record(Record::Dealt {
+                    hand: recipient(player_hands, index, is_second_turn),
+                    card: new_card,
+                });
                 if index < player_hands.len() {
                     {
                         if is_second_turn {
@@ -98,17 +356,15 @@ fn play_turn(
                     dealer_hand.push(new_card);
                 }
 
-                score = compute_scores(player_hands, dealer_hand);
-
                 action = pick_action(
-                    &score,
                     player_hands,
                     dealer_hand,
-                    index,
+                    pack,
+                    seat,
                     is_second_turn,
-                    player_type,
                     (bets, bank),
-                );
+                    table,
+                )?;
             }
             PlayerAction::Split => {
                 let hand = player_hands.get_mut(index).unwrap();
@@ -116,19 +372,22 @@ fn play_turn(
                 hand.1 = Some(vec![card]);
                 bank[index] -= bets[index];
 
-                score = compute_scores(player_hands, dealer_hand);
                 action = pick_action(
-                    &score,
                     player_hands,
                     dealer_hand,
-                    index,
+                    pack,
+                    seat,
                     false,
-                    player_type,
                     (bets, bank),
-                );
+                    table,
+                )?;
             }
             PlayerAction::Double => {
-                let new_card = pick_card(pack);
+                let new_card = table.draw(pack);
+                table.record(Record::Dealt {
+                    hand: recipient(player_hands, index, is_second_turn),
+                    card: new_card,
+                });
                 {
                     if is_second_turn {
                         player_hands[index].1.as_mut().unwrap()
@@ -141,62 +400,173 @@ fn play_turn(
                 bank[index] -= bets[index];
                 bets[index] *= 2;
 
+                ending = PlayerAction::Double;
+                action = PlayerAction::Stand;
+            }
+            PlayerAction::Surrender => {
+                ending = PlayerAction::Surrender;
                 action = PlayerAction::Stand;
             }
             PlayerAction::Stand => unreachable!(),
         }
     }
+    Ok(ending)
+}
+
+/// The hand of the hand history played by the seat at `index`,
+/// which is the dealer after the players.
+fn recipient(
+    player_hands: &[(Vec<Card>, Option<Vec<Card>>)],
+    index: usize,
+    is_second: bool,
+) -> Hand {
+    if index < player_hands.len() {
+        Hand::Player {
+            seat: index,
+            second: is_second,
+        }
+    } else {
+        Hand::Dealer
+    }
 }
 
 /// Asks a player to give an action to follow until the action
-/// in question is legal, and returns it.
+/// in question is legal, and returns it, or [Quit] when the humans
+/// ended the game.
 ///
 /// Automatically Stands when the hand has more than 21 points.
+/// Split hands may only be doubled when the rules of the table
+/// allow doubling after a split, and the players may only surrender
+/// their first two cards when the rules offer late surrender.
 fn pick_action(
-    scores: &[(u32, Option<u32>)],
     player_hands: &[(Vec<Card>, Option<Vec<Card>>)],
     dealer_hand: &[Card],
-    index: usize,
+    pack: &[Card],
+    seat: (usize, &PlayerType, Option<&dyn Strategy>),
     is_second: bool,
-    player_type: &PlayerType,
     bets_and_bank: (&mut Vec<u32>, &mut Vec<u32>),
-) -> PlayerAction {
+    table: &Table,
+) -> Result<PlayerAction, Quit> {
     let (bets, bank) = bets_and_bank;
+    let (index, player_type, strategy) = seat;
+    let scores = &compute_scores(player_hands, dealer_hand);
+    let rules = table.rules;
+    let may_double = |index: usize| {
+        index < player_hands.len()
+            && bank[index] >= bets[index]
+            && (player_hands[index].1.is_none() || rules.double_after_split)
+    };
+    let is_legal = |action: PlayerAction| match action {
+        PlayerAction::Split => {
+            index < player_hands.len() //not the dealer
+                && player_hands[index].1.is_none() //not already split
+                && is_splittable(&player_hands[index].0)
+                && bank[index] >= bets[index]
+        }
+        PlayerAction::Double => {
+            may_double(index) //not the dealer
+                && {if is_second {scores[index].1.unwrap()} else {scores[index].0}} < 21
+        }
+        PlayerAction::Surrender => {
+            rules.surrender
+                && index < player_hands.len() //not the dealer
+                && player_hands[index].1.is_none() //not split
+                && player_hands[index].0.len() == 2
+        }
+        _ => true,
+    };
     if (!is_second && scores[index].0 >= 21) || (is_second && scores[index].1.unwrap() >= 21) {
-        PlayerAction::Stand
+        Ok(PlayerAction::Stand)
     } else {
+        let legal: Vec<PlayerAction> = [
+            PlayerAction::Hit,
+            PlayerAction::Stand,
+            PlayerAction::Double,
+            PlayerAction::Split,
+            PlayerAction::Surrender,
+        ]
+        .iter()
+        .copied()
+        .filter(|&action| is_legal(action))
+        .collect();
+        // the hand of a player being played
+        let played = || {
+            let hands = &player_hands[index];
+            match is_second {
+                true => hands.1.as_ref().unwrap(),
+                false => &hands.0,
+            }
+        };
         loop {
             // while action is illegal, try again
             let action = match player_type {
-                PlayerType::Dealer => dealer_play(scores, index),
-                PlayerType::Bot => bot_play(scores, is_second, index, bank[index] >= bets[index]),
-                PlayerType::Human => {
-                    human_play(scores, player_hands, dealer_hand, is_second, index)
-                }
-            };
-            match action {
-                PlayerAction::Split => {
-                    if player_hands[index].1.is_none() //not already split
-                        && index < player_hands.len() //not the dealer
-                        && is_splittable(&player_hands[index].0)
-                        && bank[index] >= bets[index]
-                    {
-                        return action;
+                PlayerType::Dealer => dealer_play(dealer_hand, rules),
+                PlayerType::Bot => {
+                    let hands = &player_hands[index];
+                    let hand = if is_second {
+                        hands.1.as_ref().unwrap()
                     } else {
-                    }
+                        &hands.0
+                    };
+                    bot_play(
+                        strategy.expect("Bots have a strategy"),
+                        hand,
+                        &dealer_hand[0],
+                        &legal,
+                        pack,
+                        rules,
+                    )
                 }
-                PlayerAction::Double => {
-                    if bank[index] >= bets[index]
-                        && index < player_hands.len() //not the dealer
-                        && {if is_second {scores[index].1.unwrap()} else {scores[index].0}} < 21
-                    {
-                        return action;
-                    } else {
-                    }
+                PlayerType::Human => match table.tui {
+                    Some(tui) => tui.ask_action(
+                        &TableView {
+                            names: table.names,
+                            hands: player_hands,
+                            dealer_hand,
+                            bets,
+                            bank,
+                            turn: Some((index, is_second)),
+                            nets: None,
+                        },
+                        &legal,
+                        &table.session(bank, pack),
+                    )?,
+                    None => human_play(
+                        scores,
+                        player_hands,
+                        dealer_hand,
+                        (index, is_second),
+                        &legal,
+                        &table.session(bank, pack),
+                    )?,
+                },
+                PlayerType::External => {
+                    let message = Message::Action {
+                        round: table.history.len(),
+                        hand: cards(played()),
+                        second: is_second,
+                        dealer: cards(dealer_hand),
+                        table: table_view(table.names, player_hands, bets),
+                        bank: bank[index],
+                        cards_left: pack.len(),
+                        legal: &legal,
+                    };
+                    table.external(index).action(&message, &legal)
+                }
+                PlayerType::Remote => {
+                    table
+                        .server()
+                        .action(index, (played(), is_second), dealer_hand, &legal)
                 }
-                _ => return action,
+            };
+            if is_legal(action) {
+                table.record(Record::Action {
+                    hand: recipient(player_hands, index, is_second),
+                    action,
+                });
+                return Ok(action);
             }
-            println!("Illegal action ! Try again:")
+            table.notify("Illegal action ! Try again:")
         }
     }
 }
@@ -204,23 +574,87 @@ fn pick_action(
 /// Asks for a betting amount from the player. Used
 /// at the beginning of the round.
 ///
-/// Bots always bet half of their bank reserve.
-/// Humans may pick a bet of their choosing.
+/// Bots bet according to their [BetPolicy], counting
+/// the cards that were dealt from the pack, and their bet is
+/// brought within the limits of the table if needed.
+/// Humans may pick a bet of their choosing, within the
+/// limits of the table, after seeing the bets already placed.
 /// This method does not check whether the player
 /// has enough resources to make such a bet, this is done
-/// at a higher-level.
-fn pick_bet(index: usize, player_type: &PlayerType, available: u32) -> u32 {
-    match player_type {
-        PlayerType::Bot => available >> 1,
-        PlayerType::Human => human_bet(index, available),
+/// at a higher-level. Returns [Quit] when the humans ended the game.
+fn pick_bet(
+    index: usize,
+    player_type: &PlayerType,
+    bank: &[u32],
+    policy: &mut BetPolicy,
+    pack: &[Card],
+    table: (&Table, &[u32]),
+) -> Result<u32, Quit> {
+    let (table, table_bets) = table;
+    let limits = table.limits;
+    let available = bank[index];
+    let last_bet = table.last_bets.get(index).copied();
+    let bet = match player_type {
+        PlayerType::Bot => {
+            let bet = policy.bet(&BetContext {
+                available,
+                dealt: &dealt_from_remaining(pack, table.rules.num_packs),
+                decks_remaining: pack.len() as f64 / 52.0,
+                limits,
+                table_bets,
+            });
+            if limits.allows(bet, available) {
+                bet
+            } else {
+                limits.clamp(bet as f64, available)
+            }
+        }
+        PlayerType::Human => match table.tui {
+            Some(tui) => tui.ask_bet(
+                &TableView {
+                    names: table.names,
+                    hands: &[],
+                    dealer_hand: &[],
+                    bets: table_bets,
+                    bank,
+                    turn: None,
+                    nets: None,
+                },
+                index,
+                (limits, last_bet),
+                &table.session(bank, pack),
+            )?,
+            None => loop {
+                let bet = human_bet(index, table_bets, last_bet, &table.session(bank, pack))?;
+                if limits.allows(bet, available) {
+                    return Ok(bet);
+                }
+                table.notify("This bet is not allowed at this table.");
+            },
+        },
+        PlayerType::External => table.external(index).bet(
+            &Message::Bet {
+                round: table.history.len(),
+                bank: available,
+                limits,
+                last_bet,
+                table_bets,
+                cards_left: pack.len(),
+            },
+            limits,
+            available,
+        ),
+        PlayerType::Remote => table.server().bet(index, limits, available, last_bet),
         PlayerType::Dealer => unreachable!("Dealer does not bet"),
-    }
+    };
+    Ok(bet)
 }
 
 #[cfg(test)]
 mod test {
     use crate::card::*;
     use crate::round::*;
+    use crate::strategy::{BasicStrategy, SimpleStrategy};
     #[test]
     fn play_round_test() {
         let card1 = Card {
@@ -233,12 +667,106 @@ mod test {
         };
 
         play_round(
-            &mut vec![(vec![card1], None), (vec![card2], None)],
+            &mut [(vec![card1], None), (vec![card2], None)],
             &mut vec![],
             &mut vec![],
-            &vec![PlayerType::Bot, PlayerType::Bot, PlayerType::Bot],
-            &mut vec![0, 0, 0],
-            &mut vec![0, 0, 0],
+            &[PlayerType::Bot, PlayerType::Bot, PlayerType::Dealer],
+            (&mut vec![0, 0, 0], &mut vec![0, 0, 0]),
+            (
+                &mut [BetPolicy::HalfBank, BetPolicy::HalfBank],
+                &[Box::new(SimpleStrategy), Box::new(BasicStrategy)],
+            ),
+            &Table {
+                limits: &TableLimits::default(),
+                rules: &Rules::default(),
+                names: &[String::from("Player 1"), String::from("Player 2")],
+                tui: None,
+                log: None,
+                last_bets: &[],
+                stats: &[],
+                history: &[],
+                saved: None,
+                externals: &[],
+                server: None,
+            },
         )
+        .unwrap();
+    }
+
+    #[test]
+    fn settle_round_test() {
+        let card = |rank| Card {
+            rank,
+            suit: Suit::Hearts,
+        };
+        // Ann doubled 10 on 11 and drew a 9, Bob stands on 18 with 25
+        let player_hands = [
+            (
+                vec![card(Rank::Six), card(Rank::Five), card(Rank::Nine)],
+                None,
+            ),
+            (vec![card(Rank::Ten), card(Rank::Eight)], None),
+        ];
+        let dealer_hand = [card(Rank::Ten), card(Rank::Nine)];
+        let mut bank = vec![80, 75];
+        let settled = settle_round(
+            &player_hands,
+            &dealer_hand,
+            &[20, 25],
+            &Turns {
+                doubled: vec![(true, false), (false, false)],
+                surrendered: vec![false, false],
+            },
+            &mut bank,
+            &mut [BetPolicy::HalfBank, BetPolicy::HalfBank],
+            &Table {
+                limits: &TableLimits::default(),
+                rules: &Rules::default(),
+                names: &[String::from("Ann"), String::from("Bob")],
+                tui: None,
+                log: None,
+                last_bets: &[],
+                stats: &[],
+                history: &[],
+                saved: None,
+                externals: &[],
+                server: None,
+            },
+        );
+        assert_eq!(vec![120, 75], bank);
+        assert_eq!(vec![20, -25], settled.nets);
+        assert_eq!(vec![10, 25], settled.last_bets);
+        assert_eq!(vec![(0, false)], settled.results[1]);
+
+        // Bob surrenders 16 on 25 and gets 12 back
+        let player_hands = [(vec![card(Rank::Ten), card(Rank::Six)], None)];
+        let mut bank = vec![75];
+        let settled = settle_round(
+            &player_hands,
+            &dealer_hand,
+            &[25],
+            &Turns {
+                doubled: vec![(false, false)],
+                surrendered: vec![true],
+            },
+            &mut bank,
+            &mut [BetPolicy::HalfBank],
+            &Table {
+                limits: &TableLimits::default(),
+                rules: &Rules::default(),
+                names: &[String::from("Bob")],
+                tui: None,
+                log: None,
+                last_bets: &[],
+                stats: &[],
+                history: &[],
+                saved: None,
+                externals: &[],
+                server: None,
+            },
+        );
+        assert_eq!(vec![87], bank);
+        assert_eq!(vec![-13], settled.nets);
+        assert_eq!(vec![(0, false)], settled.results[3]);
     }
 }
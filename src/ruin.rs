@@ -0,0 +1,288 @@
+use crate::betting::BetRamp;
+use crate::cli::{Args, RULE_OPTIONS};
+use crate::count::CountSystem;
+use crate::rules::Rules;
+use crate::shoe::Shoe;
+use crate::sim::{simulate_round_within, Accumulator};
+use crate::strategy::{strategy_from_name, Strategy};
+
+/// Everything that defines how a counting bot plays :
+/// its playing strategy, its bet ramp, the count it uses
+/// to pick its bets and the rules of the table.
+pub struct CountingBot<'a> {
+    pub strategy: &'a dyn Strategy,
+    pub ramp: &'a BetRamp,
+    pub count: &'a CountSystem,
+    pub rules: &'a Rules,
+}
+
+impl<'a> CountingBot<'a> {
+    /// Plays a round from the given shoe with the given `money`,
+    /// betting according to the true count but never more than the
+    /// money, which also limits doubles and splits.
+    /// Returns the net result of the round, in units.
+    fn play_round(&self, shoe: &mut Shoe, money: f64) -> f64 {
+        if shoe.needs_shuffle() {
+            shoe.shuffle();
+        }
+        let bet = (self.ramp.bet(self.count.true_count(shoe)) as f64).min(money);
+        bet * simulate_round_within(shoe, self.rules, self.strategy, money / bet)
+    }
+}
+
+/// Estimates the win rate of a bot, in units per round,
+/// by playing the given number of rounds with an unlimited bankroll.
+pub fn win_rate(bot: &CountingBot, rounds: u64, seed: u64) -> Accumulator {
+    let mut shoe = Shoe::new(bot.rules, seed);
+    let mut acc = Accumulator::default();
+    for _ in 0..rounds {
+        acc.add(bot.play_round(&mut shoe, f64::INFINITY));
+    }
+    acc
+}
+
+/// Estimates the probability of losing a whole bankroll within
+/// `horizon` rounds, by playing `trials` independent sessions.
+///
+/// A session is lost as soon as the bankroll cannot cover the
+/// smallest bet of the ramp. When the bankroll is smaller than the
+/// bet required by the ramp, the bot bets what is left, and it only
+/// doubles or splits when the bankroll covers the extra bet.
+/// The returned accumulator holds 1 for each lost session, 0 otherwise.
+pub fn simulated_risk(
+    bot: &CountingBot,
+    bankroll: f64,
+    horizon: u64,
+    trials: u64,
+    seed: u64,
+) -> Accumulator {
+    let min_bet = bot.ramp.min_bet() as f64;
+    let mut acc = Accumulator::default();
+    for trial in 0..trials {
+        let mut shoe = Shoe::new(bot.rules, seed.wrapping_add(trial));
+        let mut money = bankroll;
+        let mut rounds = 0;
+        while money >= min_bet && rounds < horizon {
+            money += bot.play_round(&mut shoe, money);
+            rounds += 1;
+        }
+        acc.add(if money < min_bet { 1.0 } else { 0.0 });
+    }
+    acc
+}
+
+/// The diffusion approximation of the risk of ruin over
+/// an unlimited number of rounds, for a win rate of `mean`
+/// units per round with the given variance.
+pub fn diffusion_risk(mean: f64, variance: f64, bankroll: f64) -> f64 {
+    if mean <= 0.0 {
+        1.0
+    } else {
+        (-2.0 * mean * bankroll / variance).exp()
+    }
+}
+
+/// The diffusion approximation of the risk of ruin within
+/// a given number of rounds.
+pub fn diffusion_risk_within(mean: f64, variance: f64, bankroll: f64, rounds: u64) -> f64 {
+    let n = rounds as f64;
+    let spread = (variance * n).sqrt();
+    let direct = log_normal_cdf((-bankroll - mean * n) / spread);
+    let reflected =
+        -2.0 * mean * bankroll / variance + log_normal_cdf((-bankroll + mean * n) / spread);
+    (direct.exp() + reflected.exp()).min(1.0)
+}
+
+/// The bankroll needed to keep the risk of ruin over an unlimited
+/// number of rounds at the target level, according to the diffusion
+/// approximation. Returns None when the bot has no edge.
+pub fn required_bankroll(mean: f64, variance: f64, target_risk: f64) -> Option<f64> {
+    if mean <= 0.0 {
+        None
+    } else {
+        Some(-variance * target_risk.ln() / (2.0 * mean))
+    }
+}
+
+/// N0 : the number of rounds after which the expected win
+/// equals one standard deviation of the result.
+pub fn n0(mean: f64, variance: f64) -> f64 {
+    variance / (mean * mean)
+}
+
+/// SCORE : the expected win per 100 rounds with a 10,000 units
+/// bankroll, when bets are scaled so that the risk of ruin is 13.5%.
+pub fn score(mean: f64, variance: f64) -> f64 {
+    1_000_000.0 * mean * mean / variance * mean.signum()
+}
+
+/// The logarithm of the cumulative distribution function of
+/// the standard normal distribution, which remains accurate far
+/// in the lower tail.
+fn log_normal_cdf(x: f64) -> f64 {
+    let z = x.abs() / std::f64::consts::SQRT_2;
+    // complementary error function, from Numerical Recipes
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -1.265_512_23
+        + t * (1.000_023_68
+            + t * (0.374_091_96
+                + t * (0.096_784_18
+                    + t * (-0.186_288_06
+                        + t * (0.278_868_07
+                            + t * (-1.135_203_98
+                                + t * (1.488_515_87 + t * (-0.822_152_23 + t * 0.170_872_77))))))));
+    let log_tail = 0.5f64.ln() + t.ln() - z * z + poly;
+    if x < 0.0 {
+        log_tail
+    } else {
+        (-log_tail.exp()).ln_1p()
+    }
+}
+
+/// Runs the `ruin` subcommand, which estimates the risk of ruin
+/// of a counting bot and the bankroll it needs.
+pub fn run(args: &Args) -> Result<(), String> {
+    args.check_options(&[
+        &[
+            "bankroll", "horizon", "ramp", "rounds", "seed", "strategy", "target", "trials",
+        ],
+        RULE_OPTIONS,
+    ])?;
+    let rules = args.rules()?;
+    let name = args.get("strategy").unwrap_or("basic");
+    let strategy = strategy_from_name(name)?;
+    let ramp = match args.get("ramp") {
+        Some(text) => BetRamp::parse(text)?,
+        None => BetRamp::flat(),
+    };
+    let count = CountSystem::hi_lo();
+    let bankroll = args.parse_or("bankroll", 200.0)?;
+    let target = args.parse_or("target", 0.05)?;
+    let rounds = args.parse_or("rounds", 1_000_000)?;
+    let horizon = args.parse_or("horizon", 10_000)?;
+    let trials = args.parse_or("trials", 500)?;
+    let seed = args.parse_or("seed", 0)?;
+    if !(0.0..1.0).contains(&target) || target == 0.0 {
+        return Err(String::from("The target risk must be between 0 and 1"));
+    }
+    if horizon == 0 {
+        return Err(String::from("The horizon must be at least one round"));
+    }
+
+    let bot = CountingBot {
+        strategy: strategy.as_ref(),
+        ramp: &ramp,
+        count: &count,
+        rules: &rules,
+    };
+    let rate = win_rate(&bot, rounds, seed);
+    let (mean, variance) = (rate.mean(), rate.variance());
+    if variance <= 0.0 {
+        return Err(String::from(
+            "The results of the bot do not vary, play more --rounds",
+        ));
+    }
+
+    println!(
+        "Rules : {}, strategy {}, {} bet ramp {}",
+        rules, name, count.name, ramp
+    );
+    println!(
+        "Win rate : {:+.4} ± {:.4} units per round, standard deviation {:.3} units ({} rounds)",
+        mean,
+        1.96 * rate.std_err(),
+        variance.sqrt(),
+        rounds
+    );
+    if mean > 0.0 {
+        println!("N0 : {:.0} rounds", n0(mean, variance));
+    }
+    println!("SCORE : {:.2}", score(mean, variance));
+    println!("\nWith a bankroll of {} units :", bankroll);
+    println!(
+        "Risk of ruin (diffusion, unlimited play) : {:.2}%",
+        100.0 * diffusion_risk(mean, variance, bankroll)
+    );
+    println!(
+        "Risk of ruin (diffusion, {} rounds) : {:.2}%",
+        horizon,
+        100.0 * diffusion_risk_within(mean, variance, bankroll, horizon)
+    );
+    let simulated = simulated_risk(&bot, bankroll, horizon, trials, seed.wrapping_add(1));
+    println!(
+        "Risk of ruin (simulated, {} rounds, {} sessions) : {:.2}% ± {:.2}%",
+        horizon,
+        trials,
+        100.0 * simulated.mean(),
+        100.0 * 1.96 * simulated.std_err()
+    );
+    match required_bankroll(mean, variance, target) {
+        Some(needed) => println!(
+            "\nBankroll needed for a {}% risk of ruin : {:.0} units",
+            100.0 * target,
+            needed.ceil()
+        ),
+        None => println!("\nNo bankroll is large enough without a positive edge."),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ruin::*;
+    use crate::strategy::BasicStrategy;
+
+    #[test]
+    fn closed_forms() {
+        let (mean, variance) = (0.02, 1.3);
+        let bankroll = required_bankroll(mean, variance, 0.05).unwrap();
+        assert!((diffusion_risk(mean, variance, bankroll) - 0.05).abs() < 1e-9);
+        assert_eq!(None, required_bankroll(-0.01, variance, 0.05));
+        assert_eq!(1.0, diffusion_risk(-0.01, variance, bankroll));
+        assert!((n0(mean, variance) - 3250.0).abs() < 1e-6);
+        assert!((score(mean, variance) - 1_000_000.0 / 3250.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn finite_horizon_risk() {
+        let (mean, variance, bankroll) = (0.02, 1.3, 50.0);
+        let unlimited = diffusion_risk(mean, variance, bankroll);
+        let short = diffusion_risk_within(mean, variance, bankroll, 100);
+        let long = diffusion_risk_within(mean, variance, bankroll, 10_000_000);
+        assert!(short < long && long <= unlimited + 1e-6);
+        assert!((long - unlimited).abs() < 1e-4);
+        // a losing bot is almost surely ruined in the long run
+        assert!(diffusion_risk_within(-0.1, 1.3, 10.0, 100_000) > 0.999);
+    }
+
+    #[test]
+    fn log_normal_cdf_test() {
+        assert!((log_normal_cdf(0.0).exp() - 0.5).abs() < 1e-7);
+        assert!((log_normal_cdf(1.96).exp() - 0.975).abs() < 1e-4);
+        assert!((log_normal_cdf(-1.96).exp() - 0.025).abs() < 1e-4);
+        assert!(log_normal_cdf(-40.0).is_finite());
+    }
+
+    #[test]
+    fn simulated_risk_test() {
+        let rules = Rules::default();
+        let ramp = BetRamp::flat();
+        let count = CountSystem::hi_lo();
+        let bot = CountingBot {
+            strategy: &BasicStrategy,
+            ramp: &ramp,
+            count: &count,
+            rules: &rules,
+        };
+        // a single unit is lost quickly, a huge bankroll is not
+        assert!(simulated_risk(&bot, 1.0, 1000, 20, 0).mean() > 0.9);
+        assert_eq!(0.0, simulated_risk(&bot, 1e6, 100, 5, 0).mean());
+    }
+
+    #[test]
+    fn invalid_options() {
+        let run_with = |line: &str| run(&Args::parse(line.split_whitespace().map(String::from)));
+        assert!(run_with("ruin --horizon 0").is_err());
+        assert!(run_with("ruin --rounds 1").is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use crate::math::NUM_PACKS;
+
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// The set of table rules a round of blackjack is played with.
+///
+/// The [Default] implementation corresponds to the variant
+/// described in the README : four packs, the dealer stands
+/// on soft 17, doubling after a split is allowed, and there
+/// is neither surrender nor insurance.
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+pub struct Rules {
+    /// The number of 52-card packs in the shoe.
+    pub num_packs: usize,
+    /// Whether the dealer hits a soft 17 (H17) or stands on it (S17).
+    pub dealer_hits_soft_17: bool,
+    /// Whether a player may double down on a split hand.
+    pub double_after_split: bool,
+    /// Whether a player may give up half of their bet instead
+    /// of playing their first two cards.
+    pub surrender: bool,
+    /// Whether a player may insure their bet against a dealer
+    /// blackjack when the dealer shows an Ace.
+    pub insurance: bool,
+    /// The fraction of the shoe dealt before it is reshuffled,
+    /// between 0 and 1.
+    pub penetration: f64,
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules {
+            num_packs: NUM_PACKS,
+            dealer_hits_soft_17: false,
+            double_after_split: true,
+            surrender: false,
+            insurance: false,
+            penetration: 0.75,
+        }
+    }
+}
+
+impl std::fmt::Display for Rules {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}D {} {}{}{}",
+            self.num_packs,
+            if self.dealer_hits_soft_17 {
+                "H17"
+            } else {
+                "S17"
+            },
+            if self.double_after_split {
+                "DAS"
+            } else {
+                "NDAS"
+            },
+            if self.surrender { " LS" } else { "" },
+            if self.insurance { " INS" } else { "" },
+        )
+    }
+}
+
+/// Parses the rules as they are displayed, as in `4D S17 DAS LS`.
+/// The penetration, which is not displayed, is the default one.
+impl FromStr for Rules {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Rules, String> {
+        let invalid = || format!("Invalid rules '{}'", text);
+        let mut words = text.split_whitespace();
+        let num_packs = words
+            .next()
+            .and_then(|word| word.strip_suffix('D'))
+            .and_then(|packs| packs.parse().ok())
+            .ok_or_else(invalid)?;
+        let mut rules = Rules {
+            num_packs,
+            ..Rules::default()
+        };
+        for word in words {
+            match word {
+                "H17" => rules.dealer_hits_soft_17 = true,
+                "S17" => rules.dealer_hits_soft_17 = false,
+                "DAS" => rules.double_after_split = true,
+                "NDAS" => rules.double_after_split = false,
+                "LS" => rules.surrender = true,
+                "INS" => rules.insurance = true,
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(rules)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rules::*;
+
+    #[test]
+    fn default_matches_readme() {
+        let rules = Rules::default();
+        assert_eq!(4, rules.num_packs);
+        assert!(!rules.dealer_hits_soft_17);
+        assert!(rules.double_after_split);
+        assert!(!rules.surrender && !rules.insurance);
+    }
+
+    #[test]
+    fn rules_display() {
+        assert_eq!("4D S17 DAS", &format!("{}", Rules::default()));
+        let rules = Rules {
+            surrender: true,
+            insurance: true,
+            ..Rules::default()
+        };
+        assert_eq!("4D S17 DAS LS INS", &format!("{}", rules));
+    }
+
+    #[test]
+    fn rules_parse() {
+        let rules = Rules {
+            num_packs: 6,
+            dealer_hits_soft_17: true,
+            double_after_split: false,
+            surrender: true,
+            ..Rules::default()
+        };
+        assert_eq!(Ok(rules.clone()), rules.to_string().parse());
+        assert_eq!(Ok(Rules::default()), "4D S17 DAS".parse());
+        assert!("4 S17 DAS".parse::<Rules>().is_err());
+        assert!("4D S17 RSA".parse::<Rules>().is_err());
+    }
+}
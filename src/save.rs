@@ -0,0 +1,461 @@
+use crate::art::suit_symbol;
+use crate::betting::{BetPolicy, TableLimits};
+use crate::card::Card;
+use crate::player::PlayerType;
+use crate::rules::Rules;
+use crate::setup::{parse_player, GameSetup};
+use crate::stats::PlayerStats;
+use crate::strategy::strategy_from_name;
+use crate::utils::{init_game, set_shuffler, shuffler};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+/// The version of the save files written by the game. Files
+/// of other versions are refused rather than misread.
+pub const SAVE_VERSION: u32 = 2;
+
+/// The file `:save` writes to when it is not given one.
+pub const DEFAULT_SAVE_FILE: &str = "blackjack-save.toml";
+
+/// What changes in a game from round to round.
+#[derive(Debug)]
+pub struct GameState {
+    /// The hands of the players, empty between rounds.
+    pub player_hands: Vec<(Vec<Card>, Option<Vec<Card>>)>,
+    /// The cards left in the shoe, the next one last.
+    pub pack: Vec<Card>,
+    /// The money of each player.
+    pub bank: Vec<u32>,
+    pub bet_policies: Vec<BetPolicy>,
+    pub stats: Vec<PlayerStats>,
+    /// The banks of the players at the start of the
+    /// game and after each round.
+    pub history: Vec<Vec<u32>>,
+    /// The bets the players placed at the start of the last round.
+    pub last_bets: Vec<u32>,
+}
+
+impl GameState {
+    /// The state of a new game : a shuffled shoe, and the
+    /// bankroll of the setup for each player.
+    pub fn new(setup: &GameSetup) -> GameState {
+        let mut player_hands = Vec::new();
+        let mut pack = Vec::new();
+        let mut bank = Vec::new();
+        init_game(
+            &mut player_hands,
+            &mut pack,
+            &mut bank,
+            setup.names.len(),
+            setup.bankroll,
+            &setup.rules,
+        );
+        GameState {
+            player_hands,
+            pack,
+            history: vec![bank.clone()],
+            bank,
+            bet_policies: setup.bet_policies.clone(),
+            stats: vec![PlayerStats::default(); setup.names.len()],
+            last_bets: Vec::new(),
+        }
+    }
+}
+
+/// A game saved between two rounds, written as a TOML file :
+///
+/// ```toml
+/// version = 2
+/// seed = "42"
+/// history = [[300, 300], [275, 310]]
+/// shoe = "7D 10S AH ..."
+///
+/// [rules]
+/// num_packs = 4
+/// ...
+///
+/// [shuffler]
+/// seed = "1f0c..."
+/// stream = 0
+/// word_pos = "1024"
+///
+/// [[seats]]
+/// player = "human"
+/// name = "Ann"
+/// ...
+/// ```
+///
+/// The banks of the players are the last ones of the `history`,
+/// and the number of rounds played is the length of the history
+/// minus one. The `shoe` lists the cards left, the next one last,
+/// which also gives the position of the shoe. The `shuffler` is
+/// the state of the generator that shuffles the next shoes.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SavedGame {
+    pub version: u32,
+    pub table_name: Option<String>,
+    /// The number of rounds of the game, or None to play
+    /// until one of the players runs out of money.
+    pub rounds: Option<u32>,
+    /// The seed the game started from, written in
+    /// decimal as TOML integers are signed.
+    pub seed: Option<String>,
+    pub history: Vec<Vec<u32>>,
+    pub shoe: String,
+    pub rules: Rules,
+    pub limits: TableLimits,
+    pub shuffler: SavedShuffler,
+    pub seats: Vec<SavedSeat>,
+}
+
+/// The state of the generator of the shuffles, as in
+/// [ChaCha8Rng]. TOML integers have 64 bits, so the seed is
+/// written in hexadecimal and the word position in decimal.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SavedShuffler {
+    pub seed: String,
+    pub stream: u64,
+    pub word_pos: String,
+}
+
+/// A seat of a saved game.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SavedSeat {
+    /// `human`, `bot`, `external` or `remote`.
+    pub player: String,
+    pub name: String,
+    /// The strategy of the player, used by bots only.
+    pub strategy: String,
+    pub last_bet: Option<u32>,
+    /// The betting policy of the player, with its
+    /// progression, used by bots only.
+    pub betting: BetPolicy,
+    pub stats: PlayerStats,
+    /// The command starting the program of an external player.
+    pub command: Option<String>,
+}
+
+/// The game at the start of a round, as `:save` saves it : what the
+/// round changes is copied, and the [SavedGame] is only built when
+/// the command runs, from this and the statistics and the history of
+/// the banks, which do not change during a round.
+pub struct RoundStart<'a> {
+    setup: &'a GameSetup,
+    pack: Vec<Card>,
+    bet_policies: Vec<BetPolicy>,
+    last_bets: Vec<u32>,
+    shuffler: ChaCha8Rng,
+}
+
+impl<'a> RoundStart<'a> {
+    /// The game before its next round, along with
+    /// the current state of the generator of the shuffles.
+    pub fn new(setup: &'a GameSetup, state: &GameState) -> RoundStart<'a> {
+        RoundStart {
+            setup,
+            pack: state.pack.clone(),
+            bet_policies: state.bet_policies.clone(),
+            last_bets: state.last_bets.clone(),
+            shuffler: shuffler(),
+        }
+    }
+
+    /// The saved game, given the statistics of
+    /// the players and the history of their banks.
+    pub fn saved_game(&self, stats: &[PlayerStats], history: &[Vec<u32>]) -> SavedGame {
+        let (setup, rng) = (self.setup, &self.shuffler);
+        let seats = (0..setup.names.len())
+            .map(|index| SavedSeat {
+                player: setup.player_types[index].to_string(),
+                name: setup.names[index].clone(),
+                strategy: setup.strategies[index].clone(),
+                last_bet: self.last_bets.get(index).copied(),
+                betting: self.bet_policies[index].clone(),
+                stats: stats[index].clone(),
+                command: setup.commands[index].clone(),
+            })
+            .collect();
+        SavedGame {
+            version: SAVE_VERSION,
+            table_name: setup.table_name.clone(),
+            rounds: setup.rounds,
+            seed: setup.seed.map(|seed| seed.to_string()),
+            history: history.to_vec(),
+            shoe: self
+                .pack
+                .iter()
+                .map(|card| format!("{}{}", card.rank, suit_symbol(card.suit, false)))
+                .collect::<Vec<_>>()
+                .join(" "),
+            rules: setup.rules.clone(),
+            limits: setup.limits.clone(),
+            shuffler: SavedShuffler {
+                seed: rng
+                    .get_seed()
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect(),
+                stream: rng.get_stream(),
+                word_pos: rng.get_word_pos().to_string(),
+            },
+            seats,
+        }
+    }
+}
+
+/// Only the version, read first to refuse the
+/// files of other versions with a clear message.
+#[derive(Deserialize)]
+struct Version {
+    version: u32,
+}
+
+impl SavedGame {
+    /// Parses a saved game, refusing the other versions of the format.
+    pub fn parse(text: &str) -> Result<SavedGame, String> {
+        let version: Version = toml::from_str(text).map_err(|e| e.to_string())?;
+        if version.version != SAVE_VERSION {
+            return Err(format!(
+                "The save file is of version {}, this game reads version {}",
+                version.version, SAVE_VERSION
+            ));
+        }
+        toml::from_str(text).map_err(|e| e.to_string())
+    }
+
+    /// Loads a saved game from a file.
+    pub fn load(path: &str) -> Result<SavedGame, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read the saved game {} : {}", path, e))?;
+        SavedGame::parse(&text).map_err(|e| format!("{} : {}", path, e))
+    }
+
+    /// Writes the game to a file.
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let text = toml::to_string(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, text).map_err(|e| format!("Cannot save the game to {} : {}", path, e))
+    }
+
+    /// The setup and the state of the saved game, ready to play its
+    /// next round once the generator of the shuffles is put back in
+    /// its saved state by [SavedGame::restore_shuffler].
+    pub fn restore(&self) -> Result<(GameSetup, GameState), String> {
+        let num_players = self.seats.len();
+        if num_players == 0 {
+            return Err(String::from("seats : a game needs at least one player"));
+        }
+        if self.history.is_empty() || self.history.iter().any(|b| b.len() != num_players) {
+            return Err(String::from(
+                "history : expected the bank of each seat after each round",
+            ));
+        }
+        if self
+            .rounds
+            .is_some_and(|rounds| (rounds as usize) < self.history.len() - 1)
+        {
+            return Err(format!(
+                "rounds : the game already played {} rounds",
+                self.history.len() - 1
+            ));
+        }
+        let mut player_types = Vec::new();
+        for (index, seat) in self.seats.iter().enumerate() {
+            let key = format!("seats[{}]", index + 1);
+            player_types
+                .push(parse_player(&seat.player).map_err(|e| format!("{}.player : {}", key, e))?);
+            strategy_from_name(&seat.strategy).map_err(|e| format!("{}.strategy : {}", key, e))?;
+        }
+        player_types.push(PlayerType::Dealer);
+        let pack = self
+            .shoe
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<Vec<Card>, _>>()
+            .map_err(|e| format!("shoe : {}", e))?;
+        let seed = match &self.seed {
+            Some(seed) => Some(
+                seed.parse()
+                    .map_err(|_| String::from("seed : invalid value"))?,
+            ),
+            None => None,
+        };
+        let bank = self.history[self.history.len() - 1].clone();
+        let bet_policies: Vec<BetPolicy> =
+            self.seats.iter().map(|seat| seat.betting.clone()).collect();
+
+        let setup = GameSetup {
+            table_name: self.table_name.clone(),
+            player_types,
+            names: self.seats.iter().map(|seat| seat.name.clone()).collect(),
+            strategies: self
+                .seats
+                .iter()
+                .map(|seat| seat.strategy.clone())
+                .collect(),
+            bet_policies: bet_policies.clone(),
+            commands: self.seats.iter().map(|seat| seat.command.clone()).collect(),
+            bankroll: self.history[0][0],
+            rounds: self.rounds,
+            seed,
+            rules: self.rules.clone(),
+            limits: self.limits.clone(),
+        };
+        let state = GameState {
+            player_hands: vec![(Vec::new(), None); num_players],
+            pack,
+            bank,
+            bet_policies,
+            stats: self.seats.iter().map(|seat| seat.stats.clone()).collect(),
+            history: self.history.clone(),
+            last_bets: self
+                .seats
+                .iter()
+                .map(|seat| seat.last_bet)
+                .collect::<Option<_>>()
+                .unwrap_or_default(),
+        };
+        Ok((setup, state))
+    }
+
+    /// Puts the generator of the shuffles back in its saved state.
+    pub fn restore_shuffler(&self) -> Result<(), String> {
+        let saved = &self.shuffler;
+        let invalid = |key: &str| format!("shuffler.{} : invalid value", key);
+        if saved.seed.len() != 64 || !saved.seed.is_ascii() {
+            return Err(invalid("seed"));
+        }
+        let mut seed = [0; 32];
+        for (index, byte) in seed.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&saved.seed[2 * index..2 * index + 2], 16)
+                .map_err(|_| invalid("seed"))?;
+        }
+        let word_pos = saved.word_pos.parse().map_err(|_| invalid("word_pos"))?;
+        let mut rng = ChaCha8Rng::from_seed(seed);
+        rng.set_stream(saved.stream);
+        rng.set_word_pos(word_pos);
+        set_shuffler(rng);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::betting::KellyBetting;
+    use crate::progression::{Progression, System};
+    use crate::save::*;
+    use crate::utils::{pick_card, seed_shuffles};
+
+    fn setup() -> GameSetup {
+        GameSetup {
+            table_name: Some(String::from("Test")),
+            player_types: vec![PlayerType::Human, PlayerType::Bot, PlayerType::Dealer],
+            names: vec![String::from("Ann"), String::from("Bob")],
+            strategies: vec![String::from("simple"), String::from("basic")],
+            bet_policies: vec![
+                BetPolicy::Kelly(KellyBetting::default()),
+                BetPolicy::Progression(Progression::new(
+                    System::from_name("labouchere").unwrap(),
+                    Some(5),
+                )),
+            ],
+            commands: vec![None, None],
+            bankroll: 300,
+            rounds: Some(20),
+            seed: Some(u64::MAX),
+            rules: Rules::default(),
+            limits: TableLimits::default(),
+        }
+    }
+
+    #[test]
+    fn save_and_restore() {
+        seed_shuffles(4);
+        let setup = setup();
+        let mut state = GameState::new(&setup);
+        for _ in 0..10 {
+            pick_card(&mut state.pack, setup.rules.num_packs);
+        }
+        state.bank = vec![250, 320];
+        state.history.push(state.bank.clone());
+        state.stats[1].wins = 1;
+        state.last_bets = vec![50, 20];
+        if let BetPolicy::Progression(progression) = &mut state.bet_policies[1] {
+            progression.record(-5);
+        }
+
+        let start = RoundStart::new(&setup, &state);
+        // the round being played does not change the saved game
+        let pack = std::mem::take(&mut state.pack);
+        let text = toml::to_string(&start.saved_game(&state.stats, &state.history)).unwrap();
+        state.pack = pack;
+        let saved = SavedGame::parse(&text).unwrap();
+        let (restored_setup, restored) = saved.restore().unwrap();
+        assert_eq!(setup.names, restored_setup.names);
+        assert_eq!(setup.player_types, restored_setup.player_types);
+        assert_eq!(setup.strategies, restored_setup.strategies);
+        assert_eq!(setup.commands, restored_setup.commands);
+        assert_eq!(
+            (Some(20), 300, Some(u64::MAX)),
+            (
+                restored_setup.rounds,
+                restored_setup.bankroll,
+                restored_setup.seed
+            )
+        );
+        assert_eq!(state.pack, restored.pack);
+        assert_eq!(vec![250, 320], restored.bank);
+        assert_eq!(state.history, restored.history);
+        assert_eq!(state.stats, restored.stats);
+        assert_eq!(state.last_bets, restored.last_bets);
+        assert!(matches!(
+            (&state.bet_policies[1], &restored.bet_policies[1]),
+            (BetPolicy::Progression(a), BetPolicy::Progression(b)) if a == b
+        ));
+
+        // the next shoes are shuffled as they would have been
+        let next: Vec<Card> = (0..400).map(|_| pick_card(&mut state.pack, 4)).collect();
+        seed_shuffles(99);
+        saved.restore_shuffler().unwrap();
+        let mut pack = restored.pack;
+        let replayed: Vec<Card> = (0..400).map(|_| pick_card(&mut pack, 4)).collect();
+        assert_eq!(next, replayed);
+    }
+
+    #[test]
+    fn invalid_saves() {
+        seed_shuffles(4);
+        let setup = setup();
+        let state = GameState::new(&setup);
+        let saved = RoundStart::new(&setup, &state).saved_game(&state.stats, &state.history);
+        let text = toml::to_string(&saved).unwrap();
+        let error = SavedGame::parse(&text.replace("version = 2", "version = 1")).unwrap_err();
+        assert!(error.contains("version 1"), "{}", error);
+        let saved = SavedGame::parse(&text.replacen("AH", "XH", 1)).unwrap();
+        assert_eq!("shoe : Invalid card 'XH'", saved.restore().unwrap_err());
+        let saved = SavedGame::parse(&text.replace("\"human\"", "\"robot\"")).unwrap();
+        assert!(saved
+            .restore()
+            .unwrap_err()
+            .starts_with("seats[1].player : "));
+        assert!(SavedGame::parse(&format!("cheat = true\n{}", text)).is_err());
+
+        let mut saved = SavedGame::parse(&text).unwrap();
+        saved.history.push(vec![250, 350]);
+        saved.history.push(vec![200, 400]);
+        saved.rounds = Some(1);
+        assert_eq!(
+            "rounds : the game already played 2 rounds",
+            saved.restore().unwrap_err()
+        );
+        saved.shuffler.seed = "é".repeat(32);
+        assert_eq!(
+            "shuffler.seed : invalid value",
+            saved.restore_shuffler().unwrap_err()
+        );
+    }
+}
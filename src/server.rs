@@ -0,0 +1,483 @@
+//! Tables served over TCP : `serve` plays a game whose `remote`
+//! seats are taken by players joining from other terminals, with
+//! `nc` or `telnet`, over a protocol of text lines.
+//!
+//! A player who connects is asked their name, and takes the next
+//! free remote seat. The game starts when every remote seat is
+//! taken. Every player then sees the events of each round as they
+//! are written in hand histories ([crate::history]), as in
+//! `Ann: dealt 9C` or `Bob: hits`, and is asked for their bet and
+//! their actions on their turn. Bets are typed as at the terminal,
+//! and actions as `hit`, `stand`, `double`, `split` and `surrender`
+//! or their shortcuts `h`, `s`, `d`, `p` and `r`.
+//!
+//! ```text
+//! Ann, you have 300 units. Bets go from 1 to 1000, by 1. Your bet ?
+//! 10
+//! Ann: bets 10
+//! Ann: dealt 9C
+//! Dealer: dealt 7S
+//! Ann: dealt 2H
+//! Ann, your hand 9C 2H (11) against the dealer's 7S : hit, stand or double ?
+//! d
+//! ```
+//!
+//! A player who disconnects stays seated : they bet the smallest
+//! bet allowed and stand on every hand until the game ends. A player
+//! who does not answer within `--remote-timeout` seconds bets the
+//! smallest bet or stands for this question only.
+
+use crate::betting::TableLimits;
+use crate::card::Card;
+use crate::external::read_lines;
+use crate::history::{card_code, Record};
+use crate::math::hand_value;
+use crate::player::{action_help, parse_bet, parse_turn_input, PlayerAction, TurnInput};
+use crate::setup::check_name;
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+/// The port `serve` listens on, unless `--port` says otherwise.
+pub const DEFAULT_PORT: u16 = 7777;
+
+/// The address `serve` listens on, unless `--bind` says otherwise :
+/// only the players of this machine can join.
+pub const DEFAULT_BIND: &str = "127.0.0.1";
+
+/// The seconds remote players have to answer,
+/// unless `--remote-timeout` says otherwise.
+pub const DEFAULT_REMOTE_TIMEOUT: f64 = 60.0;
+
+/// The connection of a remote player.
+struct Client {
+    stream: TcpStream,
+    /// The lines the player types, as in [read_lines].
+    lines: Receiver<String>,
+}
+
+impl Client {
+    fn connect(stream: TcpStream) -> Result<Client, String> {
+        let input = stream.try_clone().map_err(|e| e.to_string())?;
+        Ok(Client {
+            stream,
+            lines: read_lines(input),
+        })
+    }
+
+    fn send(&mut self, text: &str) -> std::io::Result<()> {
+        self.stream.write_all(format!("{}\n", text).as_bytes())
+    }
+
+    /// Sends a question and waits for the answer, at most for
+    /// the `timeout`. Fails with [RecvTimeoutError::Disconnected]
+    /// when the player is disconnected.
+    fn ask(&mut self, question: &str, timeout: Duration) -> Result<String, RecvTimeoutError> {
+        // what was typed before the question
+        while self.lines.try_recv().is_ok() {}
+        self.send(question)
+            .map_err(|_| RecvTimeoutError::Disconnected)?;
+        self.lines.recv_timeout(timeout)
+    }
+}
+
+impl Drop for Client {
+    /// Closes the connection, which also stops the thread reading it.
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// The players of the remote seats of a table, however they
+/// reach it : over TCP with [Server], or over HTTP with
+/// [crate::api].
+pub trait RemoteSeats {
+    /// Tells the players an event of the game.
+    fn record(&self, record: &Record);
+
+    /// Asks the player of a seat for their bet, which
+    /// must be allowed by the `limits` of the table.
+    fn bet(&self, seat: usize, limits: &TableLimits, available: u32, last_bet: Option<u32>) -> u32;
+
+    /// Asks the player of a seat for their action on a hand
+    /// against the `dealer_hand`, among the `legal` ones.
+    fn action(
+        &self,
+        seat: usize,
+        hand: (&[Card], bool),
+        dealer_hand: &[Card],
+        legal: &[PlayerAction],
+    ) -> PlayerAction;
+}
+
+/// The remote players of a table served over TCP.
+pub struct Server {
+    names: Vec<String>,
+    /// How long the players have to answer.
+    timeout: Duration,
+    /// The connections, by seat : None for the seats
+    /// that are not remote and the players who left.
+    clients: RefCell<Vec<Option<Client>>>,
+}
+
+/// Joins actions as in `hit, stand or double`.
+fn list(actions: &[PlayerAction]) -> String {
+    let names: Vec<String> = actions
+        .iter()
+        .map(|action| format!("{:?}", action).to_lowercase())
+        .collect();
+    match names.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, others)) => format!("{} or {}", others.join(", "), last),
+        None => String::new(),
+    }
+}
+
+impl Server {
+    /// Waits for the players of the remote `seats` to connect
+    /// to the `listener`, in turn, and asks them their name,
+    /// which replaces the one of their seat in `names`.
+    /// The players who connect first read the `welcome`
+    /// message, the rules of the table for example. Players
+    /// have `timeout` to answer each question, or lose their
+    /// seat while the table waits for them.
+    pub fn open(
+        listener: &TcpListener,
+        names: &mut [String],
+        seats: &[usize],
+        welcome: &str,
+        timeout: Duration,
+    ) -> Result<Server, String> {
+        let mut server = Server {
+            names: names.to_vec(),
+            timeout,
+            clients: RefCell::new(names.iter().map(|_| None).collect()),
+        };
+        for (joined, &seat) in seats.iter().enumerate() {
+            let (client, name) = loop {
+                let (stream, _) = listener.accept().map_err(|e| e.to_string())?;
+                let mut client = Client::connect(stream)?;
+                let _ = client.send(welcome);
+                let mut question = format!("Your name ? (ENTER for {})", names[seat]);
+                let name = loop {
+                    let name = match client.ask(&question, timeout) {
+                        Ok(name) => name.trim().to_string(),
+                        Err(_) => break None,
+                    };
+                    if name.is_empty() {
+                        break Some(name);
+                    }
+                    let others: Vec<String> = (names.iter().enumerate())
+                        .filter(|&(other, _)| other != seat)
+                        .map(|(_, other_name)| other_name.clone())
+                        .collect();
+                    match check_name(&name, &others) {
+                        Ok(()) => break Some(name),
+                        Err(message) => question = format!("{}. Your name ?", message),
+                    }
+                };
+                // the players who leave, or take too long, before
+                // giving their name free the seat
+                if let Some(name) = name {
+                    break (client, name);
+                }
+            };
+            if !name.is_empty() {
+                names[seat] = name;
+                server.names[seat] = names[seat].clone();
+            }
+            server.clients.borrow_mut()[seat] = Some(client);
+            let waiting = seats.len() - joined - 1;
+            server.broadcast(&format!(
+                "{} takes seat {}. {}",
+                names[seat],
+                seat + 1,
+                match waiting {
+                    0 => String::from("The game starts."),
+                    1 => String::from("Waiting for 1 more player."),
+                    _ => format!("Waiting for {} more players.", waiting),
+                }
+            ));
+        }
+        Ok(server)
+    }
+
+    /// Sends a line to every connected player, and to
+    /// the terminal of the server.
+    pub fn broadcast(&self, text: &str) {
+        println!("{}", text);
+        let mut left = Vec::new();
+        for (seat, client) in self.clients.borrow_mut().iter_mut().enumerate() {
+            if client
+                .as_mut()
+                .is_some_and(|client| client.send(text).is_err())
+            {
+                *client = None;
+                left.push(seat);
+            }
+        }
+        for seat in left {
+            self.leave(seat);
+        }
+    }
+
+    /// Sends a line to the player of a seat.
+    fn tell(&self, seat: usize, text: &str) {
+        let sent = self.clients.borrow_mut()[seat]
+            .as_mut()
+            .map_or(true, |client| client.send(text).is_ok());
+        if !sent {
+            self.clients.borrow_mut()[seat] = None;
+            self.leave(seat);
+        }
+    }
+
+    /// Asks the player of a seat a question, or returns None when
+    /// they are disconnected or do not answer in time.
+    fn ask(&self, seat: usize, question: &str) -> Option<String> {
+        let answer = self.clients.borrow_mut()[seat]
+            .as_mut()
+            .map(|client| client.ask(question, self.timeout));
+        match answer? {
+            Ok(answer) => Some(answer),
+            Err(RecvTimeoutError::Timeout) => {
+                self.tell(seat, "Time is up.");
+                None
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                self.clients.borrow_mut()[seat] = None;
+                self.leave(seat);
+                None
+            }
+        }
+    }
+
+    /// Tells the table that a player has left.
+    fn leave(&self, seat: usize) {
+        self.broadcast(&format!(
+            "{} has left the table, and bets the minimum and stands from now on.",
+            self.names[seat]
+        ));
+    }
+}
+
+impl RemoteSeats for Server {
+    fn record(&self, record: &Record) {
+        self.broadcast(&record.to_line(&self.names));
+    }
+
+    /// Asks the player of a seat for their bet, until the table
+    /// allows it. Players who left or do not answer in time
+    /// bet the smallest bet allowed.
+    fn bet(&self, seat: usize, limits: &TableLimits, available: u32, last_bet: Option<u32>) -> u32 {
+        let question = format!(
+            "{}, you have {} units. Bets go from {} to {}, by {}. Your bet ?{}",
+            self.names[seat],
+            available,
+            limits.min,
+            limits.max,
+            limits.chip,
+            match last_bet {
+                Some(bet) => format!(" (ENTER to bet {} again)", bet),
+                None => String::new(),
+            }
+        );
+        loop {
+            let answer = match self.ask(seat, &question) {
+                Some(answer) => answer,
+                None => return limits.clamp(0.0, available),
+            };
+            match parse_bet(&answer, available, limits, last_bet) {
+                Ok(bet) if limits.allows(bet, available) => return bet,
+                Ok(_) => self.tell(seat, "This bet is not allowed at this table."),
+                Err(message) => self.tell(seat, &message),
+            }
+        }
+    }
+
+    /// Asks the player of a seat for their action on a hand, until
+    /// it is one of the `legal` ones. Players who left or do not
+    /// answer in time stand.
+    fn action(
+        &self,
+        seat: usize,
+        (hand, is_second): (&[Card], bool),
+        dealer_hand: &[Card],
+        legal: &[PlayerAction],
+    ) -> PlayerAction {
+        let cards = |hand: &[Card]| hand.iter().map(card_code).collect::<Vec<_>>().join(" ");
+        let question = format!(
+            "{}, your {}hand {} ({}) against the dealer's {} : {} ?",
+            self.names[seat],
+            if is_second { "second " } else { "" },
+            cards(hand),
+            hand_value(hand),
+            cards(dealer_hand),
+            list(legal)
+        );
+        loop {
+            let answer = match self.ask(seat, &question) {
+                Some(answer) => answer,
+                None => return PlayerAction::Stand,
+            };
+            match parse_turn_input(&answer) {
+                Some(TurnInput::Action(action)) if legal.contains(&action) => return action,
+                Some(TurnInput::Action(_)) => self.tell(seat, "This action is not allowed now."),
+                Some(TurnInput::Help) => self.tell(seat, action_help(legal).trim_end()),
+                None => self.tell(seat, "Unrecognized move. Type ? for help."),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::card::{Rank, Suit};
+    use crate::server::*;
+
+    use std::io::{BufRead, BufReader};
+    use std::sync::mpsc::{channel, Sender};
+    use std::thread::JoinHandle;
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// A player connecting to the server, who types the `answers`
+    /// to its questions and returns what the server sent. The
+    /// `welcomed` channel, if any, is told when the server accepts
+    /// the connection, which it does for one player at a time.
+    fn player(
+        port: u16,
+        answers: &'static [&'static str],
+        welcomed: Option<Sender<()>>,
+    ) -> JoinHandle<Vec<String>> {
+        std::thread::spawn(move || {
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+            let mut received = Vec::new();
+            let mut answers = answers.iter();
+            while let Some(Ok(line)) = lines.next() {
+                if line == "Welcome" {
+                    if let Some(welcomed) = &welcomed {
+                        let _ = welcomed.send(());
+                    }
+                }
+                if line.ends_with('?') || line.ends_with(')') {
+                    match answers.next() {
+                        Some(answer) => writeln!(stream, "{}", answer).unwrap(),
+                        None => break,
+                    }
+                }
+                received.push(line);
+            }
+            received
+        })
+    }
+
+    #[test]
+    fn remote_players() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let ann = player(port, &["Ann", "20", "x", "split", "d"], None);
+        let mut names = vec![String::from("Player 1"), String::from("Player 2")];
+        let server = Server::open(&listener, &mut names[..1], &[0], "Welcome", TIMEOUT).unwrap();
+        assert_eq!(vec!["Ann", "Player 2"], names);
+
+        let limits = TableLimits {
+            min: 10,
+            chip: 5,
+            ..TableLimits::default()
+        };
+        assert_eq!(20, server.bet(0, &limits, 300, None));
+        let card = |rank| Card {
+            rank,
+            suit: Suit::Clubs,
+        };
+        let hand = [card(Rank::Nine), card(Rank::Two)];
+        let legal = [PlayerAction::Hit, PlayerAction::Stand, PlayerAction::Double];
+        assert_eq!(
+            PlayerAction::Double,
+            server.action(0, (&hand, false), &[card(Rank::Seven)], &legal)
+        );
+        server.broadcast("The game is over.");
+        // the player has no answer left, and leaves
+        assert_eq!(
+            PlayerAction::Stand,
+            server.action(0, (&hand, false), &[], &legal)
+        );
+        assert_eq!(10, server.bet(0, &limits, 300, Some(20)));
+
+        let received = ann.join().unwrap();
+        assert_eq!("Welcome", received[0]);
+        assert!(received.contains(&String::from("Ann takes seat 1. The game starts.")));
+        assert!(received.contains(&String::from(
+            "Ann, your hand 9C 2C (11) against the dealer's 7C : hit, stand or double ?"
+        )));
+        assert!(received.contains(&String::from("Unrecognized move. Type ? for help.")));
+        assert!(received.contains(&String::from("This action is not allowed now.")));
+        assert!(received.contains(&String::from("The game is over.")));
+    }
+
+    #[test]
+    fn waiting_players() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (welcomed, accepted) = channel();
+        let ann = player(port, &["Ann"], Some(welcomed));
+        let mut names = vec![String::from("Player 1"), String::from("Player 2")];
+        let bob = std::thread::spawn(move || {
+            // the second player connects once the first one is accepted,
+            // and waits for them to be seated
+            accepted.recv().unwrap();
+            player(port, &["Ann", ""], None).join().unwrap()
+        });
+        let server = Server::open(&listener, &mut names, &[0, 1], "Welcome", TIMEOUT).unwrap();
+        assert_eq!(vec!["Ann", "Player 2"], names);
+        drop(server);
+        assert!(ann.join().unwrap().contains(&String::from(
+            "Ann takes seat 1. Waiting for 1 more player."
+        )));
+        assert!(bob.join().unwrap().contains(&String::from(
+            "Ann is already the name of another player. Your name ?"
+        )));
+    }
+
+    #[test]
+    fn slow_players() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let bob = std::thread::spawn(move || {
+            let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+            let mut received = Vec::new();
+            while let Some(Ok(line)) = lines.next() {
+                // Bob gives their name, then only answers after the timeout
+                if line.starts_with("Your name") {
+                    writeln!(stream, "Bob").unwrap();
+                } else if line.ends_with('?') {
+                    std::thread::sleep(Duration::from_millis(300));
+                    // the server may be gone by then
+                    let _ = writeln!(stream, "50");
+                }
+                received.push(line);
+            }
+            received
+        });
+        let mut names = vec![String::from("Player 1")];
+        let timeout = Duration::from_millis(100);
+        let server = Server::open(&listener, &mut names, &[0], "Welcome", timeout).unwrap();
+        let limits = TableLimits {
+            min: 10,
+            ..TableLimits::default()
+        };
+        assert_eq!(10, server.bet(0, &limits, 300, None));
+        // Bob is still seated, and their late answer is not taken for the next question
+        assert_eq!(10, server.bet(0, &limits, 300, None));
+        drop(server);
+        let received = bob.join().unwrap();
+        assert!(received.contains(&String::from("Time is up.")));
+        assert!(!received.iter().any(|line| line.contains("has left")));
+    }
+}
@@ -0,0 +1,321 @@
+use crate::betting::{BetPolicy, TableLimits};
+use crate::cli::Args;
+use crate::config::TableConfig;
+use crate::display::{ask_for_player_types, default_names};
+use crate::player::PlayerType;
+use crate::rules::Rules;
+use crate::strategy::strategy_from_name;
+
+/// The settings of an interactive game.
+#[derive(Debug)]
+pub struct GameSetup {
+    /// The name of the table, if it has one.
+    pub table_name: Option<String>,
+    /// The types of the players, the dealer last.
+    pub player_types: Vec<PlayerType>,
+    /// The names of the players, without the dealer.
+    pub names: Vec<String>,
+    /// The strategy of each player, used by bots only.
+    pub strategies: Vec<String>,
+    /// The betting policy of each player, used by bots only.
+    pub bet_policies: Vec<BetPolicy>,
+    /// The command starting the program of each player,
+    /// for external players only.
+    pub commands: Vec<Option<String>>,
+    /// The money each player starts with.
+    pub bankroll: u32,
+    /// The number of rounds to play, or None to play
+    /// until one of the players runs out of money.
+    pub rounds: Option<u32>,
+    /// The seed of the shuffles, or None for random shuffles.
+    pub seed: Option<u64>,
+    pub rules: Rules,
+    pub limits: TableLimits,
+}
+
+/// Parses the type of a player, ignoring the case :
+/// `human`, `bot`, `external` or `remote`, or their first letter.
+pub fn parse_player(text: &str) -> Result<PlayerType, String> {
+    match text.trim().to_lowercase().as_str() {
+        "human" | "h" => Ok(PlayerType::Human),
+        "bot" | "b" => Ok(PlayerType::Bot),
+        "external" | "e" => Ok(PlayerType::External),
+        "remote" | "r" => Ok(PlayerType::Remote),
+        other => Err(format!(
+            "Unknown player type '{}', expected human, bot, external or remote",
+            other
+        )),
+    }
+}
+
+/// Checks that the name of a player can be told apart from the
+/// `others` and from the other lines of hand histories
+/// ([crate::history]), where each event starts with `Name: `.
+pub fn check_name(name: &str, others: &[String]) -> Result<(), String> {
+    const RESERVED: [&str; 5] = ["Dealer", "Table", "Shoe", "Banks", "Shuffle"];
+    const PREFIXES: [&str; 3] = ["Seat ", "Round ", "Game "];
+    let starts_with = |prefix: &str| {
+        name.get(..prefix.len())
+            .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+    };
+    if name.trim().is_empty() {
+        Err(String::from("The name of a player cannot be empty"))
+    } else if name.contains(':') || name.chars().any(char::is_control) {
+        Err(format!("{} : names cannot contain a colon", name))
+    } else if RESERVED.iter().any(|word| word.eq_ignore_ascii_case(name))
+        || PREFIXES.iter().any(|prefix| starts_with(prefix))
+        || name.ends_with(" (second hand)")
+    {
+        Err(format!("{} cannot be the name of a player", name))
+    } else if others.iter().any(|other| other == name) {
+        Err(format!("{} is already the name of another player", name))
+    } else {
+        Ok(())
+    }
+}
+
+/// Parses a comma-separated list of players, such as
+/// `human,bot,bot`, and adds the dealer at the end.
+pub fn parse_players(text: &str) -> Result<Vec<PlayerType>, String> {
+    let mut player_types = text
+        .split(',')
+        .map(parse_player)
+        .collect::<Result<Vec<_>, _>>()?;
+    player_types.push(PlayerType::Dealer);
+    Ok(player_types)
+}
+
+impl GameSetup {
+    /// Reads the settings of a game from the options `--players`,
+    /// `--names`, `--bankroll`, `--rounds`, `--seed`, `--bot-strategy`,
+    /// `--bot-betting`, `--external`, the rules of [Args::rules] and the limits of
+    /// [Args::limits]. The values the command line leaves out are
+    /// taken from the table `config`, then from the defaults.
+    ///
+    /// The players are asked in the terminal when neither `--players`
+    /// nor the table give them. Players are named `Player 1`,
+    /// `Player 2`... unless they were given a name.
+    pub fn from_args(args: &Args, config: &TableConfig) -> Result<GameSetup, String> {
+        let game = &config.game;
+        let bankroll = args.parse_or("bankroll", game.bankroll.unwrap_or(300))?;
+        if bankroll == 0 {
+            return Err(String::from("The bankroll must be positive"));
+        }
+        let rounds = match args.get("rounds") {
+            None => game.rounds,
+            Some(_) => Some(args.parse_or("rounds", 0)?),
+        };
+        if rounds == Some(0) {
+            return Err(String::from("A game needs at least one round"));
+        }
+        let seed = match args.get("seed") {
+            None => game.seed,
+            Some(_) => Some(args.parse_or("seed", 0)?),
+        };
+        let rules = args.rules_over(&config.rules())?;
+        if rules.insurance {
+            return Err(String::from("Insurance is not offered in the game"));
+        }
+        let limits = args.limits_over(&config.limits())?;
+
+        // the seats of the table, unless the command line replaces them
+        let seats = match &config.seats {
+            Some(seats) if args.get("players").is_none() => seats.as_slice(),
+            _ => &[],
+        };
+        let player_types = match (args.get("players"), seats) {
+            (Some(text), _) => parse_players(text)?,
+            (None, []) => ask_for_player_types(),
+            (None, seats) => {
+                let mut player_types = seats
+                    .iter()
+                    .map(|seat| parse_player(&seat.player))
+                    .collect::<Result<Vec<_>, _>>()?;
+                player_types.push(PlayerType::Dealer);
+                player_types
+            }
+        };
+        let num_players = player_types.len() - 1;
+        if num_players == 0 {
+            return Err(String::from("A game needs at least one player"));
+        }
+        let names = match args.get("names") {
+            None => default_names(num_players)
+                .into_iter()
+                .enumerate()
+                .map(|(index, name)| match seats.get(index) {
+                    Some(seat) => seat.name.clone().unwrap_or(name),
+                    None => name,
+                })
+                .collect(),
+            Some(text) => {
+                let names: Vec<String> = text.split(',').map(|n| n.trim().to_string()).collect();
+                if names.len() != num_players || names.iter().any(String::is_empty) {
+                    return Err(format!(
+                        "Expected {} names, one per player, in --names",
+                        num_players
+                    ));
+                }
+                names
+            }
+        };
+        for (index, name) in names.iter().enumerate() {
+            check_name(name, &names[..index])?;
+        }
+
+        let mut strategies = Vec::new();
+        let mut bet_policies = Vec::new();
+        let mut commands = Vec::new();
+        for index in 0..num_players {
+            let seat = seats.get(index);
+            let command = seat
+                .and_then(|seat| seat.command.as_deref())
+                .or_else(|| args.get("external"));
+            commands.push(match (&player_types[index], command) {
+                (PlayerType::External, None) => {
+                    return Err(format!(
+                        "{} is an external player, give its program with --external",
+                        names[index]
+                    ))
+                }
+                (PlayerType::External, Some(command)) => Some(command.to_string()),
+                _ => None,
+            });
+            let strategy = args
+                .get("bot-strategy")
+                .or_else(|| seat.and_then(|seat| seat.strategy.as_deref()))
+                .or(config.bots.strategy.as_deref())
+                .unwrap_or("simple");
+            strategy_from_name(strategy)?;
+            strategies.push(strategy.to_string());
+            let betting = args
+                .get("bot-betting")
+                .or_else(|| seat.and_then(|seat| seat.betting.as_deref()))
+                .or(config.bots.betting.as_deref());
+            bet_policies.push(match betting {
+                Some(text) => BetPolicy::parse(text)?,
+                None => BetPolicy::HalfBank,
+            });
+        }
+
+        Ok(GameSetup {
+            table_name: config.name.clone(),
+            player_types,
+            names,
+            strategies,
+            bet_policies,
+            commands,
+            bankroll,
+            rounds,
+            seed,
+            rules,
+            limits,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::setup::*;
+
+    fn setup(line: &str) -> Result<GameSetup, String> {
+        setup_with(line, "")
+    }
+
+    fn setup_with(line: &str, config: &str) -> Result<GameSetup, String> {
+        GameSetup::from_args(
+            &Args::parse(line.split_whitespace().map(String::from)),
+            &TableConfig::parse(config).unwrap(),
+        )
+    }
+
+    #[test]
+    fn parse_players_test() {
+        assert_eq!(
+            vec![PlayerType::Human, PlayerType::Bot, PlayerType::Dealer],
+            parse_players("Human, bot").unwrap()
+        );
+        assert_eq!(PlayerType::Bot, parse_player("B").unwrap());
+        assert!(parse_players("human,dealer").is_err());
+        assert!(parse_players("").is_err());
+    }
+
+    #[test]
+    fn from_args_test() {
+        let game = setup(
+            "--players bot,bot --names Ann,Bob --bankroll 500 --rounds 20 --seed 4 --h17 --surrender",
+        )
+        .unwrap();
+        assert_eq!(3, game.player_types.len());
+        assert_eq!(vec!["Ann", "Bob"], game.names);
+        assert_eq!(
+            (500, Some(20), Some(4)),
+            (game.bankroll, game.rounds, game.seed)
+        );
+        assert!(game.rules.dealer_hits_soft_17 && game.rules.surrender);
+        assert_eq!(vec!["simple", "simple"], game.strategies);
+
+        let game = setup("--players human").unwrap();
+        assert_eq!(vec!["Player 1"], game.names);
+        assert_eq!((300, None, None), (game.bankroll, game.rounds, game.seed));
+
+        let game = setup("--players human,external --external ./bot").unwrap();
+        assert_eq!(vec![None, Some(String::from("./bot"))], game.commands);
+    }
+
+    #[test]
+    fn from_args_errors() {
+        assert!(setup("--players bot,bot --names Ann").is_err());
+        assert!(setup("--players bot,bot --names Ann,Ann").is_err());
+        assert!(setup("--players bot,bot --names Ann,dealer").is_err());
+        assert!(setup("--players bot --rounds 0").is_err());
+        assert!(setup("--players bot --bankroll 0").is_err());
+        assert!(setup("--players bot --seed x").is_err());
+        assert!(setup("--players bot --insurance").is_err());
+        assert!(setup("--players bot --bot-strategy best").is_err());
+        assert!(setup("--players bot --h17 yes").is_err());
+        assert!(setup("--players bot,external").is_err());
+    }
+
+    #[test]
+    fn command_line_overrides_table() {
+        let table = "[rules]\ndecks = 1\nh17 = true\n\
+                     [limits]\nmin_bet = 5\nmax_bet = 200\n\
+                     [game]\nbankroll = 1000\nrounds = 50\n\
+                     [bots]\nstrategy = \"basic\"\n\
+                     [[seats]]\nplayer = \"human\"\nname = \"Ann\"\n\
+                     [[seats]]\nplayer = \"bot\"\n\
+                     [[seats]]\nplayer = \"bot\"\nstrategy = \"simple\"\nbetting = \"martingale\"\n";
+        let game = setup_with("", table).unwrap();
+        assert_eq!(vec!["Ann", "Player 2", "Player 3"], game.names);
+        assert_eq!(vec!["basic", "basic", "simple"], game.strategies);
+        assert!(matches!(game.bet_policies[2], BetPolicy::Progression(_)));
+        assert!(matches!(game.bet_policies[1], BetPolicy::HalfBank));
+        assert_eq!((1000, Some(50)), (game.bankroll, game.rounds));
+        assert_eq!(
+            (1, true),
+            (game.rules.num_packs, game.rules.dealer_hits_soft_17)
+        );
+        assert_eq!((5, 200), (game.limits.min, game.limits.max));
+
+        let game = setup_with(
+            "--decks 6 --h17=false --max-bet 500 --rounds 10 --bot-strategy simple --names A,B,C",
+            table,
+        )
+        .unwrap();
+        assert_eq!(vec!["A", "B", "C"], game.names);
+        assert_eq!(vec!["simple"; 3], game.strategies);
+        assert_eq!(
+            (6, false),
+            (game.rules.num_packs, game.rules.dealer_hits_soft_17)
+        );
+        assert_eq!((5, 500), (game.limits.min, game.limits.max));
+        assert_eq!((1000, Some(10)), (game.bankroll, game.rounds));
+
+        let game = setup_with("--players bot", table).unwrap();
+        assert_eq!(vec!["Player 1"], game.names);
+        assert_eq!(vec!["basic"], game.strategies);
+        assert!(setup_with("--min-bet 300", table).is_err());
+        assert!(setup_with("--h17=no", table).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use crate::card::Card;
+use crate::math::rank_value;
+use crate::rules::Rules;
+
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// A shoe of a few card packs, shuffled by a seeded
+/// random number generator.
+///
+/// Two shoes built with the same [Rules] and the same seed
+/// deal exactly the same sequence of cards, which makes
+/// simulations reproducible. The shoe can be cloned to
+/// play several alternative continuations from the same position.
+///
+/// The shoe also keeps track of the cards dealt since the last
+/// shuffle, which is what card counters look at.
+#[derive(Clone)]
+pub struct Shoe {
+    cards: Vec<Card>,
+    num_packs: usize,
+    cut_card: usize,
+    rng: ChaCha8Rng,
+    dealt: [u32; 10],
+}
+
+impl Shoe {
+    /// Creates a freshly shuffled shoe following the given rules.
+    pub fn new(rules: &Rules, seed: u64) -> Shoe {
+        let size = rules.num_packs * 52;
+        let mut shoe = Shoe {
+            cards: Vec::with_capacity(size),
+            num_packs: rules.num_packs,
+            cut_card: size - (size as f64 * rules.penetration) as usize,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            dealt: [0; 10],
+        };
+        shoe.shuffle();
+        shoe
+    }
+
+    /// Gathers all the cards back into the shoe and shuffles it.
+    pub fn shuffle(&mut self) {
+        self.cards.clear();
+        for _ in 0..self.num_packs {
+            self.cards.extend(Card::card_pack());
+        }
+        self.cards.shuffle(&mut self.rng);
+        self.dealt = [0; 10];
+    }
+
+    /// Picks a card from the shoe. If the shoe is empty,
+    /// it is reshuffled first.
+    pub fn draw(&mut self) -> Card {
+        if self.cards.is_empty() {
+            self.shuffle();
+        }
+        let card = self.cards.pop().unwrap();
+        self.dealt[rank_value(&card.rank) as usize - 1] += 1;
+        card
+    }
+
+    /// Picks a card of the given value (1 for Aces) out of the cards
+    /// left in the shoe, or returns None if there is none left.
+    ///
+    /// The card is chosen at random among the matching ones, so
+    /// that the order of the other cards stays random.
+    pub fn take(&mut self, value: u32) -> Option<Card> {
+        let positions: Vec<usize> = (0..self.cards.len())
+            .filter(|&i| rank_value(&self.cards[i].rank) == value)
+            .collect();
+        let position = *positions.choose(&mut self.rng)?;
+        let card = self.cards.remove(position);
+        self.dealt[value as usize - 1] += 1;
+        Some(card)
+    }
+
+    /// Returns true whenever the cut card has been reached,
+    /// meaning the shoe should be reshuffled before the next round.
+    pub fn needs_shuffle(&self) -> bool {
+        self.cards.len() <= self.cut_card
+    }
+
+    /// The number of cards dealt since the last shuffle, for
+    /// each card value : the first element counts the Aces, the
+    /// second one the Twos, and so on until the last one
+    /// which counts all the cards worth 10.
+    pub fn dealt(&self) -> &[u32; 10] {
+        &self.dealt
+    }
+
+    /// The number of packs left to be dealt, including fractions of packs.
+    pub fn decks_remaining(&self) -> f64 {
+        self.cards.len() as f64 / 52.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shoe::*;
+
+    #[test]
+    fn same_seed_same_cards() {
+        let mut shoe1 = Shoe::new(&Rules::default(), 42);
+        let mut shoe2 = Shoe::new(&Rules::default(), 42);
+        for _ in 0..500 {
+            assert_eq!(shoe1.draw(), shoe2.draw());
+        }
+    }
+
+    #[test]
+    fn cut_card_test() {
+        let rules = Rules {
+            penetration: 0.5,
+            ..Rules::default()
+        };
+        let mut shoe = Shoe::new(&rules, 0);
+        assert_eq!(4 * 52, shoe.cards.len());
+        for _ in 0..103 {
+            shoe.draw();
+        }
+        assert!(!shoe.needs_shuffle());
+        shoe.draw();
+        assert!(shoe.needs_shuffle());
+    }
+
+    #[test]
+    fn dealt_cards_test() {
+        let mut shoe = Shoe::new(&Rules::default(), 3);
+        for _ in 0..52 {
+            shoe.draw();
+        }
+        assert_eq!(52, shoe.dealt().iter().sum::<u32>());
+        assert_eq!(3.0, shoe.decks_remaining());
+        shoe.shuffle();
+        assert_eq!(&[0; 10], shoe.dealt());
+    }
+
+    #[test]
+    fn take_test() {
+        let mut shoe = Shoe::new(&Rules::default(), 3);
+        for _ in 0..16 {
+            assert_eq!(4, rank_value(&shoe.take(4).unwrap().rank));
+        }
+        assert!(shoe.take(4).is_none());
+        assert_eq!(16, shoe.dealt()[3]);
+        assert_eq!(4 * 52 - 16, shoe.cards.len());
+    }
+}
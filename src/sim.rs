@@ -0,0 +1,351 @@
+use crate::card::{Card, Rank};
+use crate::math::{hand_value, is_blackjack, is_soft, is_splittable};
+use crate::player::PlayerAction;
+use crate::rules::Rules;
+use crate::shoe::Shoe;
+use crate::strategy::{Situation, Strategy};
+
+/// Plays a full round between a single bot and the dealer,
+/// without any terminal output, and returns the net result of
+/// the bot in units of its initial bet.
+///
+/// For instance, a lost double down returns -2.0 and a
+/// blackjack returns 1.5. The round follows the rules of the
+/// game : the dealer has no hole card and takes every bet
+/// (including doubles and splits) when drawing a blackjack,
+/// and a hand may only be split once. The two hands of a split
+/// each receive their second card right away.
+///
+/// When the table allows it, the bot may surrender its first two
+/// cards for half its bet (but loses all of it when the dealer
+/// then draws a blackjack, as for any other bet), and take insurance (half its bet, paid
+/// 2 to 1) when the dealer shows an Ace.
+///
+/// Illegal Double, Split or Surrender actions returned by the
+/// strategy are played as Hit.
+pub fn simulate_round(shoe: &mut Shoe, rules: &Rules, strategy: &dyn Strategy) -> f64 {
+    simulate_round_within(shoe, rules, strategy, f64::INFINITY)
+}
+
+/// Same as [simulate_round], for a bot that only has `funds` times
+/// its initial bet : it only insures, doubles or splits when its
+/// funds cover the extra bet, and hits instead.
+pub fn simulate_round_within(
+    shoe: &mut Shoe,
+    rules: &Rules,
+    strategy: &dyn Strategy,
+    funds: f64,
+) -> f64 {
+    let hand = vec![shoe.draw(), shoe.draw()];
+    let dealer_card = shoe.draw();
+    play_round_within(shoe, rules, strategy, (hand, dealer_card), funds)
+}
+
+/// Same as [simulate_round], once the first two cards of the
+/// player and the upcard of the dealer have been dealt.
+pub fn play_round_from(
+    shoe: &mut Shoe,
+    rules: &Rules,
+    strategy: &dyn Strategy,
+    hand: Vec<Card>,
+    dealer_card: Card,
+) -> f64 {
+    play_round_within(shoe, rules, strategy, (hand, dealer_card), f64::INFINITY)
+}
+
+/// Plays the rest of a round after the deal, with `funds`
+/// as in [simulate_round_within].
+fn play_round_within(
+    shoe: &mut Shoe,
+    rules: &Rules,
+    strategy: &dyn Strategy,
+    (hand, dealer_card): (Vec<Card>, Card),
+    funds: f64,
+) -> f64 {
+    let mut hands = vec![hand];
+    let mut stakes = vec![1.0];
+    let player_blackjack = is_blackjack(&hands[0]);
+
+    let insured = rules.insurance
+        && dealer_card.rank == Rank::Ace
+        && funds >= 1.5
+        && strategy.insure(&Situation {
+            hand: &hands[0],
+            dealer_card: &dealer_card,
+            can_double: false,
+            can_split: false,
+            can_surrender: false,
+            dealt: shoe.dealt(),
+            decks_remaining: shoe.decks_remaining(),
+            rules,
+        });
+    let funds = if insured { funds - 0.5 } else { funds };
+
+    let mut surrendered = false;
+    if !player_blackjack {
+        let mut index = 0;
+        while index < hands.len() {
+            surrendered |= play_hand(
+                shoe,
+                rules,
+                strategy,
+                &dealer_card,
+                (&mut hands, &mut stakes),
+                (index, funds),
+            );
+            index += 1;
+        }
+    }
+
+    let mut dealer_hand = vec![dealer_card];
+    while dealer_should_hit(&dealer_hand, rules) {
+        dealer_hand.push(shoe.draw());
+    }
+    let dealer_blackjack = is_blackjack(&dealer_hand);
+
+    let insurance = match (insured, dealer_blackjack) {
+        (false, _) => 0.0,
+        (true, true) => 1.0,
+        (true, false) => -0.5,
+    };
+    let result = if surrendered {
+        if dealer_blackjack {
+            -1.0
+        } else {
+            -0.5
+        }
+    } else if player_blackjack {
+        if dealer_blackjack {
+            0.0
+        } else {
+            1.5
+        }
+    } else {
+        hands
+            .iter()
+            .zip(stakes)
+            .map(|(hand, stake)| stake * settle(hand, &dealer_hand))
+            .sum()
+    };
+    insurance + result
+}
+
+/// Asks the strategy for actions on the hand at the given
+/// index until it stands, busts, doubles down or surrenders,
+/// doubling or splitting only when the `funds` cover the extra bet.
+/// Returns true if the hand was surrendered.
+fn play_hand(
+    shoe: &mut Shoe,
+    rules: &Rules,
+    strategy: &dyn Strategy,
+    dealer_card: &Card,
+    hands_and_stakes: (&mut Vec<Vec<Card>>, &mut Vec<f64>),
+    (index, funds): (usize, f64),
+) -> bool {
+    let (hands, stakes) = hands_and_stakes;
+    while hand_value(&hands[index]) < 21 {
+        let staked: f64 = stakes.iter().sum();
+        let can_split = hands.len() == 1 && is_splittable(&hands[0]) && staked + 1.0 <= funds;
+        let can_double = hands[index].len() == 2
+            && (hands.len() == 1 || rules.double_after_split)
+            && staked + stakes[index] <= funds;
+        let can_surrender = rules.surrender && hands.len() == 1 && hands[0].len() == 2;
+        let action = strategy.decide(&Situation {
+            hand: &hands[index],
+            dealer_card,
+            can_double,
+            can_split,
+            can_surrender,
+            dealt: shoe.dealt(),
+            decks_remaining: shoe.decks_remaining(),
+            rules,
+        });
+        match action {
+            PlayerAction::Stand => return false,
+            PlayerAction::Surrender if can_surrender => return true,
+            PlayerAction::Double if can_double => {
+                stakes[index] *= 2.0;
+                hands[index].push(shoe.draw());
+                return false;
+            }
+            PlayerAction::Split if can_split => {
+                let card = hands[0].pop().unwrap();
+                hands[0].push(shoe.draw());
+                hands.push(vec![card, shoe.draw()]);
+                stakes.push(1.0);
+            }
+            _ => hands[index].push(shoe.draw()),
+        }
+    }
+    false
+}
+
+/// Returns true whenever the dealer must draw another card
+/// according to the rules of the table.
+pub fn dealer_should_hit(dealer_hand: &[Card], rules: &Rules) -> bool {
+    let value = hand_value(dealer_hand);
+    value < 17 || (value == 17 && rules.dealer_hits_soft_17 && is_soft(dealer_hand))
+}
+
+/// The result of a single non-blackjack hand of the player
+/// against the final hand of the dealer, for a unit stake.
+fn settle(hand: &[Card], dealer_hand: &[Card]) -> f64 {
+    let value = hand_value(hand);
+    let dealer_value = hand_value(dealer_hand);
+    if value > 21 || is_blackjack(dealer_hand) {
+        -1.0
+    } else if dealer_value > 21 || value > dealer_value {
+        1.0
+    } else if value == dealer_value {
+        0.0
+    } else {
+        -1.0
+    }
+}
+
+/// Accumulates samples to compute their mean and variance
+/// in a single pass (Welford's algorithm).
+#[derive(Default, Clone, Debug)]
+pub struct Accumulator {
+    count: u64,
+    mean: f64,
+    m2: f64,
+}
+
+impl Accumulator {
+    pub fn add(&mut self, sample: f64) {
+        self.count += 1;
+        let delta = sample - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (sample - self.mean);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// The unbiased sample variance.
+    pub fn variance(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2 / (self.count - 1) as f64
+        }
+    }
+
+    /// The standard error of the mean.
+    pub fn std_err(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            (self.variance() / self.count as f64).sqrt()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::card::*;
+    use crate::sim::*;
+    use crate::strategy::BasicStrategy;
+
+    fn card(rank: Rank) -> Card {
+        Card {
+            rank,
+            suit: Suit::Hearts,
+        }
+    }
+
+    #[test]
+    fn dealer_soft_17() {
+        let hand = [card(Rank::Ace), card(Rank::Six)];
+        assert!(!dealer_should_hit(&hand, &Rules::default()));
+        let h17 = Rules {
+            dealer_hits_soft_17: true,
+            ..Rules::default()
+        };
+        assert!(dealer_should_hit(&hand, &h17));
+        assert!(!dealer_should_hit(
+            &[card(Rank::Ten), card(Rank::Seven)],
+            &h17
+        ));
+    }
+
+    #[test]
+    fn settle_test() {
+        let twenty = [card(Rank::King), card(Rank::Queen)];
+        let bust = [card(Rank::King), card(Rank::Queen), card(Rank::Two)];
+        let blackjack = [card(Rank::Ace), card(Rank::Queen)];
+        assert_eq!(1.0, settle(&twenty, &bust));
+        assert_eq!(-1.0, settle(&bust, &bust));
+        assert_eq!(0.0, settle(&twenty, &twenty));
+        assert_eq!(-1.0, settle(&twenty, &blackjack));
+    }
+
+    #[test]
+    fn simulation_is_reproducible() {
+        let rules = Rules::default();
+        let mut shoe1 = Shoe::new(&rules, 7);
+        let mut shoe2 = Shoe::new(&rules, 7);
+        for _ in 0..1000 {
+            assert_eq!(
+                simulate_round(&mut shoe1, &rules, &BasicStrategy),
+                simulate_round(&mut shoe2, &rules, &BasicStrategy)
+            );
+        }
+    }
+
+    struct AlwaysSurrender;
+
+    impl Strategy for AlwaysSurrender {
+        fn decide(&self, _: &Situation) -> PlayerAction {
+            PlayerAction::Surrender
+        }
+    }
+
+    #[test]
+    fn surrender_test() {
+        let rules = Rules {
+            surrender: true,
+            ..Rules::default()
+        };
+        let mut shoe = Shoe::new(&rules, 3);
+        for _ in 0..1000 {
+            let result = simulate_round(&mut shoe, &rules, &AlwaysSurrender);
+            // a dealer blackjack takes the whole bet, a player
+            // blackjack is never surrendered
+            assert!([-0.5, -1.0, 0.0, 1.5].contains(&result));
+        }
+        // without surrender, the hand is hit instead
+        let mut shoe = Shoe::new(&Rules::default(), 3);
+        let results: Vec<f64> = (0..100)
+            .map(|_| simulate_round(&mut shoe, &Rules::default(), &AlwaysSurrender))
+            .collect();
+        assert!(results.iter().all(|&result| result != -0.5));
+    }
+
+    #[test]
+    fn limited_funds() {
+        let rules = Rules::default();
+        let mut shoe = Shoe::new(&rules, 7);
+        // a bot with a single bet never doubles nor splits
+        for _ in 0..1000 {
+            assert!(simulate_round_within(&mut shoe, &rules, &BasicStrategy, 1.0) >= -1.0);
+        }
+    }
+
+    #[test]
+    fn accumulator_test() {
+        let mut acc = Accumulator::default();
+        for &sample in &[1.0, 2.0, 3.0, 4.0] {
+            acc.add(sample);
+        }
+        assert_eq!(4, acc.count());
+        assert!((acc.mean() - 2.5).abs() < 1e-12);
+        assert!((acc.variance() - 5.0 / 3.0).abs() < 1e-12);
+    }
+}
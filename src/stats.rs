@@ -0,0 +1,239 @@
+use crate::card::Card;
+use crate::math::hand_value;
+
+use serde::{Deserialize, Serialize};
+
+/// The statistics of a seat over a session.
+#[derive(PartialEq, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PlayerStats {
+    /// The number of hands played, counting both hands of a split.
+    pub hands: u32,
+    pub wins: u32,
+    pub pushes: u32,
+    pub losses: u32,
+    /// The hands won with a blackjack, also counted in `wins`.
+    pub blackjacks: u32,
+    /// The hands that went over 21, also counted in `losses`.
+    pub busts: u32,
+    pub doubles: u32,
+    pub doubles_won: u32,
+    /// The number of pairs split.
+    pub splits: u32,
+    /// The hands won out of the two hands of each split.
+    pub split_hands_won: u32,
+    /// The money won over the session, negative when it was lost.
+    pub net: i64,
+    /// The best result of a round.
+    pub biggest_win: i64,
+    /// The worst result of a round, as a negative number.
+    pub biggest_loss: i64,
+    /// The current run of rounds won (when positive) or lost
+    /// (when negative). Pushed rounds end both.
+    pub streak: i32,
+    pub longest_win_streak: u32,
+    pub longest_loss_streak: u32,
+}
+
+/// The outcome of a hand against the dealer.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Outcome {
+    Blackjack,
+    Win,
+    Push,
+    Loss,
+}
+
+impl Outcome {
+    /// The outcomes of the hands of the four lists
+    /// of [crate::math::compute_result], in order.
+    pub const BY_RESULT: [Outcome; 4] = [
+        Outcome::Blackjack,
+        Outcome::Win,
+        Outcome::Push,
+        Outcome::Loss,
+    ];
+}
+
+impl PlayerStats {
+    /// The percentage of `part` in `total`, 0 for an empty total.
+    pub fn rate(part: u32, total: u32) -> f64 {
+        if total == 0 {
+            0.0
+        } else {
+            100.0 * part as f64 / total as f64
+        }
+    }
+
+    /// Records a hand, given whether it was doubled and
+    /// whether it comes from a split.
+    fn record_hand(&mut self, hand: &[Card], outcome: Outcome, doubled: bool, split: bool) {
+        self.hands += 1;
+        let won = matches!(outcome, Outcome::Blackjack | Outcome::Win);
+        match outcome {
+            Outcome::Blackjack => {
+                self.blackjacks += 1;
+                self.wins += 1;
+            }
+            Outcome::Win => self.wins += 1,
+            Outcome::Push => self.pushes += 1,
+            Outcome::Loss => self.losses += 1,
+        }
+        if hand_value(hand) > 21 {
+            self.busts += 1;
+        }
+        if doubled {
+            self.doubles += 1;
+            self.doubles_won += won as u32;
+        }
+        if split {
+            self.split_hands_won += won as u32;
+        }
+    }
+
+    /// Records the money won (or lost) during a round.
+    fn record_net(&mut self, net: i64) {
+        self.net += net;
+        self.biggest_win = self.biggest_win.max(net);
+        self.biggest_loss = self.biggest_loss.min(net);
+        self.streak = match net.signum() {
+            1 => self.streak.max(0) + 1,
+            -1 => self.streak.min(0) - 1,
+            _ => 0,
+        };
+        if self.streak > 0 {
+            self.longest_win_streak = self.longest_win_streak.max(self.streak as u32);
+        } else {
+            self.longest_loss_streak = self.longest_loss_streak.max(-self.streak as u32);
+        }
+    }
+}
+
+/// Adds a round to the statistics of each seat.
+///
+/// # Arguments
+/// * `results` The blackjacked, won, pushed and lost
+///   hands, as computed by [crate::math::compute_result].
+/// * `doubled` Whether the hands of each player were doubled,
+///   as returned by [crate::round::play_round].
+/// * `nets` The money won by each player during the round, as
+///   computed by [crate::utils::net_results].
+pub fn record_round(
+    stats: &mut [PlayerStats],
+    player_hands: &[(Vec<Card>, Option<Vec<Card>>)],
+    results: &[Vec<(usize, bool)>; 4],
+    doubled: &[(bool, bool)],
+    nets: &[i64],
+) {
+    for (hands, &outcome) in results.iter().zip(Outcome::BY_RESULT.iter()) {
+        for &(index, is_second) in hands {
+            let (first, second) = &player_hands[index];
+            let hand = if is_second {
+                second.as_ref().unwrap()
+            } else {
+                first
+            };
+            let was_doubled = if is_second {
+                doubled[index].1
+            } else {
+                doubled[index].0
+            };
+            stats[index].record_hand(hand, outcome, was_doubled, second.is_some());
+        }
+    }
+    for (index, hands) in player_hands.iter().enumerate() {
+        if hands.1.is_some() {
+            stats[index].splits += 1;
+        }
+        stats[index].record_net(nets[index]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::card::*;
+    use crate::stats::*;
+
+    fn cards(ranks: &[Rank]) -> Vec<Card> {
+        ranks
+            .iter()
+            .map(|&rank| Card {
+                rank,
+                suit: Suit::Hearts,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn record_round_test() {
+        let mut stats = vec![PlayerStats::default(); 2];
+        let hands = vec![
+            (cards(&[Rank::Ace, Rank::King]), None),
+            (
+                cards(&[Rank::Eight, Rank::Three, Rank::Ten]),
+                Some(cards(&[Rank::Eight, Rank::Nine, Rank::Seven])),
+            ),
+        ];
+        let results = [vec![(0, false)], vec![(1, false)], vec![], vec![(1, true)]];
+        record_round(
+            &mut stats,
+            &hands,
+            &results,
+            &[(false, false), (true, false)],
+            &[15, 0],
+        );
+        assert_eq!(
+            (1, 1, 1, 0),
+            (
+                stats[0].hands,
+                stats[0].wins,
+                stats[0].blackjacks,
+                stats[0].busts
+            )
+        );
+        assert_eq!(
+            (15, 15, 1),
+            (
+                stats[0].net,
+                stats[0].biggest_win,
+                stats[0].longest_win_streak
+            )
+        );
+        let split = &stats[1];
+        assert_eq!(
+            (2, 1, 1, 1),
+            (split.hands, split.wins, split.losses, split.busts)
+        );
+        assert_eq!(
+            (1, 1, 1, 1),
+            (
+                split.doubles,
+                split.doubles_won,
+                split.splits,
+                split.split_hands_won
+            )
+        );
+        assert_eq!(0, split.streak);
+    }
+
+    #[test]
+    fn streaks() {
+        let mut stats = PlayerStats::default();
+        for &net in &[10, 20, -5, -5, -5, 0, 10] {
+            stats.record_net(net);
+        }
+        assert_eq!(
+            (2, 3, 1),
+            (
+                stats.longest_win_streak,
+                stats.longest_loss_streak,
+                stats.streak
+            )
+        );
+        assert_eq!(
+            (25, 20, -5),
+            (stats.net, stats.biggest_win, stats.biggest_loss)
+        );
+        assert_eq!(50.0, PlayerStats::rate(1, 2));
+        assert_eq!(0.0, PlayerStats::rate(0, 0));
+    }
+}
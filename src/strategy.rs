@@ -0,0 +1,239 @@
+use crate::card::{Card, Rank};
+use crate::chart::Chart;
+use crate::deviation::Deviations;
+use crate::indices::load_table;
+use crate::math::{hand_value, is_soft, rank_value};
+use crate::player::PlayerAction;
+use crate::rules::Rules;
+
+/// Everything a bot knows about a hand when it has
+/// to pick an action.
+pub struct Situation<'a> {
+    /// The cards of the hand being played.
+    pub hand: &'a [Card],
+    /// The face-up card of the dealer.
+    pub dealer_card: &'a Card,
+    /// Whether doubling down is legal on this hand.
+    pub can_double: bool,
+    /// Whether splitting is legal on this hand.
+    pub can_split: bool,
+    /// Whether surrendering is legal on this hand.
+    pub can_surrender: bool,
+    /// The cards dealt since the last shuffle, by value
+    /// like [crate::shoe::Shoe::dealt], for counting bots.
+    pub dealt: &'a [u32; 10],
+    /// The number of packs left to be dealt.
+    pub decks_remaining: f64,
+    /// The rules of the table.
+    pub rules: &'a Rules,
+}
+
+/// A decision algorithm used by bots to play their hands.
+///
+/// Implementations should only return Double, Split or Surrender
+/// when the [Situation] says they are legal.
+pub trait Strategy {
+    fn decide(&self, situation: &Situation) -> PlayerAction;
+
+    /// Whether to take insurance, when the dealer shows an Ace
+    /// and the table offers it. Basic strategy never does.
+    fn insure(&self, _situation: &Situation) -> bool {
+        false
+    }
+}
+
+/// The historical bot algorithm, which only looks
+/// at the totals of the hands.
+/// Approximation of : <https://blog.prepscholar.com/blackjack-strategy>
+pub struct SimpleStrategy;
+
+impl Strategy for SimpleStrategy {
+    fn decide(&self, situation: &Situation) -> PlayerAction {
+        simple_action(
+            hand_value(situation.hand),
+            upcard_value(situation.dealer_card),
+            situation.can_double,
+        )
+    }
+}
+
+/// The decision rule of [SimpleStrategy], given the score
+/// of the player and the score of the dealer.
+pub fn simple_action(player_score: u32, dealer_score: u32, double_is_legal: bool) -> PlayerAction {
+    if player_score > 16 || (player_score > 11 && dealer_score < 7) {
+        PlayerAction::Stand
+    } else if double_is_legal && dealer_score < 7 && player_score > 8 {
+        PlayerAction::Double
+    } else {
+        PlayerAction::Hit
+    }
+}
+
+/// The basic strategy for a European no-hole-card game,
+/// adjusted to the H17, DAS and surrender rules of the table.
+///
+/// As the dealer takes all bets when drawing a blackjack,
+/// this strategy neither doubles nor splits as much against
+/// a 10 or an Ace as the usual hole card strategy does.
+pub struct BasicStrategy;
+
+impl Strategy for BasicStrategy {
+    fn decide(&self, situation: &Situation) -> PlayerAction {
+        let up = upcard_value(situation.dealer_card);
+        let rules = situation.rules;
+
+        if situation.can_split && should_split(situation.hand[0].rank, up, rules.double_after_split)
+        {
+            return PlayerAction::Split;
+        }
+
+        let total = hand_value(situation.hand);
+        let soft = is_soft(situation.hand);
+        if situation.can_surrender
+            && !soft
+            && ((total == 16 && up >= 9) || (total == 15 && up >= 10))
+        {
+            return PlayerAction::Surrender;
+        }
+
+        let double_or = |fallback| {
+            if situation.can_double {
+                PlayerAction::Double
+            } else {
+                fallback
+            }
+        };
+        let hit = PlayerAction::Hit;
+        let stand = PlayerAction::Stand;
+
+        if soft {
+            match total {
+                13 | 14 if (5..=6).contains(&up) => double_or(hit),
+                15 | 16 if (4..=6).contains(&up) => double_or(hit),
+                17 if (3..=6).contains(&up) => double_or(hit),
+                0..=17 => hit,
+                18 if up == 2 && rules.dealer_hits_soft_17 => double_or(stand),
+                18 if (3..=6).contains(&up) => double_or(stand),
+                18 if up >= 9 => hit,
+                19 if up == 6 && rules.dealer_hits_soft_17 => double_or(stand),
+                _ => stand,
+            }
+        } else {
+            match total {
+                9 if (3..=6).contains(&up) => double_or(hit),
+                10 | 11 if up <= 9 => double_or(hit),
+                12 if (4..=6).contains(&up) => stand,
+                13..=16 if up <= 6 => stand,
+                0..=16 => hit,
+                _ => stand,
+            }
+        }
+    }
+}
+
+/// Whether [BasicStrategy] splits a pair of the given rank
+/// against the given dealer upcard value.
+fn should_split(rank: Rank, up: u32, double_after_split: bool) -> bool {
+    match rank_value(&rank) {
+        1 => up <= 10,
+        2 | 3 => up <= 7 && (double_after_split || up >= 4),
+        4 => double_after_split && (5..=6).contains(&up),
+        6 => up <= 6 && (double_after_split || up >= 3),
+        7 => up <= 7,
+        8 => up <= 9,
+        9 => up <= 9 && up != 7,
+        _ => false,
+    }
+}
+
+/// The value of the dealer upcard, counting Aces as 11.
+pub fn upcard_value(card: &Card) -> u32 {
+    match card.rank {
+        Rank::Ace => 11,
+        rank => rank_value(&rank),
+    }
+}
+
+/// Builds the strategy matching a name given on the command line.
+///
+/// A base strategy can be followed by count-based deviations,
+/// as in `basic+i18+fab4` or `basic+16v10` (see [Deviations::parse]),
+/// `indices:FILE` plays basic strategy with the deviations of an
+/// index table written by the `indices` subcommand, and `chart:FILE`
+/// plays the strategy chart of a file (see [Chart::parse]).
+pub fn strategy_from_name(name: &str) -> Result<Box<dyn Strategy>, String> {
+    if let Some(path) = name.strip_prefix("chart:") {
+        return Ok(Box::new(Chart::load(path)?));
+    }
+    if let Some(path) = name.strip_prefix("indices:") {
+        return Ok(Box::new(load_table(path)?));
+    }
+    if let Some((base, deviations)) = name.split_once('+') {
+        let base = strategy_from_name(base)?;
+        return Ok(Box::new(Deviations::parse(base, deviations)?));
+    }
+    match name {
+        "simple" => Ok(Box::new(SimpleStrategy)),
+        "basic" => Ok(Box::new(BasicStrategy)),
+        _ => Err(format!("Unknown strategy '{}'", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::card::*;
+    use crate::strategy::*;
+
+    fn card(rank: Rank) -> Card {
+        Card {
+            rank,
+            suit: Suit::Spades,
+        }
+    }
+
+    fn decide(strategy: &dyn Strategy, hand: &[Card], dealer: Rank) -> PlayerAction {
+        strategy.decide(&Situation {
+            hand,
+            dealer_card: &card(dealer),
+            can_double: hand.len() == 2,
+            can_split: crate::math::is_splittable(hand),
+            can_surrender: false,
+            dealt: &[0; 10],
+            decks_remaining: 4.0,
+            rules: &Rules::default(),
+        })
+    }
+
+    #[test]
+    fn basic_strategy_hard_totals() {
+        let hand = [card(Rank::Ten), card(Rank::Six)];
+        assert!(decide(&BasicStrategy, &hand, Rank::Six) == PlayerAction::Stand);
+        assert!(decide(&BasicStrategy, &hand, Rank::Seven) == PlayerAction::Hit);
+
+        let hand = [card(Rank::Six), card(Rank::Five)];
+        assert!(decide(&BasicStrategy, &hand, Rank::Nine) == PlayerAction::Double);
+        assert!(decide(&BasicStrategy, &hand, Rank::King) == PlayerAction::Hit);
+    }
+
+    #[test]
+    fn basic_strategy_soft_and_pairs() {
+        let hand = [card(Rank::Ace), card(Rank::Seven)];
+        assert!(decide(&BasicStrategy, &hand, Rank::Four) == PlayerAction::Double);
+        assert!(decide(&BasicStrategy, &hand, Rank::Eight) == PlayerAction::Stand);
+        assert!(decide(&BasicStrategy, &hand, Rank::Ace) == PlayerAction::Hit);
+
+        let hand = [card(Rank::Eight), card(Rank::Eight)];
+        assert!(decide(&BasicStrategy, &hand, Rank::Nine) == PlayerAction::Split);
+        assert!(decide(&BasicStrategy, &hand, Rank::Ten) == PlayerAction::Hit);
+
+        let hand = [card(Rank::King), card(Rank::Queen)];
+        assert!(decide(&BasicStrategy, &hand, Rank::Six) == PlayerAction::Stand);
+    }
+
+    #[test]
+    fn simple_strategy_test() {
+        assert!(simple_action(17, 10, true) == PlayerAction::Stand);
+        assert!(simple_action(10, 5, true) == PlayerAction::Double);
+        assert!(simple_action(10, 5, false) == PlayerAction::Hit);
+    }
+}
@@ -0,0 +1,359 @@
+use crate::betting::{BetPolicy, Standing, TableLimits, TournamentBetting};
+use crate::card::Card;
+use crate::cli::{Args, LIMIT_OPTIONS};
+use crate::command::Quit;
+use crate::display::{display_hands_and_scores, display_results, wait_for_enter};
+use crate::math::{compute_blackjack_index, compute_result, compute_scores};
+use crate::player::PlayerType;
+use crate::round::{play_round, Table};
+use crate::rules::Rules;
+use crate::strategy::{strategy_from_name, Strategy};
+use crate::utils::{refill, update_bank};
+
+/// The format of a tournament.
+pub struct TournamentRules {
+    /// The number of hands of each round.
+    pub hands: u32,
+    /// The chips every player starts each round with.
+    pub chips: u32,
+    /// The largest number of players at a table.
+    pub table_size: usize,
+    /// The number of players of each table who advance
+    /// to the next round.
+    pub advance: usize,
+    /// The betting limits of the tables. Each player must bet at
+    /// least the minimum, or all their chips when they have less.
+    pub limits: TableLimits,
+}
+
+/// A player of a tournament.
+pub struct Entrant {
+    pub name: String,
+    pub human: bool,
+}
+
+/// Seats players at as few tables as possible, with
+/// the same number of players at each table, give or take one.
+pub fn tables(players: &[usize], table_size: usize) -> Vec<Vec<usize>> {
+    let count = players.len().div_ceil(table_size);
+    (0..count)
+        .map(|table| players.iter().skip(table).step_by(count).copied().collect())
+        .collect()
+}
+
+/// The seats of a table by decreasing chips. Ties
+/// are broken in favour of the first seats.
+pub fn ranking(chips: &[u32]) -> Vec<usize> {
+    let mut seats: Vec<usize> = (0..chips.len()).collect();
+    seats.sort_by_key(|&seat| std::cmp::Reverse(chips[seat]));
+    seats
+}
+
+/// Displays the players of a table ranked by chips,
+/// marking the ones who would advance if the round ended now.
+fn display_leaderboard(entrants: &[Entrant], players: &[usize], chips: &[u32], advance: usize) {
+    println!("Leaderboard :");
+    for (rank, seat) in ranking(chips).into_iter().enumerate() {
+        let entrant = &entrants[players[seat]];
+        println!(
+            "{:>3}. {:<12} seat {:<2} {:>7} chips{}{}",
+            rank + 1,
+            entrant.name,
+            seat + 1,
+            chips[seat],
+            if entrant.human { " (human)" } else { "" },
+            if rank < advance { " *" } else { "" }
+        );
+    }
+    println!();
+}
+
+/// Plays a round of a tournament at a table, and returns the
+/// players ranked by their chips at the end of the round, or None
+/// when the humans ended the tournament. Players who lose all
+/// their chips leave the table and are ranked last.
+///
+/// Bots play with the named strategy and bet to be among the
+/// `advance` first players. On the last hand of the round, bets
+/// are secret : nobody sees the bets of the other players.
+fn play_table(
+    entrants: &[Entrant],
+    players: &[usize],
+    rules: &TournamentRules,
+    advance: usize,
+    bot_strategy: &str,
+    title: &str,
+) -> Result<Option<Vec<usize>>, String> {
+    let mut players = players.to_vec();
+    let mut chips = vec![rules.chips; players.len()];
+    let mut policies = vec![BetPolicy::Tournament(TournamentBetting::default()); players.len()];
+    let mut strategies = players
+        .iter()
+        .map(|_| strategy_from_name(bot_strategy))
+        .collect::<Result<Vec<Box<dyn Strategy>>, _>>()?;
+    let has_humans = players.iter().any(|&player| entrants[player].human);
+    let mut busted = Vec::new();
+    let table_rules = Rules::default();
+    let mut pack: Vec<Card> = Vec::new();
+    refill(&mut pack, table_rules.num_packs);
+
+    for hand in 0..rules.hands {
+        if players.is_empty() {
+            break;
+        }
+        let hands_left = rules.hands - hand;
+        let limits = TableLimits {
+            secret_bets: hands_left == 1,
+            ..rules.limits.clone()
+        };
+        println!("------------------------");
+        println!(
+            "{} : hand {} of {}{}",
+            title,
+            hand + 1,
+            rules.hands,
+            if limits.secret_bets {
+                ", bets are secret"
+            } else {
+                ""
+            }
+        );
+        display_leaderboard(entrants, &players, &chips, advance);
+        for (seat, policy) in policies.iter_mut().enumerate() {
+            policy.update_standing(Standing {
+                chips: chips.clone(),
+                seat,
+                advance,
+                hands_left,
+            });
+        }
+
+        let mut player_types: Vec<PlayerType> = players
+            .iter()
+            .map(|&player| {
+                if entrants[player].human {
+                    PlayerType::Human
+                } else {
+                    PlayerType::Bot
+                }
+            })
+            .collect();
+        player_types.push(PlayerType::Dealer);
+        let names: Vec<String> = players
+            .iter()
+            .map(|&player| entrants[player].name.clone())
+            .collect();
+        let mut player_hands = vec![(Vec::new(), None); players.len()];
+        let mut dealer_hand = Vec::new();
+        let mut bets = Vec::new();
+        let played = play_round(
+            &mut player_hands,
+            &mut dealer_hand,
+            &mut pack,
+            &player_types,
+            (&mut bets, &mut chips),
+            (&mut policies, &strategies),
+            &Table {
+                limits: &limits,
+                rules: &table_rules,
+                names: &names,
+                tui: None,
+                last_bets: &[],
+                stats: &[],
+                history: &[],
+                saved: None,
+                log: None,
+                externals: &[],
+                server: None,
+            },
+        );
+        if let Err(Quit) = played {
+            return Ok(None);
+        }
+
+        let scores = compute_scores(&player_hands, &dealer_hand);
+        display_hands_and_scores(&scores, &player_hands, &dealer_hand, &names);
+        println!();
+        let bj_index = compute_blackjack_index(&player_hands, &dealer_hand);
+        let [three_two_index, winner_index, equal_index, loser_index] =
+            compute_result(scores, bj_index);
+        update_bank(
+            &three_two_index,
+            &winner_index,
+            &equal_index,
+            &mut chips,
+            &bets,
+        );
+        display_results(
+            &three_two_index,
+            &winner_index,
+            &equal_index,
+            &loser_index,
+            &names,
+        );
+
+        for seat in (0..players.len()).rev() {
+            if chips[seat] == 0 {
+                println!("{} is out of chips.", entrants[players[seat]].name);
+                busted.push(players.remove(seat));
+                chips.remove(seat);
+                policies.remove(seat);
+                strategies.remove(seat);
+            }
+        }
+        if has_humans {
+            wait_for_enter();
+        }
+    }
+
+    println!("{} is over.", title);
+    display_leaderboard(entrants, &players, &chips, advance);
+    let mut ranked: Vec<usize> = ranking(&chips)
+        .into_iter()
+        .map(|seat| players[seat])
+        .collect();
+    ranked.extend(busted.into_iter().rev());
+    Ok(Some(ranked))
+}
+
+/// Plays a whole tournament and returns the players of the final
+/// table ranked by their chips, the winner first, or None when
+/// the humans ended the tournament.
+///
+/// Each round seats the remaining players at tables of at most
+/// `table_size` players, and the `advance` first players of each
+/// table go to the next round. Once there are few enough players
+/// left, they play a final table.
+pub fn play_tournament(
+    entrants: &[Entrant],
+    rules: &TournamentRules,
+    bot_strategy: &str,
+) -> Result<Option<Vec<usize>>, String> {
+    let mut remaining: Vec<usize> = (0..entrants.len()).collect();
+    let mut round = 1;
+    while remaining.len() > rules.table_size {
+        let mut next = Vec::new();
+        for (index, table) in tables(&remaining, rules.table_size).iter().enumerate() {
+            let title = format!("Round {}, table {}", round, index + 1);
+            let ranked =
+                match play_table(entrants, table, rules, rules.advance, bot_strategy, &title)? {
+                    Some(ranked) => ranked,
+                    None => return Ok(None),
+                };
+            next.extend(ranked.into_iter().take(rules.advance));
+        }
+        next.sort_unstable();
+        remaining = next;
+        round += 1;
+    }
+    play_table(entrants, &remaining, rules, 1, bot_strategy, "Final table")
+}
+
+/// Runs the `tournament` subcommand :
+/// `tournament [--players N] [--humans H] [--table-size S] [--advance A]
+/// [--hands N] [--chips C] [--bot-strategy NAME] [limits]`.
+pub fn run(args: &Args) -> Result<(), String> {
+    args.check_options(&[
+        &[
+            "advance",
+            "bot-strategy",
+            "chips",
+            "hands",
+            "humans",
+            "players",
+            "table-size",
+        ],
+        LIMIT_OPTIONS,
+    ])?;
+    let players: usize = args.parse_or("players", 12)?;
+    let humans: usize = args.parse_or("humans", 0)?;
+    let mut limits = args.limits()?;
+    if args.get("min-bet").is_none() {
+        limits.min = limits.max.min(10);
+    }
+    let rules = TournamentRules {
+        hands: args.parse_or("hands", 10)?,
+        chips: args.parse_or("chips", 1000)?,
+        table_size: args.parse_or("table-size", 6)?,
+        advance: args.parse_or("advance", 2)?,
+        limits,
+    };
+    if players < 2 || humans > players {
+        return Err(String::from(
+            "A tournament needs at least two players, and no more humans than players",
+        ));
+    }
+    if rules.table_size < 2 || rules.advance == 0 || 2 * rules.advance > rules.table_size {
+        return Err(String::from(
+            "Tables need at least two seats, and at most half of their players may advance",
+        ));
+    }
+    if rules.hands == 0 || rules.chips < rules.limits.min {
+        return Err(String::from(
+            "Rounds need at least one hand, and players enough chips for the minimum bet",
+        ));
+    }
+    let bot_strategy = args.get("bot-strategy").unwrap_or("basic");
+    strategy_from_name(bot_strategy)?;
+
+    let entrants: Vec<Entrant> = (0..players)
+        .map(|index| Entrant {
+            name: format!("Player {}", index + 1),
+            human: index < humans,
+        })
+        .collect();
+    match play_tournament(&entrants, &rules, bot_strategy)? {
+        Some(ranked) => println!("{} wins the tournament!", entrants[ranked[0]].name),
+        None => println!("The tournament is over."),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tournament::*;
+
+    #[test]
+    fn tables_test() {
+        let players: Vec<usize> = (0..13).collect();
+        let seated = tables(&players, 6);
+        assert_eq!(vec![0, 3, 6, 9, 12], seated[0]);
+        assert_eq!(vec![2, 5, 8, 11], seated[2]);
+        assert_eq!(1, tables(&players[..6], 6).len());
+    }
+
+    #[test]
+    fn ranking_test() {
+        assert_eq!(vec![1, 0, 3, 2], ranking(&[50, 80, 10, 50]));
+    }
+
+    #[test]
+    fn bot_tournament() {
+        let entrants: Vec<Entrant> = (0..9)
+            .map(|index| Entrant {
+                name: format!("Bot {}", index),
+                human: false,
+            })
+            .collect();
+        let rules = TournamentRules {
+            hands: 3,
+            chips: 100,
+            table_size: 4,
+            advance: 2,
+            limits: TableLimits {
+                min: 10,
+                max: 100,
+                ..TableLimits::default()
+            },
+        };
+        let mut ranked = play_tournament(&entrants, &rules, "basic")
+            .unwrap()
+            .unwrap();
+        // three tables of three players, then two tables of three
+        // players, and the four players who advance play the final
+        assert_eq!(4, ranked.len());
+        ranked.sort_unstable();
+        ranked.dedup();
+        assert_eq!(4, ranked.len());
+    }
+}
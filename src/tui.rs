@@ -0,0 +1,558 @@
+use crate::betting::TableLimits;
+use crate::card::Card;
+use crate::command::{parse_command, run_command, Quit, Session};
+use crate::math::{hand_value, is_blackjack};
+use crate::player::PlayerAction;
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{read, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, size, Clear, ClearType, EnterAlternateScreen,
+    LeaveAlternateScreen,
+};
+use crossterm::{execute, queue};
+use std::cell::RefCell;
+use std::io::{stdout, Write};
+
+/// The state of the table, as drawn by the full-screen interface.
+pub struct TableView<'a> {
+    /// The names of the players, without the dealer.
+    pub names: &'a [String],
+    pub hands: &'a [(Vec<Card>, Option<Vec<Card>>)],
+    pub dealer_hand: &'a [Card],
+    /// The bets placed so far, which may be fewer than the players.
+    pub bets: &'a [u32],
+    pub bank: &'a [u32],
+    /// The player whose turn it is, and whether they
+    /// are playing their second hand.
+    pub turn: Option<(usize, bool)>,
+    /// The money won by each player, once the round is over.
+    pub nets: Option<&'a [i64]>,
+}
+
+/// The height of a card drawn as a box.
+const CARD_HEIGHT: usize = 3;
+/// The width of a card drawn as a box.
+const CARD_WIDTH: usize = 5;
+/// The height of the block of a seat : the name, two hands
+/// with their values, the bet, the bank and the result.
+const SEAT_HEIGHT: usize = 3 + 2 * (CARD_HEIGHT + 1) + 1;
+
+/// A grid of characters the table is drawn on. Text
+/// going out of the grid is cut.
+struct Canvas {
+    cells: Vec<Vec<char>>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Canvas {
+        Canvas {
+            cells: vec![vec![' '; width]; height],
+        }
+    }
+
+    fn put(&mut self, x: usize, y: usize, text: &str) {
+        if let Some(line) = self.cells.get_mut(y) {
+            for (cell, c) in line.iter_mut().skip(x).zip(text.chars()) {
+                *cell = c;
+            }
+        }
+    }
+
+    /// Puts a text centred on the column `center`.
+    fn put_centered(&mut self, center: usize, y: usize, text: &str) {
+        let x = center.saturating_sub(text.chars().count() / 2);
+        self.put(x, y, text);
+    }
+
+    fn lines(&self) -> Vec<String> {
+        self.cells
+            .iter()
+            .map(|line| line.iter().collect::<String>().trim_end().to_string())
+            .collect()
+    }
+}
+
+/// The lines of a card drawn as a box, such as
+/// ```text
+/// ┌───┐
+/// │10♥│
+/// └───┘
+/// ```
+pub fn card_box(card: &Card) -> [String; CARD_HEIGHT] {
+    [
+        String::from("┌───┐"),
+        format!("│{:>2}{}│", card.rank.to_string(), card.suit),
+        String::from("└───┘"),
+    ]
+}
+
+/// Draws a hand as overlapping boxes, centred on the column
+/// `center` and at most `width` characters wide. Hands too long for
+/// boxes are written as text, such as `A♠ 10♥ 3♣`, over three lines.
+fn put_hand(canvas: &mut Canvas, center: usize, y: usize, width: usize, cards: &[Card]) {
+    if cards.is_empty() {
+        return;
+    }
+    let step = match cards.len() {
+        1 => 0,
+        n => (width.saturating_sub(CARD_WIDTH) / (n - 1)).min(CARD_WIDTH + 1),
+    };
+    if cards.len() > 1 && step < 3 {
+        let mut lines = vec![String::new()];
+        for card in cards {
+            let text = card.to_string();
+            let line = lines.last_mut().unwrap();
+            if !line.is_empty() && line.chars().count() + 1 + text.chars().count() > width {
+                lines.push(text);
+            } else {
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                line.push_str(&text);
+            }
+        }
+        for (row, line) in lines.iter().take(CARD_HEIGHT).enumerate() {
+            canvas.put_centered(center, y + row, line);
+        }
+        return;
+    }
+    let total = step * (cards.len() - 1) + CARD_WIDTH;
+    let x = center.saturating_sub(total / 2);
+    for (index, card) in cards.iter().enumerate() {
+        for (row, line) in card_box(card).iter().enumerate() {
+            canvas.put(x + index * step, y + row, line);
+        }
+    }
+}
+
+/// The value of a hand as shown under it.
+fn hand_label(cards: &[Card]) -> String {
+    if cards.is_empty() {
+        String::new()
+    } else if is_blackjack(cards) {
+        String::from("Blackjack!")
+    } else if hand_value(cards) > 21 {
+        format!("{} bust", hand_value(cards))
+    } else {
+        hand_value(cards).to_string()
+    }
+}
+
+/// Lays the table out on a screen of the given size : the title on
+/// the first line, the dealer at the top, a message in the middle,
+/// the seats along the bottom and the prompt on the last line.
+pub fn render(
+    view: &TableView,
+    title: &str,
+    message: &str,
+    prompt: &str,
+    size: (usize, usize),
+) -> Vec<String> {
+    let (width, height) = size;
+    let mut canvas = Canvas::new(width, height);
+    let middle = width / 2;
+    canvas.put(0, 0, title);
+
+    canvas.put_centered(middle, 2, "Dealer");
+    put_hand(&mut canvas, middle, 3, width, view.dealer_hand);
+    canvas.put_centered(middle, 3 + CARD_HEIGHT, &hand_label(view.dealer_hand));
+    let message_row = 4 + CARD_HEIGHT + 1;
+    canvas.put_centered(middle, message_row, message);
+
+    let seats = view.names.len().max(1);
+    let seat_width = width / seats;
+    let top = height.saturating_sub(SEAT_HEIGHT + 2).max(message_row + 2);
+    for (index, name) in view.names.iter().enumerate() {
+        let center = index * seat_width + seat_width / 2;
+        let label = match view.turn {
+            Some((turn, _)) if turn == index => format!("> {} <", name),
+            _ => name.clone(),
+        };
+        canvas.put_centered(center, top, &label);
+        let mut y = top + 1;
+        if let Some((first, second)) = view.hands.get(index) {
+            for (is_second, hand) in [Some(first), second.as_ref()].iter().enumerate() {
+                let hand = match hand {
+                    Some(hand) => hand,
+                    None => continue,
+                };
+                put_hand(&mut canvas, center, y, seat_width.saturating_sub(2), hand);
+                let playing = view.turn == Some((index, is_second == 1)) && second.is_some();
+                let label = hand_label(hand);
+                canvas.put_centered(
+                    center,
+                    y + CARD_HEIGHT,
+                    &if playing {
+                        format!("* {} *", label)
+                    } else {
+                        label
+                    },
+                );
+                y += CARD_HEIGHT + 1;
+            }
+        }
+        let y = top + 1 + 2 * (CARD_HEIGHT + 1);
+        if let Some(bet) = view.bets.get(index) {
+            canvas.put_centered(center, y, &format!("Bet {}", bet));
+        }
+        if let Some(bank) = view.bank.get(index) {
+            canvas.put_centered(center, y + 1, &format!("Bank {}", bank));
+        }
+        if let Some(&net) = view.nets.and_then(|nets| nets.get(index)) {
+            let result = match net {
+                0 => String::from("Push"),
+                net if net > 0 => format!("Won {}", net),
+                net => format!("Lost {}", -net),
+            };
+            canvas.put_centered(center, y + 2, &result);
+        }
+    }
+
+    canvas.put(0, height.saturating_sub(1), prompt);
+    canvas.lines()
+}
+
+/// The full-screen interface of the game. The terminal is put
+/// back in its normal state when the interface is dropped.
+pub struct Tui {
+    title: RefCell<String>,
+    message: RefCell<String>,
+}
+
+impl Tui {
+    /// Switches the terminal to the full-screen interface.
+    pub fn enter() -> Result<Tui, String> {
+        enable_raw_mode().map_err(|e| format!("Cannot start the interface : {}", e))?;
+        execute!(stdout(), EnterAlternateScreen, Hide)
+            .map_err(|e| format!("Cannot start the interface : {}", e))?;
+        Ok(Tui {
+            title: RefCell::new(String::new()),
+            message: RefCell::new(String::new()),
+        })
+    }
+
+    /// Sets the title shown on the first line.
+    pub fn set_title(&self, title: String) {
+        *self.title.borrow_mut() = title;
+    }
+
+    /// Shows a message in the middle of the table until
+    /// the next message.
+    pub fn notify(&self, text: &str) {
+        *self.message.borrow_mut() = text.to_string();
+    }
+
+    fn draw(&self, view: &TableView, prompt: &str) {
+        let (width, height) = size().unwrap_or((80, 24));
+        let lines = render(
+            view,
+            &self.title.borrow(),
+            &self.message.borrow(),
+            prompt,
+            (width as usize, height as usize),
+        );
+        self.draw_lines(&lines);
+    }
+
+    fn draw_lines(&self, lines: &[String]) {
+        let mut out = stdout();
+        let _ = queue!(out, Clear(ClearType::All));
+        for (y, line) in lines.iter().enumerate() {
+            let _ = queue!(out, MoveTo(0, y as u16), Print(line));
+        }
+        let _ = out.flush();
+    }
+
+    /// Waits for a key, drawing the screen first and again whenever
+    /// the terminal is resized. Ctrl-C, like a closed input, returns
+    /// [Quit] to end the game.
+    fn key(&self, draw: &dyn Fn()) -> Result<KeyCode, Quit> {
+        draw();
+        loop {
+            match read() {
+                Ok(Event::Key(KeyEvent {
+                    code,
+                    modifiers,
+                    kind: KeyEventKind::Press,
+                    ..
+                })) => {
+                    if modifiers.contains(KeyModifiers::CONTROL) && code == KeyCode::Char('c') {
+                        return Err(Quit);
+                    }
+                    return Ok(code);
+                }
+                Ok(Event::Resize(..)) => draw(),
+                Ok(_) => {}
+                Err(_) => return Err(Quit),
+            }
+        }
+    }
+
+    /// Asks the player at `index` for their bet, starting from their
+    /// `last_bet`, or the minimum bet. The bet is typed with the digit
+    /// keys, changed by one chip with `+` and `-`, or set to the
+    /// largest bet allowed with `A` (all in). `:` types a colon
+    /// command, run with the `session`.
+    pub fn ask_bet(
+        &self,
+        view: &TableView,
+        index: usize,
+        (limits, last_bet): (&TableLimits, Option<u32>),
+        session: &Session,
+    ) -> Result<u32, Quit> {
+        let available = view.bank[index];
+        let mut bet = last_bet.unwrap_or(limits.min).min(available);
+        let mut typed = false;
+        loop {
+            let prompt = format!(
+                "{}, your bet ({} to {}, by {}) : {}_   [Enter] bet  [+/-] one chip  [A]ll in",
+                view.names[index], limits.min, limits.max, limits.chip, bet
+            );
+            match self.key(&|| self.draw(view, &prompt))? {
+                KeyCode::Char(c) if c.is_ascii_digit() => {
+                    let digit = c.to_digit(10).unwrap();
+                    bet = match typed {
+                        true => bet.saturating_mul(10).saturating_add(digit),
+                        false => digit,
+                    };
+                    typed = true;
+                }
+                KeyCode::Char('+') => bet = bet.saturating_add(limits.chip).min(available),
+                KeyCode::Char('-') => bet = bet.saturating_sub(limits.chip),
+                KeyCode::Char('a') | KeyCode::Char('A') => {
+                    bet = limits.clamp(available as f64, available);
+                    typed = false;
+                }
+                KeyCode::Char(':') => self.command(view, session)?,
+                KeyCode::Backspace => bet /= 10,
+                KeyCode::Enter if limits.allows(bet, available) => {
+                    self.notify("");
+                    return Ok(bet);
+                }
+                KeyCode::Enter => self.notify("This bet is not allowed at this table."),
+                _ => {}
+            }
+        }
+    }
+
+    /// Asks the player whose turn it is for one of the `legal` actions :
+    /// H to hit, S to stand, D to double, P to split, R to surrender.
+    /// Only the keys of the legal actions are shown and accepted,
+    /// and `:` types a colon command, run with the `session`.
+    pub fn ask_action(
+        &self,
+        view: &TableView,
+        legal: &[PlayerAction],
+        session: &Session,
+    ) -> Result<PlayerAction, Quit> {
+        let (index, is_second) = view.turn.expect("A player is playing");
+        let hints: Vec<&str> = legal
+            .iter()
+            .map(|action| match action {
+                PlayerAction::Hit => "[H]it",
+                PlayerAction::Stand => "[S]tand",
+                PlayerAction::Double => "[D]ouble",
+                PlayerAction::Split => "s[P]lit",
+                PlayerAction::Surrender => "su[R]render",
+            })
+            .collect();
+        let prompt = format!(
+            "{}{} : {}",
+            view.names[index],
+            if is_second { ", second hand" } else { "" },
+            hints.join("  ")
+        );
+        loop {
+            let key = self.key(&|| self.draw(view, &prompt))?;
+            let action = match key {
+                KeyCode::Char(':') => {
+                    self.command(view, session)?;
+                    None
+                }
+                KeyCode::Char(c) => match c.to_ascii_lowercase() {
+                    'h' => Some(PlayerAction::Hit),
+                    's' => Some(PlayerAction::Stand),
+                    'd' => Some(PlayerAction::Double),
+                    'p' => Some(PlayerAction::Split),
+                    'r' => Some(PlayerAction::Surrender),
+                    _ => None,
+                },
+                _ => None,
+            };
+            if let Some(action) = action.filter(|action| legal.contains(action)) {
+                self.notify("");
+                return Ok(action);
+            }
+        }
+    }
+
+    /// Reads a colon command typed after `:` and runs it with
+    /// [run_command], showing its text as a page. Esc, or erasing
+    /// the whole command, goes back to the prompt.
+    fn command(&self, view: &TableView, session: &Session) -> Result<(), Quit> {
+        let mut text = String::from(":");
+        loop {
+            let prompt = format!("{}_   [Enter] run  [Esc] cancel", text);
+            match self.key(&|| self.draw(view, &prompt))? {
+                KeyCode::Char(c) => text.push(c),
+                KeyCode::Backspace => {
+                    text.pop();
+                    if text.is_empty() {
+                        return Ok(());
+                    }
+                }
+                KeyCode::Esc => return Ok(()),
+                KeyCode::Enter => break,
+                _ => {}
+            }
+        }
+        match parse_command(&text).expect("The text starts with a colon") {
+            Ok(command) => {
+                let page = run_command(&command, session)?;
+                self.page(&page)
+            }
+            Err(message) => {
+                self.notify(&message);
+                Ok(())
+            }
+        }
+    }
+
+    /// Shows a page of text instead of the table until a key is pressed.
+    fn page(&self, page: &str) -> Result<(), Quit> {
+        let mut lines: Vec<String> = page.lines().map(str::to_string).collect();
+        lines.push(String::new());
+        lines.push(String::from("Press any key to go back to the table."));
+        self.key(&|| self.draw_lines(&lines))?;
+        Ok(())
+    }
+
+    /// Shows the end of a round until the player presses a key,
+    /// and returns false when they chose to quit, or pressed Ctrl-C.
+    /// `S` and `G` show the given statistics and chart of the session.
+    pub fn end_of_round(&self, view: &TableView, stats: &str, chart: &str) -> bool {
+        let prompt = "[Enter] next round  [S]tats  [G]raph  [Q]uit";
+        loop {
+            let Ok(key) = self.key(&|| self.draw(view, prompt)) else {
+                return false;
+            };
+            let page = match key {
+                KeyCode::Char(c) => match c.to_ascii_lowercase() {
+                    'q' => return false,
+                    's' => stats,
+                    'g' => chart,
+                    _ => continue,
+                },
+                KeyCode::Esc => return false,
+                KeyCode::Enter => {
+                    self.notify("");
+                    return true;
+                }
+                _ => continue,
+            };
+            if self.page(page).is_err() {
+                return false;
+            }
+        }
+    }
+
+    fn leave(&self) {
+        let _ = execute!(stdout(), LeaveAlternateScreen, Show);
+        let _ = disable_raw_mode();
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        self.leave();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::card::*;
+    use crate::tui::*;
+
+    fn card(rank: Rank, suit: Suit) -> Card {
+        Card { rank, suit }
+    }
+
+    #[test]
+    fn card_boxes() {
+        let ten = card_box(&card(Rank::Ten, Suit::Hearts));
+        assert_eq!(["┌───┐", "│10♥│", "└───┘"], ten);
+        assert_eq!("│ A♠│", card_box(&card(Rank::Ace, Suit::Spades))[1]);
+    }
+
+    #[test]
+    fn layout() {
+        let names = vec![String::from("Ann"), String::from("Bob")];
+        let hands = vec![
+            (
+                vec![
+                    card(Rank::Ace, Suit::Spades),
+                    card(Rank::King, Suit::Hearts),
+                ],
+                None,
+            ),
+            (
+                vec![
+                    card(Rank::Eight, Suit::Clubs),
+                    card(Rank::Three, Suit::Clubs),
+                ],
+                Some(vec![card(Rank::Eight, Suit::Hearts)]),
+            ),
+        ];
+        let view = TableView {
+            names: &names,
+            hands: &hands,
+            dealer_hand: &[card(Rank::Six, Suit::Diamonds)],
+            bets: &[10, 20],
+            bank: &[290, 260],
+            turn: Some((1, true)),
+            nets: None,
+        };
+        let lines = render(&view, "Round 1", "Hello", "Bob : [H]it", (40, 24));
+        assert_eq!(24, lines.len());
+        assert_eq!("Round 1", lines[0]);
+        assert_eq!("                 Dealer", lines[2]);
+        assert_eq!("                  │ 6♦│", lines[4]);
+        assert_eq!("                    6", lines[6]);
+        assert_eq!("                  Hello", lines[8]);
+        // the seats along the bottom, above the prompt
+        assert_eq!("         Ann               > Bob <", lines[10]);
+        assert_eq!("     ┌───┐ ┌───┐         ┌───┐ ┌───┐", lines[11]);
+        assert_eq!("     │ A♠│ │ K♥│         │ 8♣│ │ 3♣│", lines[12]);
+        assert_eq!("     Blackjack!              11", lines[14]);
+        assert_eq!("                            │ 8♥│", lines[16]);
+        assert_eq!("                            * 8 *", lines[18]);
+        assert_eq!("       Bet 10              Bet 20", lines[19]);
+        assert_eq!("      Bank 290            Bank 260", lines[20]);
+        assert_eq!("Bob : [H]it", lines[23]);
+    }
+
+    #[test]
+    fn results_and_long_hands() {
+        let names = vec![String::from("Ann")];
+        let hand: Vec<Card> = (0..8)
+            .map(|r| card(Rank::from_int(r), Suit::Clubs))
+            .collect();
+        let hands = vec![(hand, None)];
+        let view = TableView {
+            names: &names,
+            hands: &hands,
+            dealer_hand: &[],
+            bets: &[10],
+            bank: &[280],
+            turn: None,
+            nets: Some(&[-10]),
+        };
+        let lines = render(&view, "", "", "", (20, 24));
+        assert_eq!("  2♣ 3♣ 4♣ 5♣ 6♣ 7♣", lines[11]);
+        assert_eq!("        8♣ 9♣", lines[12]);
+        assert_eq!("       44 bust", lines[14]);
+        assert_eq!("       Lost 10", lines[21]);
+    }
+}
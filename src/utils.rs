@@ -1,52 +1,93 @@
-use crate::math::NUM_PACKS;
+use crate::rules::Rules;
 use crate::Card;
 
 use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::cell::RefCell;
 
-/// Initializes the card deck to a few card packs, shuffles it
-/// and gives a starting fund to the bank of each player.
+thread_local! {
+    /// The generator shuffling the decks of the game, seeded
+    /// randomly unless [seed_shuffles] is called.
+    static SHUFFLER: RefCell<ChaCha8Rng> = RefCell::new(ChaCha8Rng::from_entropy());
+}
+
+/// Seeds the shuffles of the decks of the current thread,
+/// so that the same seed deals the same cards.
+pub fn seed_shuffles(seed: u64) {
+    SHUFFLER.with(|rng| *rng.borrow_mut() = ChaCha8Rng::seed_from_u64(seed));
+}
+
+/// The generator of the shuffles, in its current state.
+pub fn shuffler() -> ChaCha8Rng {
+    SHUFFLER.with(|rng| rng.borrow().clone())
+}
+
+/// Puts the generator of the shuffles back in a state
+/// given by [shuffler], to deal the same cards again.
+pub fn set_shuffler(state: ChaCha8Rng) {
+    SHUFFLER.with(|rng| *rng.borrow_mut() = state);
+}
+
+/// Initializes the card deck to the packs of the rules, shuffles
+/// it and gives a starting fund to the bank of each player.
 pub fn init_game(
     player_hands: &mut Vec<(Vec<Card>, Option<Vec<Card>>)>,
     pack: &mut Vec<Card>,
     bank: &mut Vec<u32>,
     num_players: usize,
+    bankroll: u32,
+    rules: &Rules,
 ) {
-    for _ in 0..NUM_PACKS {
-        pack.extend(Card::card_pack());
-    }
-    pack.shuffle(&mut thread_rng());
+    refill(pack, rules.num_packs);
 
     for _ in 0..num_players {
         player_hands.push((Vec::new(), None));
-        bank.push(300);
+        bank.push(bankroll);
     }
 }
 
 /// Picks a card from the deck. If the deck is empty,
 /// replaces it with a fresh shuffled deck and picks a card
 /// from it.
-pub fn pick_card(pack: &mut Vec<Card>) -> Card {
+pub fn pick_card(pack: &mut Vec<Card>, num_packs: usize) -> Card {
     match pack.pop() {
         Some(card) => card,
         None => {
-            //reshuffle
-            for _ in 0..NUM_PACKS {
-                pack.extend(Card::card_pack());
-            }
-            pack.shuffle(&mut thread_rng());
+            refill(pack, num_packs);
             pack.pop().unwrap()
         }
     }
 }
 
+/// Fills the deck with a few shuffled card packs.
+pub fn refill(pack: &mut Vec<Card>, num_packs: usize) {
+    for _ in 0..num_packs {
+        pack.extend(Card::card_pack());
+    }
+    SHUFFLER.with(|rng| pack.shuffle(&mut *rng.borrow_mut()));
+}
+
+/// Replaces the deck with a fresh shuffled one once the
+/// penetration of the rules has been dealt. Called between rounds.
+/// Returns whether the deck was replaced.
+pub fn reshuffle_if_needed(pack: &mut Vec<Card>, rules: &Rules) -> bool {
+    let size = (rules.num_packs * 52) as f64;
+    let needed = (pack.len() as f64) < size * (1.0 - rules.penetration);
+    if needed {
+        pack.clear();
+        refill(pack, rules.num_packs);
+    }
+    needed
+}
+
 /// Adds rewards to the bank of players
 /// that blackjacked, won or reached equality, according to their bets.
 pub fn update_bank(
     ttt: &[(usize, bool)],
     win: &[(usize, bool)],
     equ: &[(usize, bool)],
-    bank: &mut Vec<u32>,
+    bank: &mut [u32],
     bets: &[u32],
 ) {
     for &(index, _) in ttt {
@@ -60,6 +101,53 @@ pub fn update_bank(
     }
 }
 
+/// Computes the money won by each player during the round
+/// (negative when they lost money), from the blackjacked, won
+/// and lost hands of [crate::math::compute_result] and the bets.
+pub fn net_results(
+    ttt: &[(usize, bool)],
+    win: &[(usize, bool)],
+    los: &[(usize, bool)],
+    bets: &[u32],
+) -> Vec<i64> {
+    let mut nets = vec![0; bets.len()];
+    for &(index, _) in ttt {
+        nets[index] += (3 * bets[index] as i64) >> 1;
+    }
+    for &(index, _) in win {
+        nets[index] += bets[index] as i64;
+    }
+    for &(index, _) in los {
+        nets[index] -= bets[index] as i64;
+    }
+    nets
+}
+
+/// Settles the hands of the players who surrendered as losses :
+/// moves them to the lost hands of the `results` of
+/// [crate::math::compute_result], and returns the bets at stake, in
+/// which a surrendered hand only loses half of its bet, rounded up,
+/// unless the dealer has a blackjack, which takes the whole bet.
+pub fn settle_surrenders(
+    results: &mut [Vec<(usize, bool)>; 4],
+    bets: &[u32],
+    surrendered: &[bool],
+    dealer_blackjack: bool,
+) -> Vec<u32> {
+    let mut stakes = bets.to_vec();
+    for (index, _) in surrendered.iter().enumerate().filter(|(_, &s)| s) {
+        for hands in results.iter_mut() {
+            hands.retain(|&(seat, _)| seat != index);
+        }
+        results[3].push((index, false));
+        if !dealer_blackjack {
+            stakes[index] -= bets[index] / 2;
+        }
+    }
+    results[3].sort_unstable();
+    stakes
+}
+
 /// Checks whether all players have some funds to keep playing.
 pub fn is_playable(bank: &[u32]) -> bool {
     for &b in bank {
@@ -78,7 +166,7 @@ mod tests {
         let mut hands = vec![];
         let mut pack = vec![];
         let mut bank = vec![];
-        init_game(&mut hands, &mut pack, &mut bank, 5);
+        init_game(&mut hands, &mut pack, &mut bank, 5, 300, &Rules::default());
         assert_eq!(5, hands.len());
         assert_eq!(4 * 52, pack.len());
         assert_eq!(vec!(300; 5), bank);
@@ -87,23 +175,76 @@ mod tests {
     #[test]
     fn pick_test() {
         let mut pack = vec![];
-        let _ = pick_card(&mut pack);
+        let _ = pick_card(&mut pack, 4);
         assert!(pack.len() == 4 * 52 - 1);
-        let _ = pick_card(&mut pack);
+        let _ = pick_card(&mut pack, 4);
         assert!(pack.len() == 4 * 52 - 2);
     }
 
+    #[test]
+    fn seeded_shuffles() {
+        let deal = |seed| {
+            seed_shuffles(seed);
+            let mut pack = vec![];
+            refill(&mut pack, 1);
+            pack
+        };
+        assert_eq!(deal(7), deal(7));
+        assert_ne!(deal(7), deal(8));
+    }
+
+    #[test]
+    fn reshuffle_test() {
+        let rules = Rules {
+            num_packs: 1,
+            penetration: 0.5,
+            ..Rules::default()
+        };
+        let mut pack = vec![];
+        refill(&mut pack, 1);
+        pack.truncate(26);
+        reshuffle_if_needed(&mut pack, &rules);
+        assert_eq!(26, pack.len());
+        pack.pop();
+        reshuffle_if_needed(&mut pack, &rules);
+        assert_eq!(52, pack.len());
+    }
+
     #[test]
     fn update_bank_test() {
         let mut bank = vec![300, 300];
         let bets = vec![300, 300];
         let win = vec![(0, false)];
         let equ = vec![(1, false)];
-        update_bank(&vec![], &win, &equ, &mut bank, &bets);
+        update_bank(&[], &win, &equ, &mut bank, &bets);
 
         assert_eq!(vec!(900, 600), bank);
     }
 
+    #[test]
+    fn net_results_test() {
+        let bets = vec![10, 20, 30, 5];
+        let ttt = vec![(3, false)];
+        let win = vec![(0, false), (0, true)];
+        let los = vec![(2, false)];
+        assert_eq!(vec![20, 0, -30, 7], net_results(&ttt, &win, &los, &bets));
+    }
+
+    #[test]
+    fn settle_surrenders_test() {
+        let bets = vec![10, 25, 30];
+        let mut results = [vec![], vec![(0, false)], vec![(1, false)], vec![(2, false)]];
+        let stakes = settle_surrenders(&mut results, &bets, &[false, true, false], false);
+        assert_eq!(vec![10, 13, 30], stakes);
+        assert_eq!(vec![(1, false), (2, false)], results[3]);
+        assert!(results[2].is_empty());
+
+        let mut results = [vec![], vec![], vec![], vec![(0, false), (1, false)]];
+        let stakes = settle_surrenders(&mut results, &bets[..2], &[true, false], true);
+        assert_eq!(vec![10, 25], stakes);
+        assert_eq!(vec![(0, false), (1, false)], results[3]);
+    }
+
     #[test]
     fn is_playable_test() {
         let bank1 = vec![300, 300, 22];